@@ -1,3 +1,4 @@
+use std::fmt;
 use std::fs::{File, create_dir_all};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -5,6 +6,43 @@ use std::str::FromStr;
 
 use serde::Serialize;
 
+/// Errors which might happen when the results of an analysis are written.
+#[derive(Debug)]
+pub enum FormatError {
+    /// Output error.
+    ///
+    /// The output file, or one of its parent directories, cannot be
+    /// created or written.
+    Io(PathBuf, std::io::Error),
+    /// Stdout error.
+    ///
+    /// The results cannot be printed on stdout.
+    Stdout(std::io::Error),
+    /// Serialization error.
+    ///
+    /// The results cannot be represented in the requested format.
+    Serialization(&'static str, String),
+    /// Unsupported output error.
+    ///
+    /// The requested format cannot be printed on stdout.
+    UnsupportedStdout(&'static str),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "cannot write {}: {err}", path.display()),
+            Self::Stdout(err) => write!(f, "cannot write on stdout: {err}"),
+            Self::Serialization(format, err) => write!(f, "cannot serialize as {format}: {err}"),
+            Self::UnsupportedStdout(format) => {
+                write!(f, "{format} format cannot be printed to stdout")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
 #[derive(Debug, Clone)]
 pub enum Format {
     Cbor,
@@ -18,13 +56,18 @@ impl Format {
         &["cbor", "json", "toml", "yaml"]
     }
 
+    /// Checks whether the format can be printed on stdout.
+    pub fn is_printable(&self) -> bool {
+        !matches!(self, Self::Cbor)
+    }
+
     pub fn dump_formats<T: Serialize>(
         &self,
         space: T,
         path: PathBuf,
         output_path: Option<&PathBuf>,
         pretty: bool,
-    ) {
+    ) -> Result<(), FormatError> {
         if let Some(output_path) = output_path {
             match self {
                 Self::Cbor => Cbor::with_writer(space, path, output_path),
@@ -37,7 +80,7 @@ impl Format {
                 Self::Json => Json::write_on_stdout_pretty(space, pretty),
                 Self::Toml => Toml::write_on_stdout_pretty(space, pretty),
                 Self::Yaml => Yaml::write_on_stdout(space),
-                Self::Cbor => panic!("Cbor format cannot be printed to stdout"),
+                Self::Cbor => Err(FormatError::UnsupportedStdout(Cbor::NAME)),
             }
         }
     }
@@ -58,28 +101,37 @@ impl FromStr for Format {
 }
 
 #[inline(always)]
-fn print_on_stdout(content: String) {
-    writeln!(std::io::stdout().lock(), "{content}").unwrap();
+fn print_on_stdout(content: String) -> Result<(), FormatError> {
+    writeln!(std::io::stdout().lock(), "{content}").map_err(FormatError::Stdout)
 }
 
-trait WriteOnStdout {
+trait FormatName {
+    const NAME: &'static str;
+
+    #[inline(always)]
+    fn serialization_error<E: fmt::Display>(err: E) -> FormatError {
+        FormatError::Serialization(Self::NAME, err.to_string())
+    }
+}
+
+trait WriteOnStdout: FormatName {
     #[inline(always)]
-    fn write_on_stdout<T: Serialize>(content: T) {
-        print_on_stdout(Self::format(content));
+    fn write_on_stdout<T: Serialize>(content: T) -> Result<(), FormatError> {
+        print_on_stdout(Self::format(content)?)
     }
 
-    fn format<T: Serialize>(content: T) -> String;
+    fn format<T: Serialize>(content: T) -> Result<String, FormatError>;
 }
 
 trait WritePrettyOnStdout: WriteOnStdout {
-    fn write_on_stdout_pretty<T: Serialize>(content: T, pretty: bool) {
+    fn write_on_stdout_pretty<T: Serialize>(content: T, pretty: bool) -> Result<(), FormatError> {
         print_on_stdout(if pretty {
-            Self::format_pretty(content)
+            Self::format_pretty(content)?
         } else {
-            Self::format(content)
-        });
+            Self::format(content)?
+        })
     }
-    fn format_pretty<T: Serialize>(content: T) -> String;
+    fn format_pretty<T: Serialize>(content: T) -> Result<String, FormatError>;
 }
 
 fn handle_path(path: PathBuf, output_path: &Path, extension: &str) -> PathBuf {
@@ -90,11 +142,11 @@ fn handle_path(path: PathBuf, output_path: &Path, extension: &str) -> PathBuf {
     let path = path.strip_prefix("./").unwrap_or(path);
 
     // Replace .. with . to keep files inside the output folder
-    let cleaned_path: Vec<&str> = path
+    let cleaned_path: Vec<_> = path
         .iter()
         .map(|os_str| {
-            let s_str = os_str.to_str().unwrap();
-            if s_str == ".." { "." } else { s_str }
+            let s_str = os_str.to_string_lossy();
+            if s_str == ".." { ".".into() } else { s_str }
         })
         .collect();
 
@@ -105,20 +157,28 @@ fn handle_path(path: PathBuf, output_path: &Path, extension: &str) -> PathBuf {
     output_path.join(filename)
 }
 
-trait WriteFile {
+trait WriteFile: FormatName {
     const EXTENSION: &'static str;
 
-    fn open_file(path: PathBuf, output_path: &Path) -> File {
+    fn write_file(content: &[u8], path: PathBuf, output_path: &Path) -> Result<(), FormatError> {
         // Handle output path
         let format_path = handle_path(path, output_path, Self::EXTENSION);
 
         // Create directories
-        create_dir_all(format_path.parent().unwrap()).unwrap();
+        if let Some(parent) = format_path.parent() {
+            create_dir_all(parent).map_err(|e| FormatError::Io(parent.to_path_buf(), e))?;
+        }
 
-        File::create(format_path).unwrap()
+        File::create(&format_path)
+            .and_then(|mut file| file.write_all(content))
+            .map_err(|e| FormatError::Io(format_path, e))
     }
 
-    fn with_writer<T: Serialize>(content: T, path: PathBuf, output_path: &Path);
+    fn with_writer<T: Serialize>(
+        content: T,
+        path: PathBuf,
+        output_path: &Path,
+    ) -> Result<(), FormatError>;
 }
 
 trait WritePrettyFile: WriteFile {
@@ -127,28 +187,36 @@ trait WritePrettyFile: WriteFile {
         path: PathBuf,
         output_path: &Path,
         pretty: bool,
-    );
+    ) -> Result<(), FormatError>;
 }
 
 struct Json;
 
+impl FormatName for Json {
+    const NAME: &'static str = "json";
+}
+
 impl WriteOnStdout for Json {
-    fn format<T: Serialize>(content: T) -> String {
-        serde_json::to_string(&content).unwrap()
+    fn format<T: Serialize>(content: T) -> Result<String, FormatError> {
+        serde_json::to_string(&content).map_err(Self::serialization_error)
     }
 }
 
 impl WritePrettyOnStdout for Json {
-    fn format_pretty<T: Serialize>(content: T) -> String {
-        serde_json::to_string_pretty(&content).unwrap()
+    fn format_pretty<T: Serialize>(content: T) -> Result<String, FormatError> {
+        serde_json::to_string_pretty(&content).map_err(Self::serialization_error)
     }
 }
 
 impl WriteFile for Json {
     const EXTENSION: &'static str = ".json";
 
-    fn with_writer<T: Serialize>(content: T, path: PathBuf, output_path: &Path) {
-        serde_json::to_writer(Self::open_file(path, output_path), &content).unwrap()
+    fn with_writer<T: Serialize>(
+        content: T,
+        path: PathBuf,
+        output_path: &Path,
+    ) -> Result<(), FormatError> {
+        Self::write_file(Self::format(content)?.as_bytes(), path, output_path)
     }
 }
 
@@ -158,36 +226,42 @@ impl WritePrettyFile for Json {
         path: PathBuf,
         output_path: &Path,
         pretty: bool,
-    ) {
+    ) -> Result<(), FormatError> {
         if pretty {
-            serde_json::to_writer_pretty(Self::open_file(path, output_path), &content).unwrap();
+            Self::write_file(Self::format_pretty(content)?.as_bytes(), path, output_path)
         } else {
-            Self::with_writer(content, path, output_path);
+            Self::with_writer(content, path, output_path)
         }
     }
 }
 
 struct Toml;
 
+impl FormatName for Toml {
+    const NAME: &'static str = "toml";
+}
+
 impl WriteOnStdout for Toml {
-    fn format<T: Serialize>(content: T) -> String {
-        toml::to_string(&content).unwrap()
+    fn format<T: Serialize>(content: T) -> Result<String, FormatError> {
+        toml::to_string(&content).map_err(Self::serialization_error)
     }
 }
 
 impl WritePrettyOnStdout for Toml {
-    fn format_pretty<T: Serialize>(content: T) -> String {
-        toml::to_string_pretty(&content).unwrap()
+    fn format_pretty<T: Serialize>(content: T) -> Result<String, FormatError> {
+        toml::to_string_pretty(&content).map_err(Self::serialization_error)
     }
 }
 
 impl WriteFile for Toml {
     const EXTENSION: &'static str = ".toml";
 
-    fn with_writer<T: Serialize>(content: T, path: PathBuf, output_path: &Path) {
-        Self::open_file(path, output_path)
-            .write_all(Self::format(content).as_bytes())
-            .unwrap();
+    fn with_writer<T: Serialize>(
+        content: T,
+        path: PathBuf,
+        output_path: &Path,
+    ) -> Result<(), FormatError> {
+        Self::write_file(Self::format(content)?.as_bytes(), path, output_path)
     }
 }
 
@@ -197,39 +271,54 @@ impl WritePrettyFile for Toml {
         path: PathBuf,
         output_path: &Path,
         pretty: bool,
-    ) {
+    ) -> Result<(), FormatError> {
         if pretty {
-            Self::open_file(path, output_path)
-                .write_all(Self::format_pretty(&content).as_bytes())
-                .unwrap();
+            Self::write_file(Self::format_pretty(content)?.as_bytes(), path, output_path)
         } else {
-            Self::with_writer(content, path, output_path);
+            Self::with_writer(content, path, output_path)
         }
     }
 }
 
 struct Yaml;
 
+impl FormatName for Yaml {
+    const NAME: &'static str = "yaml";
+}
+
 impl WriteOnStdout for Yaml {
-    fn format<T: Serialize>(content: T) -> String {
-        serde_yaml::to_string(&content).unwrap()
+    fn format<T: Serialize>(content: T) -> Result<String, FormatError> {
+        serde_yaml::to_string(&content).map_err(Self::serialization_error)
     }
 }
 
 impl WriteFile for Yaml {
     const EXTENSION: &'static str = ".yml";
 
-    fn with_writer<T: Serialize>(content: T, path: PathBuf, output_path: &Path) {
-        serde_yaml::to_writer(Self::open_file(path, output_path), &content).unwrap()
+    fn with_writer<T: Serialize>(
+        content: T,
+        path: PathBuf,
+        output_path: &Path,
+    ) -> Result<(), FormatError> {
+        Self::write_file(Self::format(content)?.as_bytes(), path, output_path)
     }
 }
 
 struct Cbor;
 
+impl FormatName for Cbor {
+    const NAME: &'static str = "cbor";
+}
+
 impl WriteFile for Cbor {
     const EXTENSION: &'static str = ".cbor";
 
-    fn with_writer<T: Serialize>(content: T, path: PathBuf, output_path: &Path) {
-        serde_cbor::to_writer(Self::open_file(path, output_path), &content).unwrap()
+    fn with_writer<T: Serialize>(
+        content: T,
+        path: PathBuf,
+        output_path: &Path,
+    ) -> Result<(), FormatError> {
+        let content = serde_cbor::to_vec(&content).map_err(Self::serialization_error)?;
+        Self::write_file(&content, path, output_path)
     }
 }
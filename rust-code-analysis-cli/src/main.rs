@@ -1,47 +1,71 @@
 mod formats;
+mod stats;
 
 use std::cmp::Ordering;
 use std::collections::{HashMap, hash_map};
+use std::fmt::Display;
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{self, AtomicUsize};
 use std::sync::{Arc, Mutex};
 use std::thread::available_parallelism;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
 use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{ArgGroup, Parser};
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Serialize;
 
-use formats::Format;
+use formats::{Format, FormatError};
+use stats::{Analyzed, ProgressPrinter, print_summary};
 
 // Enums
-use rust_code_analysis::LANG;
+use rust_code_analysis::{FileError, GraphFormat, HistogramSort, LANG};
+
+// Constants
+use rust_code_analysis::DEFAULT_CLONE_MIN_TOKENS;
 
 // Structs
 use rust_code_analysis::{
-    CommentRm, CommentRmCfg, ConcurrentRunner, Count, CountCfg, Dump, DumpCfg, FilesData, Find,
-    FindCfg, Function, FunctionCfg, Metrics, MetricsCfg, OpsCfg, OpsCode, PreprocParser,
-    PreprocResults,
+    ArchitectureRules, CallGraph, CallGraphBuilder, Churn, CloneCollect, CloneDetector, ClonesCfg,
+    CommentRm, CommentRmCfg, CommentsCfg, CommentsData, CommentsDump, CompileCommand,
+    ConcurrentRunner, Count, CountCfg, CoverageReport, CoverageSummary, Dump, DumpCfg, FilesData,
+    Find, FindCfg, FindData, FuncSpace, Function, FunctionCfg, FunctionData, HistogramCfg,
+    HistogramCount, Histograms, Hotspots, ImportsCfg, ImportsCollect, IncludeGraph, IncludePaths,
+    KeptComments, MacroExpansionCfg, MarkersCfg, MarkersData, MarkersDump, MetricsOpts,
+    ModuleGraph, ModuleGraphBuilder, OpsCfg, OpsCode, Packages, PreprocParser, PreprocResults,
+    RunProgress, SkipCfg,
 };
 
 // Functions
 use rust_code_analysis::{
-    action, fix_includes, get_from_ext, get_function_spaces, get_ops, get_vue_metrics,
-    guess_language, preprocess, read_file, read_file_with_eol, write_file,
+    action, check_parse_limits, check_query, dump_root, file_calls, fix_includes, get_from_ext,
+    get_vue_metrics, guess_language, is_query, metrics_with_opts, operands_and_operators,
+    preprocess, read_file, read_file_with_eol, write_file,
 };
 
 // Traits
-use rust_code_analysis::ParserTrait;
+use rust_code_analysis::{Callback, ParserTrait};
 
 #[derive(Debug)]
 struct Config {
     dump: bool,
     in_place: bool,
     comments: bool,
+    dry_run: bool,
+    kept_comments: KeptComments,
+    extract_comments: bool,
+    markers: bool,
+    marker: Vec<String>,
     find_filter: Vec<String>,
     count_filter: Vec<String>,
     language: Option<LANG>,
     function: bool,
     metrics: bool,
+    metrics_opts: MetricsOpts,
+    paths: Vec<PathBuf>,
+    coverage_lock: Option<(Arc<CoverageReport>, Arc<Mutex<CoverageSummary>>)>,
     ops: bool,
     output_format: Option<Format>,
     output: Option<PathBuf>,
@@ -51,6 +75,13 @@ struct Config {
     preproc_lock: Option<Arc<Mutex<PreprocResults>>>,
     preproc: Option<Arc<PreprocResults>>,
     count_lock: Option<Arc<Mutex<Count>>>,
+    histogram_lock: Option<Arc<Mutex<Histograms>>>,
+    clones_lock: Option<Arc<Mutex<CloneDetector>>>,
+    call_graph_lock: Option<Arc<Mutex<CallGraphBuilder>>>,
+    module_graph_lock: Option<Arc<Mutex<ModuleGraphBuilder>>>,
+    packages: bool,
+    hotspots_lock: Option<(Arc<Churn>, Arc<Mutex<Hotspots>>)>,
+    output_errors: Arc<AtomicUsize>,
 }
 
 fn mk_globset(elems: Vec<String>) -> GlobSet {
@@ -67,11 +98,104 @@ fn mk_globset(elems: Vec<String>) -> GlobSet {
     globset.build().map_or(GlobSet::empty(), |globset| globset)
 }
 
-fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<()> {
+fn report_output_error(res: Result<(), FormatError>, path: &Path, cfg: &Config) {
+    if let Err(e) = res {
+        eprintln!("Error: {e} for file {path:?}");
+        cfg.output_errors.fetch_add(1, atomic::Ordering::Relaxed);
+    }
+}
+
+// The graphs which can be exported in any graph format.
+trait ExportGraph: Serialize {
+    fn dot(&self) -> String;
+    fn graphml(&self) -> String;
+
+    fn export(&self, format: GraphFormat) -> serde_json::Result<String> {
+        match format {
+            GraphFormat::Dot => Ok(self.dot()),
+            GraphFormat::Graphml => Ok(self.graphml()),
+            GraphFormat::Json => serde_json::to_string_pretty(self),
+        }
+    }
+}
+
+macro_rules! impl_export_graph {
+    ($($graph:ty),+) => {
+        $(
+            impl ExportGraph for $graph {
+                fn dot(&self) -> String {
+                    self.to_dot()
+                }
+
+                fn graphml(&self) -> String {
+                    self.to_graphml()
+                }
+            }
+        )+
+    };
+}
+
+impl_export_graph!(CallGraph, ModuleGraph, IncludeGraph);
+
+// Writes a graph built from all the files to `<output>/<name>.<extension>`,
+// or prints it when there is no output directory.
+fn write_graph<G: ExportGraph>(
+    graph: &G,
+    format: GraphFormat,
+    name: &str,
+    opts: &Opts,
+    output_errors: &AtomicUsize,
+) {
+    match graph.export(format) {
+        Ok(data) => {
+            if let Some(output) = &opts.output {
+                let path = output.join(format!("{name}.{}", format.extension()));
+                if let Err(e) = write_file(&path, data.as_bytes()) {
+                    eprintln!("Error: cannot write {path:?}: {e}");
+                    output_errors.fetch_add(1, atomic::Ordering::Relaxed);
+                }
+            } else {
+                print!("{data}");
+            }
+        }
+        Err(e) => {
+            eprintln!(
+                "Error: cannot serialize the {}: {e}",
+                name.replace('_', " ")
+            );
+            output_errors.fetch_add(1, atomic::Ordering::Relaxed);
+        }
+    }
+}
+
+// Writes a summary of all the files to `<output>/<name>` in the output format,
+// or prints it when there is no output format.
+fn write_summary<S: Serialize + Display>(
+    summary: &S,
+    name: &str,
+    opts: &Opts,
+    output_errors: &AtomicUsize,
+) {
+    if let Some(output_format) = &opts.output_format {
+        let path = PathBuf::from(name);
+        if let Err(e) =
+            output_format.dump_formats(summary, path.clone(), opts.output.as_ref(), opts.pretty)
+        {
+            eprintln!("Error: {e} for file {path:?}");
+            output_errors.fetch_add(1, atomic::Ordering::Relaxed);
+        }
+    } else {
+        print!("{summary}");
+    }
+}
+
+// Returns the language of the analyzed file, if any,
+// and whether its parsing failed.
+fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<Option<Analyzed>> {
     let source = if let Some(source) = read_file_with_eol(&path)? {
         source
     } else {
-        return Ok(());
+        return Ok(None);
     };
 
     // Check if this is a Vue file
@@ -85,89 +209,291 @@ fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<()> {
         // For Vue files, we'll handle them specially below
         LANG::Html // Placeholder, won't be used
     } else {
-        return Ok(());
+        return Ok(None);
+    };
+
+    let acfg = AnalyzeCfg {
+        is_vue_file,
+        source: &source,
+        path: &path,
+        cfg,
     };
+    let has_error = action::<Analyze>(&language, source.clone(), &path, cfg.preproc.clone(), acfg)?;
+
+    // The sections of a Vue file are parsed on their own
+    Ok(Some(if is_vue_file {
+        Analyzed {
+            language: "vue",
+            parse_failed: false,
+        }
+    } else {
+        Analyzed {
+            language: language.get_name(),
+            parse_failed: has_error,
+        }
+    }))
+}
+
+// Runs all the modes on a file, which is parsed only once,
+// and returns whether its syntax tree contains some errors.
+struct Analyze<'a>(PhantomData<&'a ()>);
+
+struct AnalyzeCfg<'a> {
+    is_vue_file: bool,
+    // The source of the file, before any macro expansion
+    source: &'a [u8],
+    path: &'a Path,
+    cfg: &'a Config,
+}
+
+impl<'a> Callback for Analyze<'a> {
+    type Res = std::io::Result<bool>;
+    type Cfg = AnalyzeCfg<'a>;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        act_on_language(parser, &cfg)?;
+        Ok(parser.get_root().has_error())
+    }
+}
+
+fn act_on_language<T: ParserTrait>(parser: &T, acfg: &AnalyzeCfg) -> std::io::Result<()> {
+    let AnalyzeCfg {
+        is_vue_file,
+        source,
+        path,
+        cfg,
+    } = *acfg;
+
+    // The metrics are computed once for all the modes needing them
+    let space = if is_vue_file {
+        None
+    } else if cfg.metrics || cfg.call_graph_lock.is_some() || cfg.hotspots_lock.is_some() {
+        // The test directories are looked for below the analyzed path
+        let opts = match cfg.paths.iter().find(|root| path.starts_with(root)) {
+            Some(root) => cfg.metrics_opts.clone().root(root),
+            None => cfg.metrics_opts.clone(),
+        };
+        metrics_with_opts(parser, path, &opts)
+    } else {
+        None
+    };
+
+    // The analysis stops between its phases once the time budget
+    // of the file is exhausted, the error being reported by the runner
+    if check_parse_limits().is_err() {
+        return Ok(());
+    }
+    collect(parser, space.as_ref(), acfg)?;
+    if check_parse_limits().is_err() {
+        return Ok(());
+    }
 
-    let pr = cfg.preproc.clone();
     if cfg.dump {
         let cfg = DumpCfg {
             line_start: cfg.line_start,
             line_end: cfg.line_end,
         };
-        action::<Dump>(&language, source, &path, pr, cfg)
+        Dump::call(cfg, parser)
     } else if cfg.metrics {
-        if let Some(output_format) = &cfg.output_format {
-            // Handle Vue files specially
-            let space = if is_vue_file {
-                get_vue_metrics(source, &path)
-            } else {
-                get_function_spaces(&language, source, &path, pr)
-            };
+        // Handle Vue files specially
+        let space = if is_vue_file {
+            get_vue_metrics(source.to_vec(), path)
+        } else {
+            space
+        };
 
-            if let Some(space) = space {
-                output_format.dump_formats(space, path, cfg.output.as_ref(), cfg.pretty);
+        if let Some(mut space) = space {
+            if let Some((report, summary)) = &cfg.coverage_lock {
+                match report.file(path) {
+                    Ok(Some(coverage)) => coverage.apply(&mut space),
+                    Ok(None) => {}
+                    Err(e) => return Err(std::io::Error::other(e)),
+                }
+                summary.lock().unwrap().add(path, &space);
+            }
+            if let Some(output_format) = &cfg.output_format {
+                let res = output_format.dump_formats(
+                    space,
+                    path.to_path_buf(),
+                    cfg.output.as_ref(),
+                    cfg.pretty,
+                );
+                report_output_error(res, path, cfg);
+            } else {
+                dump_root(&space)?;
             }
-            Ok(())
-        } else {
-            let cfg = MetricsCfg { path };
-            let path = cfg.path.clone();
-            action::<Metrics>(&language, source, &path, pr, cfg)
         }
+        Ok(())
     } else if cfg.ops {
         if let Some(output_format) = &cfg.output_format {
-            let ops = get_ops(&language, source, &path, pr).unwrap();
-            output_format.dump_formats(ops, path, cfg.output.as_ref(), cfg.pretty);
+            if let Some(ops) = operands_and_operators(parser, path) {
+                let res = output_format.dump_formats(
+                    ops,
+                    path.to_path_buf(),
+                    cfg.output.as_ref(),
+                    cfg.pretty,
+                );
+                report_output_error(res, path, cfg);
+            }
             Ok(())
         } else {
-            let cfg = OpsCfg { path };
-            let path = cfg.path.clone();
-            action::<OpsCode>(&language, source, &path, pr, cfg)
+            let cfg = OpsCfg {
+                path: path.to_path_buf(),
+            };
+            OpsCode::call(cfg, parser)
         }
     } else if cfg.comments {
         let cfg = CommentRmCfg {
             in_place: cfg.in_place,
-            path,
+            dry_run: cfg.dry_run,
+            kept: cfg.kept_comments,
+            path: path.to_path_buf(),
         };
-        let path = cfg.path.clone();
-        if language == LANG::Cpp {
-            action::<CommentRm>(&LANG::Ccomment, source, &path, pr, cfg)
+        CommentRm::call(cfg, parser)
+    } else if cfg.function {
+        let fcfg = FunctionCfg {
+            path: path.to_path_buf(),
+        };
+        if let Some(output_format) = &cfg.output_format {
+            let functions = FunctionData::call(fcfg, parser);
+            let res = output_format.dump_formats(
+                functions,
+                path.to_path_buf(),
+                cfg.output.as_ref(),
+                cfg.pretty,
+            );
+            report_output_error(res, path, cfg);
+            Ok(())
         } else {
-            action::<CommentRm>(&language, source, &path, pr, cfg)
+            Function::call(fcfg, parser)
+        }
+    } else if cfg.extract_comments {
+        let ccfg = CommentsCfg {
+            path: path.to_path_buf(),
+        };
+        if let Some(output_format) = &cfg.output_format {
+            let comments = CommentsData::call(ccfg, parser);
+            let res = output_format.dump_formats(
+                comments,
+                path.to_path_buf(),
+                cfg.output.as_ref(),
+                cfg.pretty,
+            );
+            report_output_error(res, path, cfg);
+            Ok(())
+        } else {
+            CommentsDump::call(ccfg, parser)
+        }
+    } else if cfg.markers {
+        let mcfg = MarkersCfg {
+            path: path.to_path_buf(),
+            markers: cfg.marker.clone(),
+        };
+        if let Some(output_format) = &cfg.output_format {
+            let markers = MarkersData::call(mcfg, parser);
+            let res = output_format.dump_formats(
+                markers,
+                path.to_path_buf(),
+                cfg.output.as_ref(),
+                cfg.pretty,
+            );
+            report_output_error(res, path, cfg);
+            Ok(())
+        } else {
+            MarkersDump::call(mcfg, parser)
         }
-    } else if cfg.function {
-        let cfg = FunctionCfg { path: path.clone() };
-        action::<Function>(&language, source, &path, pr, cfg)
     } else if !cfg.find_filter.is_empty() {
-        let cfg = FindCfg {
-            path: path.clone(),
+        let fcfg = FindCfg {
+            path: path.to_path_buf(),
             filters: cfg.find_filter.clone(),
             line_start: cfg.line_start,
             line_end: cfg.line_end,
         };
-        action::<Find>(&language, source, &path, pr, cfg)
-    } else if cfg.count_lock.is_some() {
-        let cfg = CountCfg {
-            filters: cfg.count_filter.clone(),
-            stats: cfg.count_lock.as_ref().unwrap().clone(),
-        };
-        action::<Count>(&language, source, &path, pr, cfg)
-    } else if cfg.preproc_lock.is_some() {
-        if let Some(language) = guess_language(&source, &path).0
-            && language == LANG::Cpp
-        {
-            let mut results = cfg.preproc_lock.as_ref().unwrap().lock().unwrap();
-            preprocess(
-                &PreprocParser::new(source, &path, None),
-                &path,
-                &mut results,
+        if let Some(output_format) = &cfg.output_format {
+            let found = FindData::call(fcfg, parser)?;
+            let res = output_format.dump_formats(
+                found,
+                path.to_path_buf(),
+                cfg.output.as_ref(),
+                cfg.pretty,
             );
+            report_output_error(res, path, cfg);
+            Ok(())
+        } else {
+            Find::call(fcfg, parser)
         }
-        Ok(())
     } else {
         Ok(())
     }
 }
 
+// Runs the modes which gather the data of all the files, so that
+// they can be combined with each other and with the other modes.
+// The metrics of the file are the ones computed with the same parser.
+fn collect<T: ParserTrait>(
+    parser: &T,
+    space: Option<&FuncSpace>,
+    acfg: &AnalyzeCfg,
+) -> std::io::Result<()> {
+    let AnalyzeCfg {
+        source, path, cfg, ..
+    } = *acfg;
+    if let Some(builder) = &cfg.call_graph_lock
+        && let Some(space) = space
+    {
+        let calls = file_calls(parser, space, path);
+        builder.lock().unwrap().add(path.to_path_buf(), calls);
+    }
+    if let Some(builder) = &cfg.module_graph_lock {
+        let icfg = ImportsCfg {
+            path: path.to_path_buf(),
+            builder: builder.clone(),
+            types: cfg.packages,
+        };
+        ImportsCollect::call(icfg, parser)?;
+    }
+    if let Some((churn, hotspots)) = &cfg.hotspots_lock
+        && let Some(space) = space
+        && let Some(churn) = churn.file(path)
+    {
+        hotspots.lock().unwrap().add(path, space, churn);
+    }
+    if let Some(detector) = &cfg.clones_lock {
+        let ccfg = ClonesCfg {
+            path: path.to_path_buf(),
+            detector: detector.clone(),
+        };
+        CloneCollect::call(ccfg, parser)?;
+    }
+    if let Some(stats) = &cfg.histogram_lock {
+        let hcfg = HistogramCfg {
+            path: path.to_path_buf(),
+            filters: cfg.count_filter.clone(),
+            stats: stats.clone(),
+        };
+        HistogramCount::call(hcfg, parser)?;
+    }
+    if let Some(stats) = &cfg.count_lock {
+        let ccfg = CountCfg {
+            filters: cfg.count_filter.clone(),
+            stats: stats.clone(),
+        };
+        Count::call(ccfg, parser)?;
+    }
+    if let Some(results) = &cfg.preproc_lock
+        && let Some(language) = guess_language(source, path).0
+        && language == LANG::Cpp
+    {
+        let mut results = results.lock().unwrap();
+        preprocess(
+            &PreprocParser::new(source.to_vec(), path, None),
+            path,
+            &mut results,
+        );
+    }
+    Ok(())
+}
+
 fn process_dir_path(all_files: &mut HashMap<String, Vec<PathBuf>>, path: &Path, cfg: &Config) {
     if cfg.preproc_lock.is_some() {
         let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
@@ -182,12 +508,21 @@ fn process_dir_path(all_files: &mut HashMap<String, Vec<PathBuf>>, path: &Path,
     }
 }
 
+fn parse_timeout(secs: &str) -> Result<Duration, String> {
+    secs.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| format!("{secs:?} is not a valid number of seconds"))
+}
+
 #[derive(Parser, Debug)]
 #[clap(
     name = "rust-code-analysis-cli",
     version,
     author,
-    about = "Analyze source code."
+    about = "Analyze source code.",
+    group(ArgGroup::new("marker_modes").args(["markers", "metrics"]).multiple(true))
 )]
 struct Opts {
     /// Input files to analyze.
@@ -199,18 +534,95 @@ struct Opts {
     /// Remove comments in the specified files.
     #[clap(long, short)]
     comments: bool,
-    /// Find nodes of the given type.
+    /// Keep the doc comments when removing comments.
+    #[clap(long, requires = "comments")]
+    keep_doc: bool,
+    /// Keep the license header when removing comments.
+    #[clap(long, requires = "comments")]
+    keep_license: bool,
+    /// Keep the comments configuring a tool, such as `// NOLINT`
+    /// or `# noqa`, when removing comments.
+    #[clap(long, requires = "comments")]
+    keep_directives: bool,
+    /// Print the unified diff of the comment removal
+    /// without modifying the files.
+    #[clap(long, requires = "comments", conflicts_with = "in_place")]
+    dry_run: bool,
+    /// Extract the comments in the specified files, classified as
+    /// doc comments, license headers, directives or regular comments.
+    #[clap(long, conflicts_with = "comments")]
+    extract_comments: bool,
+    /// List the TODO, FIXME, HACK and XXX markers in the comments,
+    /// with the function spaces containing them.
+    #[clap(long)]
+    markers: bool,
+    /// Marker to list, or to count in the metrics, in place of the default ones.
+    #[clap(long, number_of_values = 1, requires = "marker_modes")]
+    marker: Vec<String>,
+    /// Find nodes of the given type, or matching a tree-sitter query.
     #[clap(long, short, number_of_values = 1)]
     find: Vec<String>,
     /// Get functions and their spans.
     #[clap(long, short = 'F')]
     function: bool,
-    /// Count nodes of the given type: comma separated list.
+    /// Count nodes of the given type, or matches of a tree-sitter query.
     #[clap(long, short = 'C', number_of_values = 1)]
     count: Vec<String>,
+    /// Count the nodes of each kind and of each filter given with --count,
+    /// for each file and for all the files.
+    #[clap(long)]
+    histogram: bool,
+    /// Order of the histogram entries.
+    #[clap(long, default_value = "count", value_parser = PossibleValuesParser::new(["count", "name"])
+        .map(|s| s.parse::<HistogramSort>().unwrap()))]
+    histogram_sort: HistogramSort,
+    /// Find the clones among the functions of all the files,
+    /// ignoring the differences in identifiers and literals.
+    #[clap(long)]
+    clones: bool,
+    /// Export the call graph of all the files, whose nodes are the
+    /// named functions with their metrics, in the given format.
+    #[clap(long, value_parser = PossibleValuesParser::new(["dot", "graphml", "json"])
+        .map(|s| s.parse::<GraphFormat>().unwrap()))]
+    call_graph: Option<GraphFormat>,
+    /// Export the module dependency graph of all the files, built
+    /// from their imports, in the given format.
+    #[clap(long, value_parser = PossibleValuesParser::new(["dot", "graphml", "json"])
+        .map(|s| s.parse::<GraphFormat>().unwrap()))]
+    module_graph: Option<GraphFormat>,
+    /// Compute the instability and abstractness metrics of each
+    /// directory, from the imports of its files.
+    #[clap(long)]
+    packages: bool,
+    /// TOML file with the architecture rules the imports must respect,
+    /// as `rules = ["ui/** may not import db/**"]` and `forbid_cycles = true`.
+    #[clap(long, value_parser)]
+    architecture: Option<PathBuf>,
+    /// Rank the files and the functions by their number of commits
+    /// in the local git history times their cyclomatic complexity.
+    #[clap(long)]
+    hotspots: bool,
+    /// Only count the commits more recent than a date, in any format
+    /// accepted by `git log --since`, as `6 months ago`.
+    #[clap(long, requires = "hotspots")]
+    churn_since: Option<String>,
+    /// Minimum number of tokens of a clone.
+    #[clap(long, default_value_t = DEFAULT_CLONE_MIN_TOKENS, requires = "clones")]
+    clone_min_tokens: usize,
     /// Compute different metrics.
     #[clap(long, short)]
     metrics: bool,
+    /// LCOV or Cobertura XML coverage report, whose line coverage and
+    /// CRAP scores are added to the metrics of the covered files.
+    /// Its relative paths are relative to the --coverage-root directory.
+    #[clap(long, value_parser, requires = "metrics")]
+    coverage: Option<PathBuf>,
+    /// Directory the relative paths of the coverage report are relative to.
+    #[clap(long, value_parser, default_value = ".", requires = "coverage")]
+    coverage_root: PathBuf,
+    /// Count the comment lines containing commented-out code as `cloc_code`.
+    #[clap(long, requires = "metrics")]
+    commented_code: bool,
     /// Retrieve all operands and operators in a code.
     #[clap(long, conflicts_with = "metrics")]
     ops: bool,
@@ -226,6 +638,18 @@ struct Opts {
     /// Number of jobs.
     #[clap(long, short = 'j')]
     num_jobs: Option<usize>,
+    /// Time budget in seconds granted to each file.
+    #[clap(long, value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+    /// Analyze generated, minified and oversized files found in directories too.
+    #[clap(long)]
+    no_skip: bool,
+    /// Maximum size in bytes of the analyzed files.
+    #[clap(long, conflicts_with = "no_skip")]
+    max_size: Option<u64>,
+    /// Maximum average line length of the analyzed files.
+    #[clap(long, conflicts_with = "no_skip")]
+    max_line_length: Option<usize>,
     /// Language type.
     #[clap(long, short)]
     language_type: Option<String>,
@@ -242,12 +666,48 @@ struct Opts {
     /// Get preprocessor declaration for C/C++.
     #[clap(long, value_parser, number_of_values = 1)]
     preproc: Vec<PathBuf>,
+    /// Expand the macros of C/C++ files with their definitions,
+    /// instead of neutralizing them.
+    #[clap(long)]
+    expand_macros: bool,
+    /// Define a macro used by the expansion, as NAME, NAME=VALUE
+    /// or NAME(PARAMETERS)=VALUE.
+    #[clap(long, short = 'D', requires = "expand_macros", number_of_values = 1)]
+    define: Vec<String>,
+    /// Macro undefined when expanding the macros, ignoring its definitions
+    /// in the files, written as the -U option of a compiler.
+    #[clap(long, short = 'U', requires = "expand_macros", number_of_values = 1)]
+    undefine: Vec<String>,
+    /// Export the include graph of the preprocessed files, with the
+    /// direct includes, the fan-in of each header and the include
+    /// cycles, instead of the preprocessor data.
+    #[clap(long, requires = "preproc", value_parser = PossibleValuesParser::new(["dot", "graphml", "json"])
+        .map(|s| s.parse::<GraphFormat>().unwrap()))]
+    include_graph: Option<GraphFormat>,
+    /// Export the transitive includes of each file in the include graph,
+    /// along with its direct includes.
+    #[clap(long, requires = "include_graph")]
+    transitive_includes: bool,
+    /// Directory where the included files are searched, instead of
+    /// guessing them from their names.
+    #[clap(long, requires = "preproc", number_of_values = 1)]
+    include_dir: Vec<PathBuf>,
+    /// Compilation database whose include directories are used
+    /// to search the files included by each file.
+    #[clap(long, requires = "preproc", value_parser)]
+    compile_commands: Option<PathBuf>,
     /// Line start.
     #[clap(long = "ls")]
     line_start: Option<usize>,
     /// Line end.
     #[clap(long = "le")]
     line_end: Option<usize>,
+    /// Show the progress on stderr.
+    #[clap(long)]
+    progress: bool,
+    /// Print a summary of the run on stderr.
+    #[clap(long)]
+    summary: bool,
     /// Print the warnings.
     #[clap(long, short)]
     warning: bool,
@@ -256,7 +716,95 @@ struct Opts {
 fn main() {
     let opts = Opts::parse();
 
-    let count_lock = if !opts.count.is_empty() {
+    let histogram_lock = if opts.histogram {
+        Some(Arc::new(Mutex::new(Histograms {
+            sort: opts.histogram_sort,
+            ..Default::default()
+        })))
+    } else {
+        None
+    };
+
+    let clones_lock = if opts.clones {
+        Some(Arc::new(Mutex::new(CloneDetector::new(
+            opts.clone_min_tokens,
+        ))))
+    } else {
+        None
+    };
+
+    let call_graph_lock = opts
+        .call_graph
+        .map(|_| Arc::new(Mutex::new(CallGraphBuilder::default())));
+
+    let architecture = opts.architecture.as_ref().map(|path| {
+        let data = match read_file(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Error: cannot read {path:?}: {e}");
+                process::exit(1);
+            }
+        };
+        let rules = String::from_utf8_lossy(&data);
+        match toml::from_str::<ArchitectureRules>(&rules) {
+            Ok(rules) => rules.relative_to_file(path),
+            Err(e) => {
+                eprintln!("Error: invalid architecture rules in {path:?}: {e}");
+                process::exit(1);
+            }
+        }
+    });
+
+    let module_graph_lock =
+        (opts.module_graph.is_some() || opts.packages || architecture.is_some())
+            .then(|| Arc::new(Mutex::new(ModuleGraphBuilder::default())));
+
+    let coverage_lock = opts.coverage.as_ref().map(|path| {
+        let data = match read_file(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Error: cannot read {path:?}: {e}");
+                process::exit(1);
+            }
+        };
+        match CoverageReport::new(&String::from_utf8_lossy(&data), &opts.coverage_root) {
+            Ok(report) => (
+                Arc::new(report),
+                Arc::new(Mutex::new(CoverageSummary::default())),
+            ),
+            Err(e) => {
+                eprintln!("Error: invalid coverage report {path:?}: {e}");
+                process::exit(1);
+            }
+        }
+    });
+
+    let hotspots_lock =
+        opts.hotspots.then(
+            || match Churn::from_git(&opts.paths, opts.churn_since.as_deref()) {
+                Ok(churn) => (Arc::new(churn), Arc::new(Mutex::new(Hotspots::default()))),
+                Err(e) => {
+                    eprintln!(
+                        "Error: cannot read the git history of {:?}: {e}",
+                        opts.paths
+                    );
+                    process::exit(1);
+                }
+            },
+        );
+
+    // The queries are checked once, since they are skipped
+    // for the files of the languages they are not written for
+    for source in opts.find.iter().chain(&opts.count) {
+        if is_query(source)
+            && let Err(e) = check_query(source)
+        {
+            eprintln!("Error: {e}: {source}");
+            process::exit(1);
+        }
+    }
+
+    let count_lock = if !opts.count.is_empty() && !opts.histogram {
         Some(Arc::new(Mutex::new(Count::default())))
     } else {
         None
@@ -264,28 +812,97 @@ fn main() {
 
     let (preproc_lock, preproc) = match opts.preproc.len().cmp(&1) {
         Ordering::Equal => {
-            let data = read_file(&opts.preproc[0]).unwrap();
+            let data = match read_file(&opts.preproc[0]) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Error: cannot read {:?}: {e}", opts.preproc[0]);
+                    process::exit(1);
+                }
+            };
             eprintln!("Load preproc data");
-            let x = (
-                None,
-                Some(Arc::new(
-                    serde_json::from_slice::<PreprocResults>(&data).unwrap(),
-                )),
-            );
+            let x = match serde_json::from_slice::<PreprocResults>(&data) {
+                Ok(data) => (None, Some(data)),
+                Err(e) => {
+                    eprintln!("Error: invalid preproc data in {:?}: {e}", opts.preproc[0]);
+                    process::exit(1);
+                }
+            };
             eprintln!("Load preproc data: finished");
             x
         }
         Ordering::Greater => (Some(Arc::new(Mutex::new(PreprocResults::default()))), None),
         Ordering::Less => (None, None),
     };
+    let preproc = if opts.expand_macros && preproc_lock.is_none() {
+        let mut expansion = MacroExpansionCfg::default();
+        for define in &opts.define {
+            expansion.define(define);
+        }
+        for undefine in &opts.undefine {
+            expansion.undefine(undefine);
+        }
+        Some(PreprocResults {
+            expansion: Some(expansion),
+            ..preproc.unwrap_or_default()
+        })
+    } else {
+        preproc
+    };
+    let preproc = preproc.map(Arc::new);
+
+    let mut include_paths = IncludePaths::default();
+    for dir in &opts.include_dir {
+        include_paths.add_dir(dir);
+    }
+    if let Some(path) = &opts.compile_commands {
+        let data = match read_file(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Error: cannot read {path:?}: {e}");
+                process::exit(1);
+            }
+        };
+        match serde_json::from_slice::<Vec<CompileCommand>>(&data) {
+            Ok(commands) => {
+                for command in &commands {
+                    include_paths.add_compile_command(command);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: invalid compilation database in {path:?}: {e}");
+                process::exit(1);
+            }
+        }
+    }
 
     let output_is_dir = opts.output.as_ref().map(|p| p.is_dir()).unwrap_or(false);
-    if (opts.metrics || opts.ops) && opts.output.is_some() && !output_is_dir {
+    let structured = opts.output_format.is_some()
+        && (opts.function
+            || opts.extract_comments
+            || opts.markers
+            || !opts.find.is_empty()
+            || !opts.count.is_empty()
+            || opts.histogram
+            || opts.clones
+            || opts.packages
+            || opts.architecture.is_some()
+            || opts.hotspots);
+    let graph = opts.call_graph.is_some() || opts.module_graph.is_some();
+    if (opts.metrics || opts.ops || structured || graph) && opts.output.is_some() && !output_is_dir
+    {
         eprintln!("Error: The output parameter must be a directory");
         process::exit(1);
     }
 
-    let typ = opts.language_type.unwrap_or_default();
+    if let Some(output_format) = &opts.output_format
+        && !output_format.is_printable()
+        && opts.output.is_none()
+    {
+        eprintln!("Error: The {output_format:?} format requires an output directory");
+        process::exit(1);
+    }
+
+    let typ = opts.language_type.clone().unwrap_or_default();
     let language = if preproc_lock.is_some() {
         Some(LANG::Preproc)
     } else if typ.is_empty() {
@@ -310,20 +927,36 @@ fn main() {
             ) - 1
         });
 
-    let include = mk_globset(opts.include);
-    let exclude = mk_globset(opts.exclude);
+    let output_errors = Arc::new(AtomicUsize::new(0));
+
+    let include = mk_globset(opts.include.clone());
+    let exclude = mk_globset(opts.exclude.clone());
 
     let cfg = Config {
         dump: opts.dump,
         in_place: opts.in_place,
         comments: opts.comments,
-        find_filter: opts.find,
-        count_filter: opts.count,
+        dry_run: opts.dry_run,
+        kept_comments: KeptComments {
+            doc: opts.keep_doc,
+            license: opts.keep_license,
+            directives: opts.keep_directives,
+        },
+        extract_comments: opts.extract_comments,
+        markers: opts.markers,
+        marker: opts.marker.clone(),
+        find_filter: opts.find.clone(),
+        count_filter: opts.count.clone(),
         language,
         function: opts.function,
         metrics: opts.metrics,
+        metrics_opts: MetricsOpts::default()
+            .markers(&opts.marker)
+            .commented_code(opts.commented_code),
+        paths: opts.paths.clone(),
+        coverage_lock: coverage_lock.clone(),
         ops: opts.ops,
-        output_format: opts.output_format,
+        output_format: opts.output_format.clone(),
         pretty: opts.pretty,
         output: opts.output.clone(),
         line_start: opts.line_start,
@@ -331,39 +964,198 @@ fn main() {
         preproc_lock: preproc_lock.clone(),
         preproc,
         count_lock: count_lock.clone(),
+        histogram_lock: histogram_lock.clone(),
+        clones_lock: clones_lock.clone(),
+        call_graph_lock: call_graph_lock.clone(),
+        module_graph_lock: module_graph_lock.clone(),
+        packages: opts.packages,
+        hotspots_lock: hotspots_lock.clone(),
+        output_errors: output_errors.clone(),
     };
 
     let files_data = FilesData {
         include,
         exclude,
-        paths: opts.paths,
+        paths: opts.paths.clone(),
     };
 
-    let all_files = match ConcurrentRunner::new(num_jobs, act_on_file)
+    let progress = Arc::new(RunProgress::new());
+    let mut runner = ConcurrentRunner::new(num_jobs, act_on_file)
         .set_proc_dir_paths(process_dir_path)
-        .run(cfg, files_data)
-    {
-        Ok(all_files) => all_files,
+        .set_progress(progress.clone())
+        .set_sorted(true);
+    if let Some(timeout) = opts.timeout {
+        runner = runner.set_timeout(timeout);
+    }
+    if !opts.no_skip {
+        let mut skip = SkipCfg::default();
+        if let Some(max_size) = opts.max_size {
+            skip.max_size = Some(max_size);
+        }
+        if let Some(max_line_length) = opts.max_line_length {
+            skip.max_avg_line_length = Some(max_line_length);
+        }
+        runner = runner.set_skip(skip);
+    }
+
+    let start = Instant::now();
+    let printer = opts
+        .progress
+        .then(|| ProgressPrinter::start(progress, start));
+
+    let results = runner.collect(cfg, files_data);
+
+    if let Some(printer) = printer {
+        printer.finish();
+    }
+
+    let results = match results {
+        Ok(results) => results,
         Err(e) => {
             eprintln!("{e:?}");
             process::exit(1);
         }
     };
 
+    for (path, err) in &results.errors {
+        match err {
+            // The errors found while analyzing a file, rather than reading it
+            FileError::Io(err) if err.kind() == std::io::ErrorKind::Other => {
+                eprintln!("Error: {err} for file {path:?}")
+            }
+            FileError::Io(err) => eprintln!("{err:?} for file {path:?}"),
+            FileError::Cancelled(cancelled) => {
+                eprintln!("Warning: {cancelled} for file {path:?}")
+            }
+            err => eprintln!("Error: {err} for file {path:?}"),
+        }
+    }
+
+    if !results.skipped.is_empty() {
+        eprintln!(
+            "Skipped {} file(s) (use --no-skip to analyze them):",
+            results.skipped.len()
+        );
+        for (path, reason) in &results.skipped {
+            eprintln!("  {}: {reason}", path.display());
+        }
+    }
+
+    if opts.summary {
+        print_summary(&results, start.elapsed());
+    }
+
+    let all_files = results.all_files;
+
+    if let Some(histograms) = histogram_lock {
+        let histograms = Arc::try_unwrap(histograms).unwrap().into_inner().unwrap();
+        write_summary(&histograms, "histogram", &opts, &output_errors);
+    }
+
+    if let Some(detector) = clones_lock {
+        let clones = detector.lock().unwrap().clones();
+        write_summary(&clones, "clones", &opts, &output_errors);
+    }
+
+    if let (Some(builder), Some(format)) = (call_graph_lock, opts.call_graph) {
+        let builder = Arc::try_unwrap(builder).unwrap().into_inner().unwrap();
+        write_graph(
+            &builder.build(),
+            format,
+            "call_graph",
+            &opts,
+            &output_errors,
+        );
+    }
+
+    let mut architecture_violations = 0;
+    if let Some(builder) = module_graph_lock {
+        let builder = Arc::try_unwrap(builder).unwrap().into_inner().unwrap();
+        let graph = builder.build();
+        if let Some(format) = opts.module_graph {
+            write_graph(&graph, format, "module_graph", &opts, &output_errors);
+        }
+
+        if opts.packages {
+            write_summary(&Packages::new(&graph), "packages", &opts, &output_errors);
+        }
+
+        if let Some(rules) = &architecture {
+            match rules.check(&graph) {
+                Ok(violations) => {
+                    write_summary(&violations, "architecture", &opts, &output_errors);
+                    architecture_violations = violations.violations.len();
+                }
+                Err(e) => {
+                    eprintln!("Error: cannot check the architecture rules: {e}");
+                    process::exit(1);
+                }
+            }
+        }
+    }
+
+    if let Some((_, summary)) = coverage_lock {
+        let summary = Arc::try_unwrap(summary).unwrap().into_inner().unwrap();
+        write_summary(&summary, "coverage", &opts, &output_errors);
+    }
+
+    if let Some((_, hotspots)) = hotspots_lock {
+        let mut hotspots = Arc::try_unwrap(hotspots).unwrap().into_inner().unwrap();
+        hotspots.rank();
+        write_summary(&hotspots, "hotspots", &opts, &output_errors);
+    }
+
     if let Some(count) = count_lock {
         let count = Arc::try_unwrap(count).unwrap().into_inner().unwrap();
-        println!("{count}");
+        if let Some(output_format) = &opts.output_format {
+            let path = PathBuf::from("count");
+            if let Err(e) =
+                output_format.dump_formats(&count, path.clone(), opts.output.as_ref(), opts.pretty)
+            {
+                eprintln!("Error: {e} for file {path:?}");
+                output_errors.fetch_add(1, atomic::Ordering::Relaxed);
+            }
+        } else {
+            println!("{count}");
+        }
     }
 
     if let Some(preproc) = preproc_lock {
         let mut data = Arc::try_unwrap(preproc).unwrap().into_inner().unwrap();
-        fix_includes(&mut data.files, &all_files);
+        let mut graph = fix_includes(&mut data.files, &all_files, &include_paths);
 
-        let data = serde_json::to_string(&data).unwrap();
-        if let Some(output_path) = opts.output {
-            write_file(&output_path, data.as_bytes()).unwrap();
-        } else {
-            println!("{data}");
+        let data = match opts.include_graph {
+            Some(format) => {
+                graph.transitive_edges = opts.transitive_includes;
+                graph.export(format)
+            }
+            None => serde_json::to_string(&data),
+        };
+        match data {
+            Ok(data) => {
+                if let Some(output_path) = opts.output {
+                    if let Err(e) = write_file(&output_path, data.as_bytes()) {
+                        eprintln!("Error: cannot write {output_path:?}: {e}");
+                        output_errors.fetch_add(1, atomic::Ordering::Relaxed);
+                    }
+                } else {
+                    println!("{data}");
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: cannot serialize preproc data: {e}");
+                output_errors.fetch_add(1, atomic::Ordering::Relaxed);
+            }
         }
     }
+
+    let output_errors = output_errors.load(atomic::Ordering::Relaxed);
+    if output_errors > 0 {
+        eprintln!("Error: {output_errors} output(s) could not be written");
+        process::exit(1);
+    }
+    if architecture_violations > 0 {
+        eprintln!("Error: {architecture_violations} import(s) violate the architecture rules");
+        process::exit(1);
+    }
 }
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rust_code_analysis::{FileError, RunProgress, RunResults, SkipReason};
+
+// Time between two updates of the progress indicator.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+// Time between two progress lines, when stderr is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+// Number of files listed as the slowest ones.
+const SLOWEST_FILES: usize = 10;
+
+fn rate(files: usize, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0. { files as f64 / secs } else { 0. }
+}
+
+// Prints the progress on the current line of a terminal,
+// or on a new line otherwise.
+fn print_progress(progress: &RunProgress, elapsed: Duration, is_terminal: bool) {
+    let processed = progress.processed();
+    let discovered = progress.discovered();
+    let more = if progress.is_explored() { "" } else { "+" };
+    let line = format!(
+        "Processed {processed}/{discovered}{more} files, {} skipped ({:.1} files/s)",
+        progress.skipped(),
+        rate(processed, elapsed),
+    );
+
+    // Progress is best effort, so errors on stderr are ignored
+    let mut stderr = std::io::stderr().lock();
+    let _ = if is_terminal {
+        write!(stderr, "\r\x1b[K{line}")
+    } else {
+        writeln!(stderr, "{line}")
+    };
+}
+
+/// Prints the progress of a run on stderr until it is finished.
+pub struct ProgressPrinter {
+    done: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl ProgressPrinter {
+    pub fn start(progress: Arc<RunProgress>, start: Instant) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let is_terminal = std::io::stderr().is_terminal();
+        let handle = {
+            let done = done.clone();
+            thread::spawn(move || {
+                let mut last = Instant::now();
+                while !done.load(Ordering::Relaxed) {
+                    if is_terminal || last.elapsed() >= LOG_INTERVAL {
+                        print_progress(&progress, start.elapsed(), is_terminal);
+                        last = Instant::now();
+                    }
+                    thread::sleep(REFRESH_INTERVAL);
+                }
+                print_progress(&progress, start.elapsed(), is_terminal);
+                if is_terminal {
+                    eprintln!();
+                }
+            })
+        };
+
+        Self { done, handle }
+    }
+
+    pub fn finish(self) {
+        self.done.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+/// A supported file which has been analyzed.
+pub struct Analyzed {
+    /// The name of the language of the file
+    pub language: &'static str,
+    /// Whether the syntax tree of the file contains some errors
+    pub parse_failed: bool,
+}
+
+fn skip_kind(reason: &SkipReason) -> &'static str {
+    match reason {
+        SkipReason::TooLarge(_) => "too large",
+        SkipReason::Minified(_) => "minified",
+        SkipReason::Generated(_) => "generated",
+    }
+}
+
+/// Prints the statistics of a run on stderr.
+pub fn print_summary(results: &RunResults<Option<Analyzed>>, elapsed: Duration) {
+    let processed = results.durations.len();
+    eprintln!(
+        "Summary: {processed} files processed in {elapsed:.2?} ({:.1} files/s)",
+        rate(processed, elapsed)
+    );
+
+    let mut languages = BTreeMap::new();
+    let mut unsupported = 0;
+    let mut parse_failures = 0;
+    for (_, analyzed) in &results.values {
+        match analyzed {
+            Some(analyzed) => {
+                *languages.entry(analyzed.language).or_insert(0) += 1;
+                parse_failures += usize::from(analyzed.parse_failed);
+            }
+            None => unsupported += 1,
+        }
+    }
+    eprintln!("  Files per language:");
+    for (language, count) in languages {
+        eprintln!("    {language}: {count}");
+    }
+    if unsupported > 0 {
+        eprintln!("    unsupported: {unsupported}");
+    }
+
+    eprintln!("  Parse failures: {parse_failures}");
+
+    let mut skipped = BTreeMap::new();
+    for (_, reason) in &results.skipped {
+        *skipped.entry(skip_kind(reason)).or_insert(0) += 1;
+    }
+    eprintln!("  Skipped files: {}", results.skipped.len());
+    for (kind, count) in skipped {
+        eprintln!("    {kind}: {count}");
+    }
+
+    let count_errors =
+        |f: fn(&FileError) -> bool| results.errors.iter().filter(|(_, err)| f(err)).count();
+    eprintln!("  Failures: {}", results.errors.len());
+    eprintln!(
+        "    I/O errors: {}",
+        count_errors(|err| matches!(err, FileError::Io(_)))
+    );
+    eprintln!(
+        "    interrupted analyses: {}",
+        count_errors(|err| matches!(err, FileError::Cancelled(_)))
+    );
+    eprintln!(
+        "    panics: {}",
+        count_errors(|err| matches!(err, FileError::Panic(_)))
+    );
+
+    let mut durations: Vec<_> = results.durations.iter().collect();
+    durations.sort_by(|(_, a), (_, b)| b.cmp(a));
+    if !durations.is_empty() {
+        eprintln!("  Slowest files:");
+        for (path, duration) in durations.into_iter().take(SLOWEST_FILES) {
+            eprintln!("    {duration:>10.2?} {}", path.display());
+        }
+    }
+}
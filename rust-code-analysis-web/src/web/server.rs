@@ -663,7 +663,8 @@ mod tests {
                                    "mi": {"mi_original": 139.974_331_558_152_1,
                                           "mi_sei": 161.414_455_240_662_22,
                                           "mi_visual_studio": 81.856_334_244_533_39},
-                                   "abc": {"assignments": 0.0, "branches": 0.0, "conditions": 0.0, "magnitude": 0.0, "assignments_average": 0.0, "branches_average": 0.0, "conditions_average": 0.0, "assignments_min": 0.0, "assignments_max": 0.0, "branches_min": 0.0, "branches_max": 0.0, "conditions_min": 0.0, "conditions_max": 0.0}},
+                                   "abc": {"assignments": 0.0, "branches": 0.0, "conditions": 0.0, "magnitude": 0.0, "assignments_average": 0.0, "branches_average": 0.0, "conditions_average": 0.0, "assignments_min": 0.0, "assignments_max": 0.0, "branches_min": 0.0, "branches_max": 0.0, "conditions_min": 0.0, "conditions_max": 0.0},
+                                   "docs": {"public": 1.0, "documented": 0.0, "coverage": 0.0}},
                        "name": "test.py",
                        "spaces": [{"kind": "function",
                                    "start_line": 3,
@@ -745,7 +746,8 @@ mod tests {
                                    "mi": {"mi_original": 151.203_315_883_223_2,
                                           "mi_sei": 142.643_061_717_489_76,
                                           "mi_visual_studio": 88.422_991_744_574_97},
-                                   "abc": {"assignments": 0.0, "branches": 0.0, "conditions": 0.0, "magnitude": 0.0, "assignments_average": 0.0, "branches_average": 0.0, "conditions_average": 0.0, "assignments_min": 0.0, "assignments_max": 0.0, "branches_min": 0.0, "branches_max": 0.0, "conditions_min": 0.0, "conditions_max": 0.0}},
+                                   "abc": {"assignments": 0.0, "branches": 0.0, "conditions": 0.0, "magnitude": 0.0, "assignments_average": 0.0, "branches_average": 0.0, "conditions_average": 0.0, "assignments_min": 0.0, "assignments_max": 0.0, "branches_min": 0.0, "branches_max": 0.0, "conditions_min": 0.0, "conditions_max": 0.0},
+                                   "docs": {"public": 1.0, "documented": 0.0, "coverage": 0.0}},
                        "name": "test.py",
                        "spaces": []}
         });
@@ -795,7 +797,8 @@ mod tests {
                                    "mi": {"mi_original": 151.203_315_883_223_2,
                                           "mi_sei": 142.643_061_717_489_76,
                                           "mi_visual_studio": 88.422_991_744_574_97},
-                                   "abc": {"assignments": 0.0, "branches": 0.0, "conditions": 0.0, "magnitude": 0.0, "assignments_average": 0.0, "branches_average": 0.0, "conditions_average": 0.0, "assignments_min": 0.0, "assignments_max": 0.0, "branches_min": 0.0, "branches_max": 0.0, "conditions_min": 0.0, "conditions_max": 0.0}},
+                                   "abc": {"assignments": 0.0, "branches": 0.0, "conditions": 0.0, "magnitude": 0.0, "assignments_average": 0.0, "branches_average": 0.0, "conditions_average": 0.0, "assignments_min": 0.0, "assignments_max": 0.0, "branches_min": 0.0, "branches_max": 0.0, "conditions_min": 0.0, "conditions_max": 0.0},
+                                   "docs": {"public": 1.0, "documented": 0.0, "coverage": 0.0}},
                        "name": "test.py",
                        "spaces": [{"kind": "function",
                                    "start_line": 1,
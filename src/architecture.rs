@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use globset::{Glob, GlobMatcher};
+use petgraph::{
+    algo::kosaraju_scc,
+    graph::{DiGraph, EdgeReference},
+    visit::EdgeRef,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::imports::{Import, ModuleGraph};
+use crate::tools::normalize_path;
+
+/// A rule forbidding the files matching a glob to import
+/// the files matching another glob.
+///
+/// A rule is written as `ui/** may not import db/**`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ImportRule {
+    rule: String,
+    from: GlobMatcher,
+    to: GlobMatcher,
+}
+
+impl FromStr for ImportRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let Some((from, to)) = rule.split_once(" may not import ") else {
+            return Err(format!(
+                "{rule:?} is not a rule of the form \"<glob> may not import <glob>\""
+            ));
+        };
+        let glob = |glob: &str| {
+            Glob::new(glob.trim())
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| format!("invalid glob in {rule:?}: {e}"))
+        };
+        Ok(Self {
+            rule: rule.to_string(),
+            from: glob(from)?,
+            to: glob(to)?,
+        })
+    }
+}
+
+impl TryFrom<String> for ImportRule {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        rule.parse()
+    }
+}
+
+/// The architecture rules of a project.
+///
+/// The globs of the rules are matched against the paths of the files
+/// relative to the root directory, whose first components are the
+/// top-level packages.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ArchitectureRules {
+    /// The root directory of the project
+    #[serde(default)]
+    pub root: PathBuf,
+    /// The forbidden imports
+    #[serde(default)]
+    pub rules: Vec<ImportRule>,
+    /// If `true`, the imports between the top-level packages may not form a cycle
+    #[serde(default)]
+    pub forbid_cycles: bool,
+}
+
+/// An import violating an architecture rule.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Violation {
+    /// The importing file
+    pub file: PathBuf,
+    /// The line of the import
+    pub line: usize,
+    /// The imported module, as written in the code
+    pub module: String,
+    /// The imported file
+    pub target: PathBuf,
+    /// The violated rule
+    pub rule: String,
+}
+
+/// The imports violating the architecture rules of a project.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Violations {
+    /// The violations, sorted by file and line
+    pub violations: Vec<Violation>,
+}
+
+impl fmt::Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for violation in &self.violations {
+            writeln!(
+                f,
+                "{}:{}: import of {} ({}) violates \"{}\"",
+                violation.file.display(),
+                violation.line,
+                violation.module,
+                violation.target.display(),
+                violation.rule
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl ArchitectureRules {
+    /// Makes the root directory relative to the directory of the file
+    /// the rules are read from, rather than to the current directory.
+    pub fn relative_to_file(self, file: &Path) -> Self {
+        let dir = file
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        Self {
+            root: dir.join(&self.root),
+            ..self
+        }
+    }
+
+    // Returns the path of a file relative to the root directory.
+    fn relative(root: &Path, path: &Path) -> std::io::Result<PathBuf> {
+        let path = normalize_path(std::path::absolute(path)?);
+        Ok(path
+            .strip_prefix(root)
+            .map(Path::to_path_buf)
+            .unwrap_or(path))
+    }
+
+    // Returns the top-level package containing a file,
+    // that is the first component of its relative path.
+    fn package(path: &Path) -> Option<PathBuf> {
+        let mut components = path.components();
+        let package = components.next()?;
+        // A file in the root directory does not belong to any package
+        components.next().map(|_| PathBuf::from(&package))
+    }
+
+    /// Finds the imports of a module graph violating the rules.
+    ///
+    /// An empty root is the current directory.
+    ///
+    /// Returns an error when the current directory is needed
+    /// to make a path absolute and cannot be read.
+    pub fn check(&self, graph: &ModuleGraph) -> std::io::Result<Violations> {
+        let root = if self.root.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &self.root
+        };
+        let root = normalize_path(std::path::absolute(root)?);
+        let relative = graph
+            .graph
+            .node_indices()
+            .map(|index| Self::relative(&root, &graph.graph[index]))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let mut violations = Vec::new();
+
+        let mut report = |edge: EdgeReference<'_, Vec<Import>>, rule: &str| {
+            for import in edge.weight() {
+                violations.push(Violation {
+                    file: graph.graph[edge.source()].clone(),
+                    line: import.line,
+                    module: import.module.clone(),
+                    target: graph.graph[edge.target()].clone(),
+                    rule: rule.to_string(),
+                });
+            }
+        };
+
+        for edge in graph.graph.edge_references() {
+            let source = &relative[edge.source().index()];
+            let target = &relative[edge.target().index()];
+            for rule in &self.rules {
+                if rule.from.is_match(source) && rule.to.is_match(target) {
+                    report(edge, &rule.rule);
+                }
+            }
+        }
+
+        if self.forbid_cycles {
+            // The graph of the imports between the top-level packages
+            let mut packages = DiGraph::<PathBuf, ()>::new();
+            let mut indices = HashMap::new();
+            let mut package_of = |path: &Path| {
+                Self::package(path).map(|package| {
+                    *indices
+                        .entry(package.clone())
+                        .or_insert_with(|| packages.add_node(package))
+                })
+            };
+            let edges: Vec<_> = graph
+                .graph
+                .edge_references()
+                .filter_map(|edge| {
+                    let source = package_of(&relative[edge.source().index()])?;
+                    let target = package_of(&relative[edge.target().index()])?;
+                    (source != target).then_some((edge, source, target))
+                })
+                .collect();
+            for (_, source, target) in &edges {
+                packages.update_edge(*source, *target, ());
+            }
+
+            let mut components = HashMap::new();
+            for component in kosaraju_scc(&packages) {
+                if component.len() > 1 {
+                    let mut names: Vec<_> = component
+                        .iter()
+                        .map(|package| packages[*package].to_string_lossy().into_owned())
+                        .collect();
+                    names.sort();
+                    let rule = format!("no cycle between {}", names.join(", "));
+                    for package in component {
+                        components.insert(package, rule.clone());
+                    }
+                }
+            }
+            for (edge, source, target) in edges {
+                if let Some(rule) = components.get(&source)
+                    && components.get(&target) == Some(rule)
+                {
+                    report(edge, rule);
+                }
+            }
+        }
+
+        violations.sort();
+        violations.dedup();
+        Ok(Violations { violations })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ModuleGraphBuilder, ParserTrait, PythonParser, file_imports};
+
+    use super::*;
+
+    fn module_graph(files: &[(&str, &str)]) -> ModuleGraph {
+        let mut builder = ModuleGraphBuilder::default();
+        for (path, source) in files {
+            let path = Path::new(path);
+            let parser = PythonParser::new(source.as_bytes().to_vec(), path, None);
+            builder.add(path.to_path_buf(), file_imports(&parser, path));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn import_rule_syntax() {
+        assert!("ui/** may not import db/**".parse::<ImportRule>().is_ok());
+        assert!("ui/** must not import db/**".parse::<ImportRule>().is_err());
+        assert!("ui/[ may not import db/**".parse::<ImportRule>().is_err());
+    }
+
+    #[test]
+    fn architecture_violations() {
+        let graph = module_graph(&[
+            (
+                "/project/ui/view.py",
+                "import db.store\nfrom core import model\n",
+            ),
+            ("/project/ui/widgets.py", "from ..db import store\n"),
+            ("/project/db/store.py", "import core.model\n"),
+            ("/project/core/model.py", "import db.store\n"),
+            ("/project/main.py", "import ui.view\n"),
+        ]);
+        let rules = ArchitectureRules {
+            root: PathBuf::from("/project"),
+            rules: vec!["ui/** may not import db/**".parse().unwrap()],
+            forbid_cycles: true,
+        };
+
+        insta::assert_snapshot!(rules.check(&graph).unwrap().to_string(), @r#"
+            /project/core/model.py:1: import of db.store (/project/db/store.py) violates "no cycle between core, db"
+            /project/db/store.py:1: import of core.model (/project/core/model.py) violates "no cycle between core, db"
+            /project/ui/view.py:1: import of db.store (/project/db/store.py) violates "ui/** may not import db/**"
+            /project/ui/widgets.py:1: import of ..db (/project/db/store.py) violates "ui/** may not import db/**"
+            "#);
+    }
+
+    #[test]
+    fn architecture_rules_file_by_name() {
+        let graph = module_graph(&[
+            ("src/ui/view.py", "import db.store\n"),
+            ("src/db/store.py", ""),
+        ]);
+        let rules = ArchitectureRules {
+            root: PathBuf::from("src"),
+            rules: vec!["ui/** may not import db/**".parse().unwrap()],
+            forbid_cycles: false,
+        }
+        .relative_to_file(Path::new("architecture.toml"));
+
+        assert_eq!(rules.root, Path::new("./src"));
+        insta::assert_snapshot!(rules.check(&graph).unwrap().to_string(), @r#"
+            src/ui/view.py:1: import of db.store (src/db/store.py) violates "ui/** may not import db/**"
+            "#);
+
+        // Without a root, the root is the directory of the file
+        let graph = module_graph(&[("ui/view.py", "import db.store\n"), ("db/store.py", "")]);
+        let rules = ArchitectureRules {
+            rules: rules.rules,
+            ..Default::default()
+        }
+        .relative_to_file(Path::new("architecture.toml"));
+
+        assert_eq!(rules.root, Path::new("."));
+        insta::assert_snapshot!(rules.check(&graph).unwrap().to_string(), @r#"
+            ui/view.py:1: import of db.store (db/store.py) violates "ui/** may not import db/**"
+            "#);
+    }
+}
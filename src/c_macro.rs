@@ -1,6 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 
 use crate::c_langs_macros::is_predefined_macros;
+use crate::preproc::MacroDefinition;
+
+// The maximum nesting of the expansions of a macro.
+const MAX_EXPANSION_DEPTH: usize = 32;
 
 const DOLLARS: [u8; 2048] = [b'$'; 2048];
 
@@ -65,6 +70,378 @@ pub fn replace<S: ::std::hash::BuildHasher>(
     }
 }
 
+/// Maps the byte offsets of an expanded code to the offsets
+/// of its original code.
+///
+/// The expansions of the macros never change the lines of the code,
+/// so only the columns of the expanded code need to be mapped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    // The ranges of the expansions and of the invocations
+    // they replace, sorted by their position
+    expansions: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl SourceMap {
+    /// Returns the offset in the original code of an offset in the expanded code.
+    ///
+    /// An offset inside an expansion is mapped to the start of the invocation
+    /// of its macro.
+    pub fn original_offset(&self, offset: usize) -> usize {
+        match self.expansions.partition_point(|(e, _)| e.start <= offset) {
+            0 => offset,
+            i => {
+                let (expanded, original) = &self.expansions[i - 1];
+                if offset < expanded.end {
+                    original.start
+                } else {
+                    original.end + (offset - expanded.end)
+                }
+            }
+        }
+    }
+
+    /// Returns the range in the original code of a range of the expanded code.
+    ///
+    /// A range starting or ending inside an expansion is widened
+    /// to the whole invocation of its macro.
+    pub fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.original_offset(range.start);
+        let end = match self
+            .expansions
+            .partition_point(|(e, _)| e.start < range.end)
+        {
+            0 => range.end,
+            i => {
+                let (expanded, original) = &self.expansions[i - 1];
+                if range.end <= expanded.end {
+                    original.end
+                } else {
+                    original.end + (range.end - expanded.end)
+                }
+            }
+        };
+        start..end
+    }
+
+    /// Returns the ranges of the invocations of the macros in the original code.
+    pub fn invocations(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.expansions.iter().map(|(_, original)| original.clone())
+    }
+}
+
+// Skips a string or a character literal starting at `start`,
+// returning the offset following it.
+fn skip_literal(code: &[u8], start: usize) -> usize {
+    let quote = code[start];
+    let mut i = start + 1;
+    while i < code.len() && code[i] != quote && code[i] != b'\n' {
+        i += if code[i] == b'\\' { 2 } else { 1 };
+    }
+    (i + 1).min(code.len())
+}
+
+// Skips a comment starting at `start`, if any, returning the offset following it.
+fn skip_comment(code: &[u8], start: usize) -> Option<usize> {
+    match code.get(start..start + 2)? {
+        b"//" => Some(
+            code[start..]
+                .iter()
+                .position(|&c| c == b'\n')
+                .map_or(code.len(), |p| start + p),
+        ),
+        b"/*" => Some(
+            code[start + 2..]
+                .windows(2)
+                .position(|w| w == b"*/")
+                .map_or(code.len(), |p| start + p + 4),
+        ),
+        _ => None,
+    }
+}
+
+// Skips a preprocessor directive starting at `start`, with its continuation
+// lines, returning the offset of the new line ending it.
+fn skip_directive(code: &[u8], start: usize) -> usize {
+    let mut i = start;
+    while i < code.len() && code[i] != b'\n' {
+        if code[i] == b'\\' && code.get(i + 1) == Some(&b'\n') {
+            i += 1;
+        }
+        i += 1;
+    }
+    i
+}
+
+// Parses the arguments of a macro invocation starting at the open parenthesis,
+// returning the arguments, without their comments, and the offset following the
+// closing parenthesis.
+fn parse_arguments(code: &[u8], open: usize) -> Option<(Vec<Vec<u8>>, usize)> {
+    let mut arguments = vec![Vec::new()];
+    let mut depth = 0;
+    let mut i = open + 1;
+    while i < code.len() {
+        if let Some(end) = skip_comment(code, i) {
+            arguments.last_mut().unwrap().push(b' ');
+            i = end;
+            continue;
+        }
+        let c = code[i];
+        match c {
+            b'"' | b'\'' => {
+                let end = skip_literal(code, i);
+                arguments.last_mut().unwrap().extend(&code[i..end]);
+                i = end;
+                continue;
+            }
+            b'(' => depth += 1,
+            b')' if depth == 0 => return Some((arguments, i + 1)),
+            b')' => depth -= 1,
+            b',' if depth == 0 => {
+                arguments.push(Vec::new());
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        arguments.last_mut().unwrap().push(c);
+        i += 1;
+    }
+    None
+}
+
+// Returns the text of a macro argument as a string literal.
+fn stringify(argument: &[u8]) -> Vec<u8> {
+    let mut literal = vec![b'"'];
+    for &c in argument.trim_ascii() {
+        if c == b'"' || c == b'\\' {
+            literal.push(b'\\');
+        }
+        literal.push(c);
+    }
+    literal.push(b'"');
+    literal
+}
+
+struct Expander<'a, S> {
+    definitions: &'a HashMap<String, MacroDefinition, S>,
+    macros: &'a HashSet<String, S>,
+}
+
+impl<S: ::std::hash::BuildHasher> Expander<'_, S> {
+    // Returns the value of each parameter of a macro for the arguments of an invocation.
+    fn bind(definition: &MacroDefinition, mut arguments: Vec<Vec<u8>>) -> HashMap<&str, Vec<u8>> {
+        let parameters = definition.parameters.as_deref().unwrap_or_default();
+        if parameters.is_empty() && arguments.len() == 1 && arguments[0].trim_ascii().is_empty() {
+            arguments.clear();
+        }
+
+        let mut bindings = HashMap::new();
+        for (i, parameter) in parameters.iter().enumerate() {
+            if let Some(name) = parameter.strip_suffix("...") {
+                // The variadic arguments are bound with their separating commas
+                let name = if name.is_empty() { "__VA_ARGS__" } else { name };
+                let rest = arguments.get(i..).unwrap_or_default().join(&b","[..]);
+                bindings.insert(name, rest);
+                break;
+            }
+            let argument = arguments.get(i).cloned().unwrap_or_default();
+            bindings.insert(parameter.as_str(), argument.trim_ascii().to_vec());
+        }
+        bindings
+    }
+
+    // Replaces the parameters of the body of a macro with their arguments,
+    // applying the `#` and `##` operators.
+    fn substitute(definition: &MacroDefinition, arguments: Vec<Vec<u8>>) -> Vec<u8> {
+        let body = definition.body.as_bytes();
+        let bindings = Self::bind(definition, arguments);
+        let mut result: Vec<u8> = Vec::with_capacity(body.len());
+        let mut i = 0;
+        while i < body.len() {
+            let c = body[i];
+            if c == b'"' || c == b'\'' {
+                let end = skip_literal(body, i);
+                result.extend(&body[i..end]);
+                i = end;
+            } else if body[i..].starts_with(b"##") {
+                // Paste the tokens around the operator
+                while result.last().is_some_and(|c| c.is_ascii_whitespace()) {
+                    result.pop();
+                }
+                i += 2;
+                while i < body.len() && body[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+            } else if c == b'#' && definition.parameters.is_some() {
+                let start = body[i + 1..]
+                    .iter()
+                    .position(|c| !c.is_ascii_whitespace())
+                    .map_or(body.len(), |p| i + 1 + p);
+                let end = identifier_end(body, start);
+                let name = String::from_utf8_lossy(&body[start..end]);
+                match bindings.get(name.as_ref()) {
+                    Some(argument) if end > start => {
+                        result.extend(stringify(argument));
+                        i = end;
+                    }
+                    _ => {
+                        result.push(c);
+                        i += 1;
+                    }
+                }
+            } else if is_identifier_starter(c) {
+                let end = identifier_end(body, i);
+                let name = String::from_utf8_lossy(&body[i..end]);
+                match bindings.get(name.as_ref()) {
+                    Some(argument) => result.extend(argument),
+                    None => result.extend(&body[i..end]),
+                }
+                i = end;
+            } else {
+                result.push(c);
+                i += 1;
+            }
+        }
+        result
+    }
+
+    // Expands the macros of a code, except the disabled ones.
+    //
+    // The preprocessor directives, the comments and the literals are
+    // left untouched, while the macros without a definition are neutralized.
+    // The expansions are recorded with the ranges of their invocations.
+    fn expand(
+        &self,
+        code: &[u8],
+        disabled: &mut Vec<String>,
+        expansions: &mut Vec<(Range<usize>, Range<usize>)>,
+    ) -> Vec<u8> {
+        let mut new_code = Vec::with_capacity(code.len());
+        let mut line_start = true;
+        let mut i = 0;
+        while i < code.len() {
+            let c = code[i];
+            if let Some(end) = skip_comment(code, i) {
+                new_code.extend(&code[i..end]);
+                i = end;
+                continue;
+            }
+            match c {
+                b'\n' => line_start = true,
+                b'#' if line_start => {
+                    let end = skip_directive(code, i);
+                    new_code.extend(&code[i..end]);
+                    i = end;
+                    continue;
+                }
+                b'"' | b'\'' => {
+                    let end = skip_literal(code, i);
+                    new_code.extend(&code[i..end]);
+                    i = end;
+                    line_start = false;
+                    continue;
+                }
+                c if is_identifier_starter(c) => {
+                    let end = identifier_end(code, i);
+                    i = self.expand_identifier(code, i..end, &mut new_code, disabled, expansions);
+                    line_start = false;
+                    continue;
+                }
+                b' ' | b'\t' | b'\r' => {}
+                _ => line_start = false,
+            }
+            new_code.push(c);
+            i += 1;
+        }
+        new_code
+    }
+
+    // Expands an identifier of a code, returning the offset following it
+    // or following the arguments of its invocation.
+    fn expand_identifier(
+        &self,
+        code: &[u8],
+        identifier: Range<usize>,
+        new_code: &mut Vec<u8>,
+        disabled: &mut Vec<String>,
+        expansions: &mut Vec<(Range<usize>, Range<usize>)>,
+    ) -> usize {
+        let name = String::from_utf8_lossy(&code[identifier.clone()]).into_owned();
+        let definition = self
+            .definitions
+            .get(&name)
+            .filter(|_| !disabled.contains(&name) && disabled.len() < MAX_EXPANSION_DEPTH);
+        let Some(definition) = definition else {
+            if !disabled.contains(&name) && is_macro(&name, self.macros) {
+                new_code.extend(&DOLLARS[..identifier.len()]);
+            } else {
+                new_code.extend(&code[identifier.clone()]);
+            }
+            return identifier.end;
+        };
+
+        let (body, end) = if definition.parameters.is_some() {
+            // A function-like macro is only invoked when followed by its arguments
+            let open = code[identifier.end..]
+                .iter()
+                .position(|c| !c.is_ascii_whitespace())
+                .map(|p| identifier.end + p);
+            match open
+                .filter(|&open| code[open] == b'(')
+                .and_then(|open| parse_arguments(code, open))
+            {
+                Some((arguments, end)) => (Self::substitute(definition, arguments), end),
+                None => {
+                    new_code.extend(&code[identifier.clone()]);
+                    return identifier.end;
+                }
+            }
+        } else {
+            (definition.body.as_bytes().to_vec(), identifier.end)
+        };
+
+        disabled.push(name);
+        let expansion = self.expand(&body, disabled, &mut Vec::new());
+        disabled.pop();
+
+        // Keep the lines of the code, moving the new lines of
+        // the invocation after its expansion
+        let start = new_code.len();
+        new_code.extend(expansion.iter().map(|&c| if c == b'\n' { b' ' } else { c }));
+        new_code.extend(code[identifier.start..end].iter().filter(|&&c| c == b'\n'));
+        expansions.push((start..new_code.len(), identifier.start..end));
+        end
+    }
+}
+
+#[inline(always)]
+fn identifier_end(code: &[u8], start: usize) -> usize {
+    code[start..]
+        .iter()
+        .position(|&c| !is_identifier_part(c))
+        .map_or(code.len(), |p| start + p)
+}
+
+/// Expands the macros of a `C/C++` code with their definitions.
+///
+/// The macros which have no definition are neutralized as in [`replace`].
+///
+/// [`replace`]: fn.replace.html
+pub fn expand<S: ::std::hash::BuildHasher>(
+    code: &[u8],
+    definitions: &HashMap<String, MacroDefinition, S>,
+    macros: &HashSet<String, S>,
+) -> (Vec<u8>, SourceMap) {
+    let expander = Expander {
+        definitions,
+        macros,
+    };
+    let mut expansions = Vec::new();
+    let new_code = expander.expand(code, &mut Vec::new(), &mut expansions);
+    (new_code, SourceMap { expansions })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,4 +475,64 @@ mod tests {
             replace(b"abc def ghi z9_ jkl", &mac).unwrap()
         );
     }
+
+    fn definitions(defines: &[&str]) -> HashMap<String, MacroDefinition> {
+        let mut cfg = crate::MacroExpansionCfg::default();
+        for define in defines {
+            cfg.define(define);
+        }
+        cfg.defines
+            .into_iter()
+            .map(|d| (d.name.clone(), d))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        let definitions = definitions(&[
+            "FOR_EACH(i, n)=for (int i = 0; i < n; i++)",
+            "MAX(a, b)=((a) > (b) ? (a) : (b))",
+            "LIMIT=10",
+            "STR(x)=#x",
+            "CAT(a, b)=a ## b",
+            "LOG(...)=printf(__VA_ARGS__)",
+            "REC=REC + 1",
+        ]);
+        let mut macros = HashSet::new();
+        macros.insert("UNKNOWN".to_string());
+
+        let code = b"#define LIMIT 10
+int f(int x) {
+    FOR_EACH(i,
+             LIMIT) { g(i); } // MAX(a, b)
+    LOG(\"%s %d\", STR(a \"b\"), CAT(x, 1));
+    return MAX(x, LIMIT) + REC + UNKNOWN + MAX;
+}
+";
+        let (new_code, source_map) = expand(code, &definitions, &macros);
+        assert_eq!(
+            String::from_utf8(new_code.clone()).unwrap(),
+            "#define LIMIT 10
+int f(int x) {
+    for (int i = 0; i < 10; i++)
+ { g(i); } // MAX(a, b)
+    printf(\"%s %d\", \"a \\\"b\\\"\", x1);
+    return ((x) > (10) ? (x) : (10)) + REC + 1 + $$$$$$$ + MAX;
+}
+"
+        );
+
+        // The lines are kept and the offsets after an expansion are shifted
+        let original = code.iter().rposition(|&c| c == b'{').unwrap();
+        let expanded = new_code.iter().rposition(|&c| c == b'{').unwrap();
+        assert_eq!(source_map.original_offset(expanded), original);
+        assert_eq!(source_map.invocations().count(), 4);
+
+        // An expression containing an expansion is mapped to its invocation
+        let start =
+            |code: &[u8], text: &[u8]| code.windows(text.len()).position(|w| w == text).unwrap();
+        let expanded = start(&new_code, b"((x)")..start(&new_code, b" + REC");
+        let original = start(code, b"MAX(x")..start(code, b" + REC");
+        assert_eq!(source_map.original_range(expanded), original);
+    }
 }
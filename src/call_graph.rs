@@ -0,0 +1,455 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use petgraph::{Direction, graph::DiGraph, graph::NodeIndex, visit::EdgeRef};
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+
+use crate::checker::Checker;
+use crate::graph::{GraphExport, Value};
+use crate::node::Node;
+use crate::spaces::{CodeMetrics, FuncSpace, SpaceKind, metrics};
+use crate::traits::*;
+
+/// A function of a call graph.
+#[derive(Clone, Debug, Serialize)]
+pub struct CallGraphFunction {
+    /// The name of the function
+    pub name: String,
+    /// The path of the file containing the function
+    pub file: PathBuf,
+    /// The first line of the function
+    pub start_line: usize,
+    /// The last line of the function
+    pub end_line: usize,
+    /// The metrics of the function
+    pub metrics: CodeMetrics,
+}
+
+/// The calls from a function to another one.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CallSites {
+    /// The lines of the calls, in the file of the caller
+    pub lines: Vec<usize>,
+}
+
+/// The functions of a file and the calls they contain.
+#[derive(Debug, Default)]
+pub struct FileCalls {
+    functions: Vec<CallGraphFunction>,
+    // The index of the caller, the name of the callee and the line of a call
+    calls: Vec<(usize, String, usize)>,
+}
+
+// Returns the index of the named function of each space, in pre-order,
+// which is the order of the nodes of the spaces in the syntax tree.
+fn named_functions(
+    space: &FuncSpace,
+    path: &Path,
+    functions: &mut Vec<CallGraphFunction>,
+    callers: &mut Vec<Option<usize>>,
+) {
+    let caller = (space.kind == SpaceKind::Function)
+        .then(|| space.name.as_ref().filter(|n| *n != "<anonymous>"))
+        .flatten()
+        .map(|name| {
+            functions.push(CallGraphFunction {
+                name: name.clone(),
+                file: path.to_path_buf(),
+                start_line: space.start_line,
+                end_line: space.end_line,
+                metrics: space.metrics.clone(),
+            });
+            functions.len() - 1
+        });
+    callers.push(caller);
+    for space in &space.spaces {
+        named_functions(space, path, functions, callers);
+    }
+}
+
+// Returns the name of the function called by a call, that is the last
+// identifier of its callee, as `bar` in `foo.bar()` or `Foo::bar()`.
+fn callee_name<'a>(node: &Node, code: &'a [u8]) -> Option<&'a str> {
+    let callee = node
+        .child_by_field_name("function")
+        .or_else(|| node.child_by_field_name("name"))
+        .or_else(|| node.child(0))?;
+    let text = std::str::from_utf8(&code[callee.start_byte()..callee.end_byte()]).ok()?;
+    // Generic arguments are not part of the name
+    let text = text.split('<').next()?.trim_end_matches("::");
+    text.rsplit(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// Finds the named functions of a code and the calls they contain,
+/// from the spaces computed by [`metrics`] for the same parser.
+///
+/// The calls contained in anonymous functions are assigned to the
+/// named function containing them.
+///
+/// [`metrics`]: fn.metrics.html
+pub fn file_calls<T: ParserTrait>(parser: &T, space: &FuncSpace, path: &Path) -> FileCalls {
+    let mut functions = Vec::new();
+    let mut callers = Vec::new();
+    named_functions(space, path, &mut functions, &mut callers);
+    let mut next_space = 0;
+
+    let code = parser.get_code();
+    let root = parser.get_root();
+    let mut calls = Vec::new();
+    root.traverse(None, |node, caller| {
+        // The nodes of the spaces are visited in the order of the spaces
+        let caller = if T::Checker::is_func(node) || T::Checker::is_func_space(node) {
+            next_space += 1;
+            callers.get(next_space - 1).copied().flatten().or(caller)
+        } else {
+            caller
+        };
+        if let Some(caller) = caller
+            && T::Checker::is_call(node)
+            && let Some(callee) = callee_name(node, code)
+        {
+            calls.push((caller, callee.to_owned(), node.start_row() + 1));
+        }
+        Some(caller)
+    });
+
+    FileCalls { functions, calls }
+}
+
+/// Collects the functions and the calls of a set of files.
+#[derive(Debug, Default)]
+pub struct CallGraphBuilder {
+    files: BTreeMap<PathBuf, FileCalls>,
+}
+
+impl CallGraphBuilder {
+    /// Adds the functions and the calls of a file.
+    pub fn add(&mut self, path: PathBuf, calls: FileCalls) {
+        self.files.insert(path, calls);
+    }
+
+    /// Builds the call graph, resolving the callees by their names.
+    ///
+    /// A callee is resolved to the functions with the same name in the
+    /// file of the call, or else to the ones in all the other files.
+    /// The calls to unknown functions, as the ones of external libraries,
+    /// are ignored.
+    pub fn build(self) -> CallGraph {
+        let mut graph = DiGraph::new();
+        let mut global: HashMap<String, Vec<NodeIndex>> = HashMap::new();
+        let mut files = Vec::new();
+
+        for file in self.files.into_values() {
+            let mut local: HashMap<String, Vec<NodeIndex>> = HashMap::new();
+            let mut indices = Vec::new();
+            for function in file.functions {
+                let name = function.name.clone();
+                let index = graph.add_node(function);
+                local.entry(name.clone()).or_default().push(index);
+                global.entry(name).or_default().push(index);
+                indices.push(index);
+            }
+            files.push((indices, local, file.calls));
+        }
+
+        for (indices, local, calls) in files {
+            for (caller, callee, line) in calls {
+                let Some(callees) = local.get(&callee).or_else(|| global.get(&callee)) else {
+                    continue;
+                };
+                for &callee in callees {
+                    let caller = indices[caller];
+                    let edge = match graph.find_edge(caller, callee) {
+                        Some(edge) => edge,
+                        None => graph.add_edge(caller, callee, CallSites::default()),
+                    };
+                    graph[edge].lines.push(line);
+                }
+            }
+        }
+
+        CallGraph { graph }
+    }
+}
+
+/// A call graph, whose nodes are functions and whose edges are the calls
+/// from a function to another one.
+#[derive(Debug)]
+pub struct CallGraph {
+    /// The graph
+    pub graph: DiGraph<CallGraphFunction, CallSites>,
+}
+
+impl CallGraph {
+    // A recursive function is unreferenced when it only calls itself.
+    fn is_unreferenced(&self, index: NodeIndex) -> bool {
+        self.graph
+            .neighbors_directed(index, Direction::Incoming)
+            .all(|caller| caller == index)
+    }
+
+    /// Returns the functions which are not called by any other function.
+    pub fn unreferenced(&self) -> Vec<&CallGraphFunction> {
+        self.graph
+            .node_indices()
+            .filter(|&index| self.is_unreferenced(index))
+            .map(|index| &self.graph[index])
+            .collect()
+    }
+
+    // Describes the nodes and the edges of the graph for exporting it.
+    fn export(&self) -> GraphExport<'_, CallGraphFunction, CallSites> {
+        GraphExport {
+            name: "calls",
+            graph: &self.graph,
+            node_keys: &[
+                ("name", "string"),
+                ("file", "string"),
+                ("start_line", "int"),
+                ("end_line", "int"),
+                ("sloc", "double"),
+                ("cyclomatic", "double"),
+                ("cognitive", "double"),
+            ],
+            edge_keys: &[("calls", "int")],
+            node_attributes: Box::new(|index| {
+                let function = &self.graph[index];
+                let metrics = &function.metrics;
+                vec![
+                    Value::Text(function.name.clone()),
+                    Value::Text(function.file.to_string_lossy().into_owned()),
+                    Value::Int(function.start_line),
+                    Value::Int(function.end_line),
+                    Value::Double(metrics.loc.sloc()),
+                    Value::Double(metrics.cyclomatic.cyclomatic()),
+                    Value::Double(metrics.cognitive.cognitive()),
+                ]
+            }),
+            edge_attributes: Box::new(|calls| vec![Value::Int(calls.lines.len())]),
+            dashed_edges: Vec::new(),
+        }
+    }
+
+    /// Exports the graph in the `Graphviz` DOT language.
+    pub fn to_dot(&self) -> String {
+        self.export().to_dot()
+    }
+
+    /// Exports the graph in the `GraphML` format.
+    pub fn to_graphml(&self) -> String {
+        self.export().to_graphml()
+    }
+}
+
+struct GraphNode<'a>(NodeIndex, &'a CallGraphFunction);
+
+impl Serialize for GraphNode<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("node", 6)?;
+        st.serialize_field("id", &self.0.index())?;
+        st.serialize_field("name", &self.1.name)?;
+        st.serialize_field("file", &self.1.file)?;
+        st.serialize_field("start_line", &self.1.start_line)?;
+        st.serialize_field("end_line", &self.1.end_line)?;
+        st.serialize_field("metrics", &self.1.metrics)?;
+        st.end()
+    }
+}
+
+#[derive(Serialize)]
+struct GraphEdge<'a> {
+    source: usize,
+    target: usize,
+    lines: &'a [usize],
+}
+
+impl Serialize for CallGraph {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let nodes: Vec<_> = self
+            .graph
+            .node_indices()
+            .map(|index| GraphNode(index, &self.graph[index]))
+            .collect();
+        let edges: Vec<_> = self
+            .graph
+            .edge_references()
+            .map(|edge| GraphEdge {
+                source: edge.source().index(),
+                target: edge.target().index(),
+                lines: &edge.weight().lines,
+            })
+            .collect();
+        let unreferenced: Vec<_> = self
+            .graph
+            .node_indices()
+            .filter(|&index| self.is_unreferenced(index))
+            .map(|index| index.index())
+            .collect();
+
+        let mut st = serializer.serialize_struct("call_graph", 3)?;
+        st.serialize_field("nodes", &nodes)?;
+        st.serialize_field("edges", &edges)?;
+        st.serialize_field("unreferenced", &unreferenced)?;
+        st.end()
+    }
+}
+
+/// Configuration options for building the call graph of a set of files.
+#[derive(Debug)]
+pub struct CallGraphCfg {
+    /// Path to the file containing the code
+    pub path: PathBuf,
+    /// The builder collecting the calls of each file
+    pub builder: Arc<Mutex<CallGraphBuilder>>,
+}
+
+/// Collects the functions and the calls of a code.
+pub struct CallGraphCollect {
+    _guard: (),
+}
+
+impl Callback for CallGraphCollect {
+    type Res = std::io::Result<()>;
+    type Cfg = CallGraphCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        if let Some(space) = metrics(parser, &cfg.path) {
+            let calls = file_calls(parser, &space, &cfg.path);
+            cfg.builder.lock().unwrap().add(cfg.path, calls);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PythonParser, RustParser};
+
+    use super::*;
+
+    fn calls<T: ParserTrait>(source: &str, path: &str) -> FileCalls {
+        let path = Path::new(path);
+        let parser = T::new(source.as_bytes().to_vec(), path, None);
+        file_calls(&parser, &metrics(&parser, path).unwrap(), path)
+    }
+
+    #[test]
+    fn rust_call_graph() {
+        let main = "fn main() {\n    \
+                        let x = helper(1);\n    \
+                        util::log(x);\n    \
+                        std::process::exit(helper(2));\n\
+                    }\n\
+                    fn helper(x: i32) -> i32 {\n    \
+                        let f = || helper(x - 1);\n    \
+                        x\n\
+                    }\n";
+        let util = "pub fn log(x: i32) {\n    \
+                        println!(\"{x}\");\n\
+                    }\n\
+                    pub fn unused() {}\n";
+
+        let mut builder = CallGraphBuilder::default();
+        builder.add(
+            PathBuf::from("main.rs"),
+            calls::<RustParser>(main, "main.rs"),
+        );
+        builder.add(
+            PathBuf::from("util.rs"),
+            calls::<RustParser>(util, "util.rs"),
+        );
+        let graph = builder.build();
+
+        let edges: Vec<_> = graph
+            .graph
+            .edge_references()
+            .map(|e| {
+                (
+                    graph.graph[e.source()].name.as_str(),
+                    graph.graph[e.target()].name.as_str(),
+                    e.weight().lines.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("main", "helper", vec![2, 4]),
+                ("main", "log", vec![3]),
+                ("helper", "helper", vec![7]),
+            ]
+        );
+        let unreferenced: Vec<_> = graph.unreferenced().iter().map(|f| &f.name).collect();
+        assert_eq!(unreferenced, ["main", "unused"]);
+
+        insta::assert_snapshot!(
+            graph.to_dot(),
+            @r###"
+        digraph calls {
+            n0 [label="main", file="main.rs", start_line=1, end_line=5, sloc=5, cyclomatic=1, cognitive=0];
+            n1 [label="helper", file="main.rs", start_line=6, end_line=9, sloc=4, cyclomatic=1, cognitive=0];
+            n2 [label="log", file="util.rs", start_line=1, end_line=3, sloc=3, cyclomatic=1, cognitive=0];
+            n3 [label="unused", file="util.rs", start_line=4, end_line=4, sloc=1, cyclomatic=1, cognitive=0];
+            n0 -> n1 [calls=2];
+            n0 -> n2 [calls=1];
+            n1 -> n1 [calls=1];
+        }
+        "###
+        );
+    }
+
+    #[test]
+    fn python_methods_call_graph() {
+        let source = "class A:\n    \
+                          def run(self):\n        \
+                              return self.step()\n    \
+                          def step(self):\n        \
+                              return len([])\n";
+
+        let mut builder = CallGraphBuilder::default();
+        builder.add(PathBuf::from("a.py"), calls::<PythonParser>(source, "a.py"));
+        let graph = builder.build();
+
+        assert_eq!(graph.graph.node_count(), 2);
+        assert_eq!(graph.graph.edge_count(), 1);
+        let edge = graph.graph.edge_references().next().unwrap();
+        assert_eq!(graph.graph[edge.source()].name, "run");
+        assert_eq!(graph.graph[edge.target()].name, "step");
+        assert!(
+            graph
+                .to_graphml()
+                .contains("<data key=\"name\">step</data>")
+        );
+    }
+
+    #[test]
+    fn functions_on_the_same_lines() {
+        let source = "fn ping(n: u8) { pong(n) } fn pong(n: u8) { ping(n - 1) }\n";
+
+        let mut builder = CallGraphBuilder::default();
+        builder.add(PathBuf::from("a.rs"), calls::<RustParser>(source, "a.rs"));
+        let graph = builder.build();
+
+        let edges: Vec<_> = graph
+            .graph
+            .edge_references()
+            .map(|e| {
+                (
+                    graph.graph[e.source()].name.as_str(),
+                    graph.graph[e.target()].name.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(edges, [("ping", "pong"), ("pong", "ping")]);
+    }
+}
@@ -663,8 +663,8 @@ impl Checker for RustCode {
 }
 
 impl Checker for KotlinCode {
-    fn is_comment(_: &Node) -> bool {
-        false
+    fn is_comment(node: &Node) -> bool {
+        node.kind_id() == Kotlin::LineComment || node.kind_id() == Kotlin::MultilineComment
     }
 
     fn is_useful_comment(_: &Node, _: &[u8]) -> bool {
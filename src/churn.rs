@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::spaces::{FuncSpace, SpaceKind, metrics};
+use crate::traits::*;
+
+// A hunk of a diff, whose ranges start at the line before
+// the changed lines when they are empty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+}
+
+impl Hunk {
+    // Parses a hunk header, as `@@ -10,2 +12 @@`.
+    fn parse(header: &str) -> Option<Self> {
+        let mut ranges = header.strip_prefix("@@ ")?.split(' ');
+        let range = |range: &str| -> Option<(usize, usize)> {
+            match range.split_once(',') {
+                Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+                None => Some((range.parse().ok()?, 1)),
+            }
+        };
+        let (old_start, old_len) = range(ranges.next()?.strip_prefix('-')?)?;
+        let (new_start, new_len) = range(ranges.next()?.strip_prefix('+')?)?;
+        Some(Self {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+        })
+    }
+
+    // The changed lines, in the new version of the file.
+    // A deletion changes the line preceding it.
+    fn new_lines(&self) -> (usize, usize) {
+        if self.new_len == 0 {
+            let line = self.new_start.max(1);
+            (line, line)
+        } else {
+            (self.new_start, self.new_start + self.new_len - 1)
+        }
+    }
+}
+
+// Maps a line of the old version of a file to the new one,
+// a changed line being mapped to the first line of its hunk.
+fn map_line(line: usize, hunks: &[Hunk]) -> usize {
+    let mut delta = 0isize;
+    for hunk in hunks {
+        let after = if hunk.old_len == 0 {
+            hunk.old_start + 1
+        } else {
+            hunk.old_start + hunk.old_len
+        };
+        if line >= after {
+            delta += hunk.new_len as isize - hunk.old_len as isize;
+        } else if hunk.old_len > 0 && line >= hunk.old_start {
+            return hunk.new_start.max(1);
+        } else {
+            break;
+        }
+    }
+    line.saturating_add_signed(delta).max(1)
+}
+
+/// The changes of a file in the history of a repository.
+#[derive(Clone, Debug, Default)]
+pub struct FileChurn {
+    /// The number of commits changing the file
+    pub commits: usize,
+    /// The number of added and deleted lines
+    pub lines: usize,
+    // The ranges of the lines changed by each commit,
+    // mapped to the current version of the file
+    changes: Vec<Vec<(usize, usize)>>,
+    // The hunks of the commits already read, the most recent first,
+    // used to map the lines of the older commits
+    later: Vec<Vec<Hunk>>,
+}
+
+impl FileChurn {
+    // Adds the hunks of a commit older than the ones already added.
+    fn add_commit(&mut self, mut hunks: Vec<Hunk>, lines: usize) {
+        hunks.sort_by_key(|hunk| hunk.old_start);
+        let changes = hunks
+            .iter()
+            .map(|hunk| {
+                let (start, end) = hunk.new_lines();
+                // The newer commits are applied from the oldest one
+                self.later
+                    .iter()
+                    .rev()
+                    .fold((start, end), |(start, end), hunks| {
+                        let start = map_line(start, hunks);
+                        (start, map_line(end, hunks).max(start))
+                    })
+            })
+            .collect();
+        self.commits += 1;
+        self.lines += lines;
+        self.changes.push(changes);
+        self.later.push(hunks);
+    }
+
+    /// Returns the number of commits which changed some lines
+    /// in a range of the current version of the file.
+    pub fn commits_in(&self, start_line: usize, end_line: usize) -> usize {
+        self.changes
+            .iter()
+            .filter(|changes| {
+                changes
+                    .iter()
+                    .any(|&(start, end)| start <= end_line && end >= start_line)
+            })
+            .count()
+    }
+}
+
+/// The changes of the files of a `git` repository over a period of time.
+#[derive(Debug, Default)]
+pub struct Churn {
+    root: PathBuf,
+    files: HashMap<PathBuf, FileChurn>,
+}
+
+// A file changed by a commit.
+#[derive(Default)]
+struct FileDiff {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+    lines: usize,
+}
+
+impl Churn {
+    /// Reads the history of the `git` repository containing the first path,
+    /// restricted to the changes of the given paths and to the commits more
+    /// recent than `since`, in any format accepted by `git log --since`,
+    /// as `6 months ago`.
+    ///
+    /// The local `git` executable is used, and the merge commits are ignored.
+    /// A file renamed from a path which is not given is followed only
+    /// from its renaming.
+    pub fn from_git<P: AsRef<Path>>(paths: &[P], since: Option<&str>) -> io::Result<Self> {
+        let paths = if paths.is_empty() {
+            vec![fs::canonicalize(".")?]
+        } else {
+            paths
+                .iter()
+                .map(fs::canonicalize)
+                .collect::<io::Result<Vec<_>>>()?
+        };
+        let dir = if paths[0].is_dir() {
+            paths[0].as_path()
+        } else {
+            paths[0].parent().unwrap_or(&paths[0])
+        };
+        let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+        let root = fs::canonicalize(root.trim_end())?;
+
+        let mut log = Command::new("git");
+        log.arg("-C").arg(&root).args([
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--no-merges",
+            "--format=commit %H",
+            // The hunks are needed to find the changed functions,
+            // so the patches are read rather than the number of changed lines
+            "--patch",
+            "--unified=0",
+            "--find-renames",
+            "--no-color",
+            "--no-ext-diff",
+        ]);
+        if let Some(since) = since {
+            log.arg(format!("--since={since}"));
+        }
+        log.arg("--").args(&paths);
+
+        let mut child = log.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().expect("the stdout of git is piped");
+        let churn = Self::parse(BufReader::new(stdout));
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        let mut churn = churn?;
+        churn.root = root;
+        Ok(churn)
+    }
+
+    // Parses the output of `git log --patch --unified=0`, the most recent
+    // commit first, following the renames of the files.
+    fn parse<R: BufRead>(mut log: R) -> io::Result<Self> {
+        let mut files: HashMap<PathBuf, FileChurn> = HashMap::new();
+        // The current path of each renamed file, an empty one for a deleted file
+        let mut renames: HashMap<String, String> = HashMap::new();
+        let mut diffs: Vec<FileDiff> = Vec::new();
+
+        let mut add_commit = |diffs: &mut Vec<FileDiff>, renames: &mut HashMap<String, String>| {
+            for diff in diffs.drain(..) {
+                let current = |path: &String| renames.get(path).cloned().unwrap_or(path.clone());
+                let Some(path) = diff.new_path.as_ref().map(current) else {
+                    // The changes before a deletion are not part of the current file
+                    if let Some(old_path) = diff.old_path {
+                        renames.insert(old_path, String::new());
+                    }
+                    continue;
+                };
+                if path.is_empty() {
+                    continue;
+                }
+                files
+                    .entry(PathBuf::from(&path))
+                    .or_default()
+                    .add_commit(diff.hunks, diff.lines);
+                if let Some(old_path) = diff
+                    .old_path
+                    .filter(|old| Some(old) != diff.new_path.as_ref())
+                {
+                    renames.insert(old_path, path);
+                }
+            }
+        };
+
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if log.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            // The changed lines of a patch are not always valid UTF-8
+            let text = String::from_utf8_lossy(&buf);
+            let line = text.trim_end_matches(['\n', '\r']);
+            if line.starts_with("commit ") {
+                add_commit(&mut diffs, &mut renames);
+            } else if line.starts_with("diff --git ") {
+                diffs.push(FileDiff::default());
+            } else if let Some(diff) = diffs.last_mut() {
+                if let Some(path) = line
+                    .strip_prefix("--- a/")
+                    .or_else(|| line.strip_prefix("rename from "))
+                {
+                    diff.old_path = Some(path.to_string());
+                } else if let Some(path) = line
+                    .strip_prefix("+++ b/")
+                    .or_else(|| line.strip_prefix("rename to "))
+                {
+                    diff.new_path = Some(path.to_string());
+                } else if line == "+++ /dev/null" {
+                    diff.new_path = None;
+                } else if let Some(hunk) = Hunk::parse(line) {
+                    diff.hunks.push(hunk);
+                } else if !diff.hunks.is_empty() && (line.starts_with('+') || line.starts_with('-'))
+                {
+                    diff.lines += 1;
+                }
+            }
+        }
+        add_commit(&mut diffs, &mut renames);
+
+        Ok(Self {
+            root: PathBuf::new(),
+            files,
+        })
+    }
+
+    /// Returns the changes of a file, if it has been changed.
+    pub fn file(&self, path: &Path) -> Option<&FileChurn> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let path = path.strip_prefix(&self.root).unwrap_or(&path);
+        self.files.get(path)
+    }
+}
+
+// Runs a `git` command in a directory, returning its output.
+fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A file or a function ranked by its changes and its complexity.
+#[derive(Clone, Debug, Serialize)]
+pub struct Hotspot {
+    /// The path of the file
+    pub file: PathBuf,
+    /// The name of the function, `None` for a whole file
+    pub function: Option<String>,
+    /// The first line of the function or of the file
+    pub start_line: usize,
+    /// The last line of the function or of the file
+    pub end_line: usize,
+    /// The number of commits changing the function or the file
+    pub commits: usize,
+    /// The cyclomatic complexity of the function or of the file
+    pub cyclomatic: f64,
+    /// The score of the hotspot, that is the commits times the complexity
+    pub score: f64,
+}
+
+impl Hotspot {
+    fn new(file: &Path, space: &FuncSpace, commits: usize) -> Self {
+        let cyclomatic = space.metrics.cyclomatic.cyclomatic_sum();
+        Self {
+            file: file.to_path_buf(),
+            function: (space.kind != SpaceKind::Unit).then(|| {
+                space
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "<anonymous>".to_string())
+            }),
+            start_line: space.start_line,
+            end_line: space.end_line,
+            commits,
+            cyclomatic,
+            score: commits as f64 * cyclomatic,
+        }
+    }
+}
+
+/// The files and the functions which are both complex and frequently changed.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Hotspots {
+    /// The changed files, ranked by score
+    pub files: Vec<Hotspot>,
+    /// The changed functions, ranked by score
+    pub functions: Vec<Hotspot>,
+}
+
+fn function_hotspots(
+    space: &FuncSpace,
+    path: &Path,
+    churn: &FileChurn,
+    hotspots: &mut Vec<Hotspot>,
+) {
+    for space in &space.spaces {
+        if space.kind == SpaceKind::Function {
+            let commits = churn.commits_in(space.start_line, space.end_line);
+            if commits > 0 {
+                hotspots.push(Hotspot::new(path, space, commits));
+            }
+        }
+        function_hotspots(space, path, churn, hotspots);
+    }
+}
+
+impl Hotspots {
+    /// Adds the hotspots of a file, from its spaces and its changes.
+    pub fn add(&mut self, path: &Path, space: &FuncSpace, churn: &FileChurn) {
+        self.files.push(Hotspot::new(path, space, churn.commits));
+        function_hotspots(space, path, churn, &mut self.functions);
+    }
+
+    /// Ranks the hotspots by decreasing score.
+    pub fn rank(&mut self) {
+        for hotspots in [&mut self.files, &mut self.functions] {
+            hotspots.sort_by(|a, b| {
+                b.score
+                    .total_cmp(&a.score)
+                    .then_with(|| (&a.file, a.start_line).cmp(&(&b.file, b.start_line)))
+            });
+        }
+    }
+}
+
+impl fmt::Display for Hotspots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Files:")?;
+        for hotspot in &self.files {
+            writeln!(
+                f,
+                "  {}: commits: {}, cyclomatic: {}, score: {}",
+                hotspot.file.display(),
+                hotspot.commits,
+                hotspot.cyclomatic,
+                hotspot.score
+            )?;
+        }
+        writeln!(f, "Functions:")?;
+        for hotspot in &self.functions {
+            writeln!(
+                f,
+                "  {}:{}-{} {}: commits: {}, cyclomatic: {}, score: {}",
+                hotspot.file.display(),
+                hotspot.start_line,
+                hotspot.end_line,
+                hotspot.function.as_deref().unwrap_or_default(),
+                hotspot.commits,
+                hotspot.cyclomatic,
+                hotspot.score
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Configuration options for finding the hotspots of a set of files.
+#[derive(Debug)]
+pub struct HotspotsCfg {
+    /// Path to the file containing the code
+    pub path: PathBuf,
+    /// The changes of the files of the repository
+    pub churn: Arc<Churn>,
+    /// The hotspots found in the files
+    pub hotspots: Arc<Mutex<Hotspots>>,
+}
+
+/// Joins the changes of a code with the complexity of its spaces.
+pub struct HotspotsCollect {
+    _guard: (),
+}
+
+impl Callback for HotspotsCollect {
+    type Res = std::io::Result<()>;
+    type Cfg = HotspotsCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        let Some(churn) = cfg.churn.file(&cfg.path) else {
+            return Ok(());
+        };
+        if let Some(space) = metrics(parser, &cfg.path) {
+            cfg.hotspots.lock().unwrap().add(&cfg.path, &space, churn);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParserTrait, PythonParser};
+
+    use super::*;
+
+    // The history of `util.py`, renamed from `helpers.py`, the most recent commit first.
+    const LOG: &str = "commit 3333333333333333333333333333333333333333
+
+diff --git a/util.py b/util.py
+index 1111111..2222222 100644
+--- a/util.py
++++ b/util.py
+@@ -1,0 +2,2 @@ def f(x):
++    if x:
++        return 1
+commit 2222222222222222222222222222222222222222
+
+diff --git a/helpers.py b/util.py
+similarity index 80%
+rename from helpers.py
+rename to util.py
+index 1111111..2222222 100644
+--- a/helpers.py
++++ b/util.py
+@@ -5 +5 @@ def g():
+-    return 0
++    return 2
+commit 1111111111111111111111111111111111111111
+
+diff --git a/helpers.py b/helpers.py
+index 1111111..2222222 100644
+--- a/helpers.py
++++ b/helpers.py
+@@ -1 +1 @@
+-def f(x):
++def f(x, y):
+@@ -4,0 +5 @@ def g():
++    return 0
+diff --git a/old.py b/old.py
+deleted file mode 100644
+index 1111111..0000000
+--- a/old.py
++++ /dev/null
+@@ -1 +0,0 @@
+-x = 1
+";
+
+    #[test]
+    fn git_log_churn() {
+        let churn = Churn::parse(LOG.as_bytes()).unwrap();
+        let file = churn.file(Path::new("util.py")).unwrap();
+
+        assert_eq!(file.commits, 3);
+        assert_eq!(file.lines, 7);
+        // The lines of the older commits are moved by the insertion of the newest one
+        assert_eq!(
+            file.changes,
+            [vec![(2, 3)], vec![(7, 7)], vec![(1, 1), (7, 7)]]
+        );
+        assert!(churn.file(Path::new("helpers.py")).is_none());
+        assert!(churn.file(Path::new("old.py")).is_none());
+    }
+
+    #[test]
+    fn hotspots_ranking() {
+        let source =
+            "def f(x, y):\n    if x:\n        return 1\n    return y\n\ndef g():\n    return 2\n";
+        let path = Path::new("util.py");
+        let parser = PythonParser::new(source.as_bytes().to_vec(), path, None);
+        let space = metrics(&parser, path).unwrap();
+        let churn = Churn::parse(LOG.as_bytes()).unwrap();
+
+        let mut hotspots = Hotspots::default();
+        hotspots.add(path, &space, churn.file(path).unwrap());
+        hotspots.rank();
+
+        insta::assert_snapshot!(hotspots.to_string(), @r#"
+            Files:
+              util.py: commits: 3, cyclomatic: 4, score: 12
+            Functions:
+              util.py:1-4 f: commits: 2, cyclomatic: 2, score: 4
+              util.py:6-7 g: commits: 2, cyclomatic: 1, score: 2
+            "#);
+    }
+}
@@ -0,0 +1,468 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::checker::Checker;
+use crate::getter::Getter;
+use crate::halstead::HalsteadType;
+use crate::langs::LANG;
+use crate::node::Node;
+use crate::traits::*;
+
+/// The minimum number of tokens of a clone, by default.
+pub const DEFAULT_CLONE_MIN_TOKENS: usize = 50;
+
+// The token replacing identifiers and literals.
+const OPERAND: u64 = 1;
+const HASH_BASE: u64 = 1_000_003;
+
+// The tokens of a function, normalized so that the clones which differ
+// only in their identifiers and literals are the same.
+#[derive(Debug)]
+struct Unit {
+    name: Option<String>,
+    tokens: Vec<u64>,
+    lines: Vec<usize>,
+}
+
+/// The normalized tokens of the functions of a file.
+#[derive(Debug)]
+pub struct CloneTokens {
+    lang: LANG,
+    units: Vec<Unit>,
+    code_lines: usize,
+}
+
+fn push_tokens<T: ParserTrait>(node: &Node, unit: &mut Unit) {
+    node.visit(|node| {
+        if T::Checker::is_comment(node) {
+            return false;
+        }
+        if matches!(T::Getter::get_op_type(node), HalsteadType::Operand) {
+            unit.tokens.push(OPERAND);
+            unit.lines.push(node.start_row() + 1);
+            return false;
+        }
+        if node.child_count() == 0 {
+            unit.tokens.push(node.kind_id() as u64 + 2);
+            unit.lines.push(node.start_row() + 1);
+            return false;
+        }
+        true
+    });
+}
+
+/// Extracts the normalized tokens of the outermost functions of a code,
+/// where all the identifiers and the literals are replaced by the same token.
+pub fn clone_tokens<T: ParserTrait>(parser: &T) -> CloneTokens {
+    let root = parser.get_root();
+    let code = parser.get_code();
+    let mut units = Vec::new();
+    let mut code_lines = HashSet::new();
+    root.visit(|node| {
+        if T::Checker::is_comment(node) {
+            return false;
+        }
+        if T::Checker::is_func(node) {
+            let mut unit = Unit {
+                name: T::Getter::get_func_space_name(node, code).map(|name| name.to_owned()),
+                tokens: Vec::new(),
+                lines: Vec::new(),
+            };
+            push_tokens::<T>(node, &mut unit);
+            code_lines.extend(unit.lines.iter().copied());
+            units.push(unit);
+            return false;
+        }
+        if node.child_count() == 0 {
+            code_lines.insert(node.start_row() + 1);
+            return false;
+        }
+        true
+    });
+
+    CloneTokens {
+        lang: parser.get_language(),
+        units,
+        code_lines: code_lines.len(),
+    }
+}
+
+/// An occurrence of a clone.
+#[derive(Clone, Debug, Serialize)]
+pub struct CloneOccurrence {
+    /// The path of the file containing the clone
+    pub file: PathBuf,
+    /// The name of the function containing the clone
+    pub function: Option<String>,
+    /// The first line of the clone
+    pub start_line: usize,
+    /// The last line of the clone
+    pub end_line: usize,
+}
+
+/// A group of code fragments which are the same,
+/// but for their identifiers and their literals.
+#[derive(Clone, Debug, Serialize)]
+pub struct CloneGroup {
+    /// The number of tokens of the fragments
+    pub tokens: usize,
+    /// The occurrences of the fragments
+    pub occurrences: Vec<CloneOccurrence>,
+}
+
+/// The lines of a file which are part of a clone.
+#[derive(Clone, Debug, Serialize)]
+pub struct Duplication {
+    /// The path of the file
+    pub file: PathBuf,
+    /// The number of lines containing some code
+    pub lines: usize,
+    /// The number of lines containing some code which is part of a clone
+    pub duplicated_lines: usize,
+    /// The percentage of the duplicated lines
+    pub percentage: f64,
+}
+
+/// The clones found in a set of files.
+#[derive(Clone, Debug, Serialize)]
+pub struct Clones {
+    /// The groups of clones
+    pub groups: Vec<CloneGroup>,
+    /// The duplicated lines of each file
+    pub files: Vec<Duplication>,
+}
+
+/// Finds the clones among the functions of a set of files.
+#[derive(Debug)]
+pub struct CloneDetector {
+    /// The minimum number of tokens of a clone
+    pub min_tokens: usize,
+    files: BTreeMap<PathBuf, CloneTokens>,
+}
+
+impl Default for CloneDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_CLONE_MIN_TOKENS)
+    }
+}
+
+// A fragment of a function, as the index of its file, the index
+// of the function in its file and the offset of its first token.
+type Fragment = (usize, usize, usize);
+
+impl CloneDetector {
+    /// Creates a detector of the clones having at least `min_tokens` tokens.
+    pub fn new(min_tokens: usize) -> Self {
+        Self {
+            min_tokens: min_tokens.max(1),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the normalized tokens of a file.
+    pub fn add(&mut self, path: PathBuf, tokens: CloneTokens) {
+        self.files.insert(path, tokens);
+    }
+
+    /// Finds the clones among the added files.
+    pub fn clones(&self) -> Clones {
+        let files: Vec<_> = self.files.iter().collect();
+        let units = |(f, u, _): Fragment| &files[f].1.units[u];
+        let tokens = |frag: Fragment, len: usize| &units(frag).tokens[frag.2..frag.2 + len];
+        let min = self.min_tokens;
+
+        // Fragments of `min` tokens having the same hash
+        let mut buckets: HashMap<(usize, u64), Vec<Fragment>> = HashMap::new();
+        let power = (1..min).fold(1u64, |p, _| p.wrapping_mul(HASH_BASE));
+        for (f, (_, file)) in files.iter().enumerate() {
+            for (u, unit) in file.units.iter().enumerate() {
+                if unit.tokens.len() < min {
+                    continue;
+                }
+                let mut hash = unit.tokens[..min]
+                    .iter()
+                    .fold(0u64, |h, t| h.wrapping_mul(HASH_BASE).wrapping_add(*t));
+                for offset in 0..=unit.tokens.len() - min {
+                    if offset > 0 {
+                        hash = hash
+                            .wrapping_sub(unit.tokens[offset - 1].wrapping_mul(power))
+                            .wrapping_mul(HASH_BASE)
+                            .wrapping_add(unit.tokens[offset + min - 1]);
+                    }
+                    buckets
+                        .entry((file.lang as usize, hash))
+                        .or_default()
+                        .push((f, u, offset));
+                }
+            }
+        }
+
+        let mut groups = Vec::new();
+        let mut duplicated: Vec<HashSet<usize>> = vec![HashSet::new(); files.len()];
+        for bucket in buckets.into_values().filter(|b| b.len() > 1) {
+            // Fragments with the same hash could be different
+            let mut classes: Vec<Vec<Fragment>> = Vec::new();
+            for frag in bucket {
+                match classes
+                    .iter_mut()
+                    .find(|c| tokens(c[0], min) == tokens(frag, min))
+                {
+                    Some(class) => class.push(frag),
+                    None => classes.push(vec![frag]),
+                }
+            }
+
+            for mut class in classes {
+                class.sort_unstable();
+                // A fragment overlapping the previous one in the same function
+                // is not a clone
+                class.dedup_by(|b, a| (a.0, a.1) == (b.0, b.1) && b.2 < a.2 + min);
+                if class.len() < 2 {
+                    continue;
+                }
+
+                // The clone is already found when all the fragments
+                // are preceded by the same token
+                let previous =
+                    |frag: Fragment| (frag.2 > 0).then(|| units(frag).tokens[frag.2 - 1]);
+                if previous(class[0]).is_some()
+                    && class
+                        .iter()
+                        .all(|&frag| previous(frag) == previous(class[0]))
+                {
+                    continue;
+                }
+
+                // Extends the fragments as long as they are the same
+                let mut len = min;
+                loop {
+                    let next = |frag: Fragment| units(frag).tokens.get(frag.2 + len).copied();
+                    let same = next(class[0]).is_some()
+                        && class.iter().all(|&frag| next(frag) == next(class[0]))
+                        && class
+                            .windows(2)
+                            .all(|w| (w[0].0, w[0].1) != (w[1].0, w[1].1) || w[1].2 > w[0].2 + len);
+                    if !same {
+                        break;
+                    }
+                    len += 1;
+                }
+
+                let occurrences = class
+                    .iter()
+                    .map(|&frag| {
+                        let unit = units(frag);
+                        let lines = &unit.lines[frag.2..frag.2 + len];
+                        duplicated[frag.0].extend(lines.iter().copied());
+                        CloneOccurrence {
+                            file: files[frag.0].0.clone(),
+                            function: unit.name.clone(),
+                            start_line: lines[0],
+                            end_line: lines[len - 1],
+                        }
+                    })
+                    .collect();
+                groups.push(CloneGroup {
+                    tokens: len,
+                    occurrences,
+                });
+            }
+        }
+
+        groups.sort_by(|a, b| {
+            let key = |g: &CloneGroup| {
+                let first = &g.occurrences[0];
+                (first.file.clone(), first.start_line, usize::MAX - g.tokens)
+            };
+            key(a).cmp(&key(b))
+        });
+
+        let files = files
+            .iter()
+            .zip(duplicated)
+            .map(|((path, file), duplicated)| Duplication {
+                file: (*path).clone(),
+                lines: file.code_lines,
+                duplicated_lines: duplicated.len(),
+                percentage: if file.code_lines == 0 {
+                    0.
+                } else {
+                    duplicated.len() as f64 / file.code_lines as f64 * 100.
+                },
+            })
+            .collect();
+
+        Clones { groups, files }
+    }
+}
+
+impl fmt::Display for Clones {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, group) in self.groups.iter().enumerate() {
+            writeln!(f, "Clone {}: {} tokens", i + 1, group.tokens)?;
+            for occurrence in &group.occurrences {
+                write!(
+                    f,
+                    "  {}:{}-{}",
+                    occurrence.file.display(),
+                    occurrence.start_line,
+                    occurrence.end_line
+                )?;
+                if let Some(function) = &occurrence.function {
+                    write!(f, " in {function}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        writeln!(f, "Duplicated lines:")?;
+        for file in self.files.iter().filter(|file| file.duplicated_lines > 0) {
+            writeln!(
+                f,
+                "  {}: {}/{} ({:.2}%)",
+                file.file.display(),
+                file.duplicated_lines,
+                file.lines,
+                file.percentage
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Configuration options for finding the clones in a set of files.
+#[derive(Debug)]
+pub struct ClonesCfg {
+    /// Path to the file containing the code
+    pub path: PathBuf,
+    /// The detector collecting the tokens of each file
+    pub detector: Arc<Mutex<CloneDetector>>,
+}
+
+/// Collects the normalized tokens of a code to find its clones.
+pub struct CloneCollect {
+    _guard: (),
+}
+
+impl Callback for CloneCollect {
+    type Res = std::io::Result<()>;
+    type Cfg = ClonesCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        let tokens = clone_tokens(parser);
+        cfg.detector.lock().unwrap().add(cfg.path, tokens);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{PythonParser, RustParser};
+
+    use super::*;
+
+    fn tokens<T: ParserTrait>(source: &str, path: &str) -> CloneTokens {
+        let parser = T::new(source.as_bytes().to_vec(), Path::new(path), None);
+        clone_tokens(&parser)
+    }
+
+    #[test]
+    fn rust_clones() {
+        let first = "fn foo(a: u32) -> u32 {\n    \
+                         let b = a * 2;\n    \
+                         if b > 10 {\n        \
+                             return b - 1;\n    \
+                         }\n    \
+                         b + 1\n\
+                     }\n";
+        let second = "// Renamed\n\
+                      fn bar(x: u32) -> u32 {\n    \
+                          let y = x * 3;\n    \
+                          if y > 42 {\n        \
+                              return y - 7;\n    \
+                          }\n    \
+                          y + 1\n\
+                      }\n\
+                      fn baz() {}\n";
+
+        let mut detector = CloneDetector::new(20);
+        detector.add(PathBuf::from("a.rs"), tokens::<RustParser>(first, "a.rs"));
+        detector.add(PathBuf::from("b.rs"), tokens::<RustParser>(second, "b.rs"));
+        let clones = detector.clones();
+
+        insta::assert_json_snapshot!(
+            clones,
+            @r###"
+        {
+          "groups": [
+            {
+              "tokens": 32,
+              "occurrences": [
+                {
+                  "file": "a.rs",
+                  "function": "foo",
+                  "start_line": 1,
+                  "end_line": 7
+                },
+                {
+                  "file": "b.rs",
+                  "function": "bar",
+                  "start_line": 2,
+                  "end_line": 8
+                }
+              ]
+            }
+          ],
+          "files": [
+            {
+              "file": "a.rs",
+              "lines": 7,
+              "duplicated_lines": 7,
+              "percentage": 100.0
+            },
+            {
+              "file": "b.rs",
+              "lines": 8,
+              "duplicated_lines": 7,
+              "percentage": 87.5
+            }
+          ]
+        }
+        "###
+        );
+    }
+
+    #[test]
+    fn python_no_clones_across_languages() {
+        let source = "def foo(a):\n    \
+                          b = a * 2\n    \
+                          return b + 1\n";
+
+        let mut detector = CloneDetector::new(5);
+        detector.add(
+            PathBuf::from("a.py"),
+            tokens::<PythonParser>(source, "a.py"),
+        );
+        detector.add(
+            PathBuf::from("b.rs"),
+            tokens::<RustParser>("fn foo() { let b = 2; }", "b.rs"),
+        );
+        let clones = detector.clones();
+        assert!(clones.groups.is_empty());
+        assert_eq!(clones.files[0].duplicated_lines, 0);
+
+        detector.add(
+            PathBuf::from("c.py"),
+            tokens::<PythonParser>(source, "c.py"),
+        );
+        let clones = detector.clones();
+        assert_eq!(clones.groups.len(), 1);
+        assert_eq!(clones.groups[0].occurrences.len(), 2);
+        assert_eq!(clones.files[0].percentage, 100.);
+    }
+}
@@ -1,50 +1,76 @@
 use std::io::{self, Write};
 use std::path::PathBuf;
 
+use similar::TextDiff;
+
 use crate::checker::Checker;
+use crate::comments::{CommentKind, classify_comments};
 
 use crate::tools::*;
 use crate::traits::*;
 
 const CR: [u8; 8192] = [b'\n'; 8192];
 
+/// The kinds of comments kept when removing the comments from a code.
+///
+/// The comments needed to parse a code, such as the encoding declaration
+/// of a Python file, are always kept.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeptComments {
+    /// If `true`, doc comments are kept
+    pub doc: bool,
+    /// If `true`, the comments in the header of a file containing
+    /// its license or its copyright are kept
+    pub license: bool,
+    /// If `true`, the comments configuring a tool, such as
+    /// `// NOLINT` or `# noqa`, are kept
+    pub directives: bool,
+}
+
+impl KeptComments {
+    fn keeps(&self, comment_kind: CommentKind) -> bool {
+        match comment_kind {
+            CommentKind::Doc => self.doc,
+            CommentKind::License => self.license,
+            CommentKind::Directive => self.directives,
+            CommentKind::Regular => false,
+        }
+    }
+}
+
 /// Removes comments from a code.
 pub fn rm_comments<T: ParserTrait>(parser: &T) -> Option<Vec<u8>> {
-    let node = parser.get_root();
-    let mut stack = Vec::new();
-    let mut cursor = node.cursor();
-    let mut spans = Vec::new();
-
-    stack.push(node);
+    rm_comments_keeping(parser, KeptComments::default())
+}
 
-    while let Some(node) = stack.pop() {
-        if T::Checker::is_comment(&node) && !T::Checker::is_useful_comment(&node, parser.get_code())
-        {
+/// Removes comments from a code, keeping the given kinds of comments.
+pub fn rm_comments_keeping<T: ParserTrait>(parser: &T, kept: KeptComments) -> Option<Vec<u8>> {
+    let code = parser.get_code();
+    let spans: Vec<_> = classify_comments(parser)
+        .into_iter()
+        .filter(|(node, comment_kind)| {
+            // Python docstrings are strings, so they are never removed
+            T::Checker::is_comment(node)
+                && !T::Checker::is_useful_comment(node, code)
+                && !kept.keeps(*comment_kind)
+        })
+        .map(|(node, _)| {
             let lines = node.end_row() - node.start_row();
-            spans.push((node.start_byte(), node.end_byte(), lines));
-        } else {
-            cursor.reset(&node);
-            if cursor.goto_first_child() {
-                loop {
-                    stack.push(cursor.node());
-                    if !cursor.goto_next_sibling() {
-                        break;
-                    }
-                }
-            }
-        }
-    }
+            (node.start_byte(), node.end_byte(), lines)
+        })
+        .collect();
+
     if !spans.is_empty() {
-        Some(remove_from_code(parser.get_code(), spans))
+        Some(remove_from_code(code, spans))
     } else {
         None
     }
 }
 
-fn remove_from_code(code: &[u8], mut spans: Vec<(usize, usize, usize)>) -> Vec<u8> {
+fn remove_from_code(code: &[u8], spans: Vec<(usize, usize, usize)>) -> Vec<u8> {
     let mut new_code = Vec::with_capacity(code.len());
     let mut code_start = 0;
-    for (start, end, lines) in spans.drain(..).rev() {
+    for (start, end, lines) in spans {
         new_code.extend(&code[code_start..start]);
         if lines != 0 {
             if lines <= CR.len() {
@@ -61,11 +87,27 @@ fn remove_from_code(code: &[u8], mut spans: Vec<(usize, usize, usize)>) -> Vec<u
     new_code
 }
 
+/// Returns the unified diff between a code and the code without comments.
+pub fn comments_diff(path: &str, code: &[u8], new_code: &[u8]) -> String {
+    let code = String::from_utf8_lossy(code);
+    let new_code = String::from_utf8_lossy(new_code);
+    let path = path.trim_start_matches('/');
+    TextDiff::from_lines(code.as_ref(), new_code.as_ref())
+        .unified_diff()
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string()
+}
+
 /// Configuration options for removing comments from a code.
 #[derive(Debug)]
 pub struct CommentRmCfg {
     /// If `true`, the modified code is saved on a file
     pub in_place: bool,
+    /// If `true`, the file is not modified and the unified diff
+    /// of the changes is printed instead
+    pub dry_run: bool,
+    /// The kinds of comments to keep
+    pub kept: KeptComments,
     /// Path to output file
     pub path: PathBuf,
 }
@@ -79,8 +121,11 @@ impl Callback for CommentRm {
     type Cfg = CommentRmCfg;
 
     fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
-        if let Some(new_source) = rm_comments(parser) {
-            if cfg.in_place {
+        if let Some(new_source) = rm_comments_keeping(parser, cfg.kept) {
+            if cfg.dry_run {
+                let path = cfg.path.to_string_lossy();
+                print!("{}", comments_diff(&path, parser.get_code(), &new_source));
+            } else if cfg.in_place {
                 write_file(&cfg.path, &new_source)?;
             } else if let Ok(new_source) = std::str::from_utf8(&new_source) {
                 println!("{new_source}");
@@ -96,9 +141,9 @@ impl Callback for CommentRm {
 mod tests {
     use std::path::PathBuf;
 
-    use crate::{CcommentParser, ParserTrait};
+    use crate::{CcommentParser, CppParser, ParserTrait, RustParser};
 
-    use super::rm_comments;
+    use super::*;
 
     const SOURCE_CODE: &str = "/* Remove this code block */\n\
                                int a = 42; // Remove this comment\n\
@@ -129,4 +174,75 @@ mod tests {
 
         assert_eq!(no_comments.as_slice(), SOURCE_CODE_NO_COMMENTS.as_bytes());
     }
+
+    #[test]
+    fn cpp_remove_comments() {
+        // The strings and the raw strings of the C++ grammar
+        // are kept, even when they look like comments
+        let source = "// Remove this\n\
+                      const char *url = \"http://example.com\"; // Remove this\n\
+                      const char *raw = R\"(/* not a comment */)\";\n\
+                      /* Remove\n\
+                       * this */\n\
+                      int a = 42;\n";
+        let parser = CppParser::new(source.as_bytes().to_vec(), &PathBuf::from("foo.cpp"), None);
+
+        let no_comments = rm_comments(&parser).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&no_comments).unwrap(),
+            "\n\
+             const char *url = \"http://example.com\"; \n\
+             const char *raw = R\"(/* not a comment */)\";\n\
+             \n\
+             \n\
+             int a = 42;\n"
+        );
+    }
+
+    #[test]
+    fn rust_keep_comments() {
+        let source = "// Copyright 2024 Foo\n\
+                      /// Returns the answer\n\
+                      fn foo() -> &'static str {\n    \
+                          // Just a comment\n    \
+                          \"// Not a comment\" // NOLINT\n\
+                      }\n";
+        let parser = RustParser::new(source.as_bytes().to_vec(), &PathBuf::from("foo.rs"), None);
+
+        let kept = KeptComments {
+            doc: true,
+            license: true,
+            directives: false,
+        };
+        let no_comments = rm_comments_keeping(&parser, kept).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&no_comments).unwrap(),
+            "// Copyright 2024 Foo\n\
+             /// Returns the answer\n\
+             fn foo() -> &'static str {\n    \
+                 \n    \
+                 \"// Not a comment\" \n\
+             }\n"
+        );
+
+        let no_comments = rm_comments(&parser).unwrap();
+        insta::assert_snapshot!(
+            comments_diff("foo.rs", parser.get_code(), &no_comments),
+            @r#"
+        --- a/foo.rs
+        +++ b/foo.rs
+        @@ -1,6 +1,6 @@
+        -// Copyright 2024 Foo
+        -/// Returns the answer
+        +
+        +
+         fn foo() -> &'static str {
+        -    // Just a comment
+        -    "// Not a comment" // NOLINT
+        +    
+        +    "// Not a comment" 
+         }
+        "#
+        );
+    }
 }
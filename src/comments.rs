@@ -0,0 +1,665 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, Anchored, Input, StartKind};
+use serde::Serialize;
+use termcolor::{Color, ColorChoice, StandardStream};
+
+use crate::checker::Checker;
+use crate::find::FoundNode;
+use crate::node::Node;
+use crate::tools::{color, intense_color};
+use crate::traits::*;
+use crate::*;
+
+static DIRECTIVES: OnceLock<AhoCorasick> = OnceLock::new();
+static LICENSES: OnceLock<AhoCorasick> = OnceLock::new();
+
+// Comments which configure a tool rather than documenting a code:
+// the directive must open the comment body, so that a comment
+// just mentioning a directive is not one.
+fn is_directive(text: &[u8]) -> bool {
+    let directives = DIRECTIVES.get_or_init(|| {
+        AhoCorasickBuilder::new()
+            .start_kind(StartKind::Anchored)
+            .build([
+                "eslint-disable",
+                "eslint-enable",
+                "@ts-ignore",
+                "@ts-expect-error",
+                "@ts-nocheck",
+                "prettier-ignore",
+                "istanbul ignore",
+                "NOLINT",
+                "clang-format off",
+                "clang-format on",
+                "noqa",
+                "pylint:",
+                "type: ignore",
+                "fmt: off",
+                "fmt: on",
+                "noinspection",
+                "#region",
+                "#endregion",
+            ])
+            .unwrap()
+    });
+    let text = text.trim_ascii_start();
+    let body = ["<!--", "//", "/*", "--", "#", ";", "%"]
+        .iter()
+        .find_map(|opener| text.strip_prefix(opener.as_bytes()))
+        .unwrap_or(text);
+    let start = body
+        .iter()
+        .position(|c| !c.is_ascii_whitespace() && !matches!(c, b'*' | b'/' | b'!'))
+        .unwrap_or(body.len());
+    // `#region` is a directive on its own in languages using `#` comments
+    [text, &body[start..]]
+        .iter()
+        .any(|t| directives.is_match(Input::new(t).anchored(Anchored::Yes)))
+}
+
+fn is_license(text: &[u8]) -> bool {
+    LICENSES
+        .get_or_init(|| {
+            AhoCorasickBuilder::new()
+                .ascii_case_insensitive(true)
+                .build(["license", "copyright", "spdx-license-identifier"])
+                .unwrap()
+        })
+        .is_match(text)
+}
+
+pub(crate) fn is_doc(text: &[u8]) -> bool {
+    (text.starts_with(b"///") && !text.starts_with(b"////"))
+        || text.starts_with(b"//!")
+        || (text.starts_with(b"/**") && !text.starts_with(b"/**/"))
+        || text.starts_with(b"/*!")
+}
+
+/// The kind of a comment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentKind {
+    /// A comment documenting the code which follows it, such as
+    /// `///` and `/** */` comments, JSDoc and Python docstrings
+    Doc,
+    /// A comment in the header of a file containing its license
+    /// or its copyright
+    License,
+    /// A comment configuring a tool, such as a linter or a formatter
+    Directive,
+    /// Any other comment
+    Regular,
+}
+
+/// A comment found in a code.
+#[derive(Clone, Debug, Serialize)]
+pub struct Comment {
+    /// The kind of the comment
+    #[serde(rename = "type")]
+    pub comment_kind: CommentKind,
+    /// The node of the comment
+    #[serde(flatten)]
+    pub node: FoundNode,
+}
+
+/// The comments found in a file.
+#[derive(Debug, Serialize)]
+pub struct Comments {
+    /// The path of the file
+    pub file: PathBuf,
+    /// The comments in the file
+    pub comments: Vec<Comment>,
+}
+
+// Returns true when a node is a Python docstring, that is a string
+// which is the first statement of a module, a class or a function.
+pub(crate) fn is_docstring(node: &Node) -> bool {
+    if node.kind_id() != Python::ExpressionStatement
+        || node.child_count() != 1
+        || node.child(0).is_none_or(|n| n.kind_id() != Python::String)
+    {
+        return false;
+    }
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    let documented = match parent.kind_id().into() {
+        Python::Module => true,
+        Python::Block | Python::Block2 => parent.parent().is_some_and(|p| {
+            matches!(
+                p.kind_id().into(),
+                Python::FunctionDefinition | Python::ClassDefinition
+            )
+        }),
+        _ => false,
+    };
+
+    documented
+        && parent
+            .children()
+            .find(|n| n.kind_id() != Python::Comment)
+            .is_some_and(|n| n.id() == node.id())
+}
+
+// Finds the comments of a code, classifying them.
+// Python docstrings are returned as well, as the string node they contain.
+pub(crate) fn classify_comments<T: ParserTrait>(parser: &T) -> Vec<(Node<'_>, CommentKind)> {
+    let root = parser.get_root();
+    let code = parser.get_code();
+    let is_python = parser.get_language() == LANG::Python;
+
+    // The header of a file ends with its first node which is not a comment
+    let header_end = root
+        .children()
+        .find(|n| !T::Checker::is_comment(n))
+        .map_or(code.len(), |n| n.start_byte());
+
+    let mut comments = Vec::new();
+    root.visit(|node| {
+        if T::Checker::is_comment(node) {
+            let text = &code[node.start_byte()..node.end_byte()];
+            let comment_kind = if T::Checker::is_useful_comment(node, code) || is_directive(text) {
+                CommentKind::Directive
+            } else if is_doc(text) {
+                CommentKind::Doc
+            } else {
+                CommentKind::Regular
+            };
+            comments.push((*node, comment_kind));
+            return false;
+        }
+        if is_python && is_docstring(node) {
+            comments.push((node.child(0).unwrap(), CommentKind::Doc));
+            return false;
+        }
+        true
+    });
+
+    // A license spans all the regular comments of the header
+    let text = |node: &Node| &code[node.start_byte()..node.end_byte()];
+    let header = comments
+        .iter()
+        .take_while(|(node, _)| node.end_byte() <= header_end)
+        .count();
+    if comments[..header]
+        .iter()
+        .any(|(node, _)| is_license(text(node)))
+    {
+        for (node, comment_kind) in &mut comments[..header] {
+            if *comment_kind == CommentKind::Regular || is_license(text(node)) {
+                *comment_kind = CommentKind::License;
+            }
+        }
+    }
+
+    comments
+}
+
+/// Extracts the comments of a code, classifying them.
+///
+/// Python docstrings are extracted as well, even if they are strings.
+pub fn comments<T: ParserTrait>(parser: &T) -> Vec<Comment> {
+    classify_comments(parser)
+        .into_iter()
+        .map(|(node, comment_kind)| Comment {
+            comment_kind,
+            node: FoundNode::new(&node, parser),
+        })
+        .collect()
+}
+
+// The code surrounding a snippet so that it can be parsed in a place
+// where its statements or its members are allowed.
+fn code_wrappers(lang: LANG) -> &'static [(&'static str, &'static str)] {
+    match lang {
+        LANG::Rust => &[("", ""), ("fn __f() {\n", "\n}")],
+        LANG::Cpp => &[("", ""), ("void __f() {\n", "\n}")],
+        LANG::Kotlin => &[("", ""), ("fun __f() {\n", "\n}")],
+        LANG::Java | LANG::Csharp => &[
+            ("", ""),
+            ("class __C {\n", "\n}"),
+            ("class __C { void __f() {\n", "\n} }"),
+        ],
+        LANG::Php => &[("<?php\n", "")],
+        LANG::Mozjs
+        | LANG::Javascript
+        | LANG::Typescript
+        | LANG::Tsx
+        | LANG::Python
+        | LANG::Perl
+        | LANG::Css => &[("", "")],
+        // Any text is valid in these languages
+        LANG::Ccomment | LANG::Preproc | LANG::Html => &[],
+    }
+}
+
+// The last row containing a comment, since a line comment can
+// include its newline.
+fn last_row(node: &Node) -> usize {
+    let (row, column) = node.end_position();
+    if column == 0 && row > node.start_row() {
+        row - 1
+    } else {
+        row
+    }
+}
+
+// Checks whether a comment is the only thing on its first line.
+fn is_own_line(node: &Node, code: &[u8]) -> bool {
+    code[..node.start_byte()]
+        .iter()
+        .rev()
+        .take_while(|&&c| c != b'\n')
+        .all(|c| c.is_ascii_whitespace())
+}
+
+// Removes the delimiters of a comment, returning its lines.
+fn comment_lines(text: &[u8]) -> Vec<&[u8]> {
+    let text = text.trim_ascii();
+    if let Some(block) = text.strip_prefix(b"/*") {
+        let block = block.strip_suffix(b"*/").unwrap_or(block);
+        let lines: Vec<_> = block.split(|&c| c == b'\n').collect();
+        // Remove the stars which decorate the lines of a block comment
+        if lines[1..]
+            .iter()
+            .all(|l| l.trim_ascii().is_empty() || l.trim_ascii_start().starts_with(b"*"))
+        {
+            return lines
+                .into_iter()
+                .enumerate()
+                .map(|(i, l)| {
+                    let trimmed = l.trim_ascii_start();
+                    match trimmed.strip_prefix(b"*") {
+                        Some(rest) if i > 0 => rest,
+                        _ => l,
+                    }
+                })
+                .collect();
+        }
+        return lines;
+    }
+    let line = text
+        .strip_prefix(b"//")
+        .or_else(|| text.strip_prefix(b"#"))
+        .unwrap_or(text);
+    vec![line]
+}
+
+// Joins the lines of some comments, removing their common indentation.
+fn dedent(lines: &[&[u8]]) -> Vec<u8> {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim_ascii().is_empty())
+        .map(|l| l.iter().take_while(|c| c.is_ascii_whitespace()).count())
+        .min()
+        .unwrap_or_default();
+    let mut code = Vec::new();
+    for line in lines {
+        code.extend_from_slice(line.get(indent..).unwrap_or_default().trim_ascii_end());
+        code.push(b'\n');
+    }
+    code
+}
+
+// Checks whether a comment could be a part of some code spanning
+// several comments, such as a statement or a block.
+fn is_code_like(text: &[u8]) -> bool {
+    text.iter().any(|c| b";{}()[]=:,".contains(c))
+}
+
+// Checks whether a snippet is valid code in the language of a parser.
+fn is_code<T: ParserTrait>(lang: LANG, snippet: &[u8]) -> bool {
+    // Prose parsed as code hardly contains these characters
+    if !snippet.iter().any(|c| b";{}()=".contains(c)) {
+        return false;
+    }
+    code_wrappers(lang).iter().any(|(prefix, suffix)| {
+        let code = [prefix.as_bytes(), snippet, suffix.as_bytes()].concat();
+        let parser = T::new(code, Path::new(""), None);
+        !parser.get_root().has_error()
+    })
+}
+
+/// Finds the comments containing commented-out code, that is
+/// the comments whose text is valid code in the language of the parser.
+///
+/// Consecutive line comments are parsed together, so that a statement
+/// spanning several lines is detected.
+/// Documentation comments, directives and comments following some code
+/// on the same line are never considered code.
+pub(crate) fn commented_code<T: ParserTrait>(parser: &T) -> Vec<Node<'_>> {
+    let root = parser.get_root();
+    let code = parser.get_code();
+    let lang = parser.get_language();
+    if code_wrappers(lang).is_empty() {
+        return Vec::new();
+    }
+
+    // Groups of consecutive line comments, or single block comments
+    let mut groups: Vec<Vec<Node>> = Vec::new();
+    let mut last_joinable = false;
+    root.visit(|node| {
+        if T::Checker::is_comment(node) {
+            let text = &code[node.start_byte()..node.end_byte()];
+            // Comments following some code on the same line explain it
+            if T::Checker::is_useful_comment(node, code)
+                || is_directive(text)
+                || is_doc(text)
+                || !is_own_line(node, code)
+            {
+                last_joinable = false;
+                return false;
+            }
+            let joinable = !text.starts_with(b"/*");
+            match groups.last_mut() {
+                Some(group)
+                    if last_joinable
+                        && joinable
+                        && group
+                            .last()
+                            .is_some_and(|n| last_row(n) + 1 == node.start_row()) =>
+                {
+                    group.push(*node)
+                }
+                _ => groups.push(vec![*node]),
+            }
+            last_joinable = joinable;
+            return false;
+        }
+        last_joinable = false;
+        true
+    });
+
+    let lines = |nodes: &[Node]| -> Vec<u8> {
+        let lines: Vec<_> = nodes
+            .iter()
+            .flat_map(|n| comment_lines(&code[n.start_byte()..n.end_byte()]))
+            .collect();
+        dedent(&lines)
+    };
+
+    // Each comment is parsed alone once, then each run of consecutive
+    // comments which could be code is parsed as a whole, so the number
+    // of parses is linear in the number of comments
+    let mut res = Vec::new();
+    for group in groups {
+        let is_code_in = |start: usize, end: usize| is_code::<T>(lang, &lines(&group[start..end]));
+        let mut found: Vec<_> = (0..group.len()).map(|i| is_code_in(i, i + 1)).collect();
+        let mut start = 0;
+        while start < group.len() {
+            let end = start
+                + group[start..]
+                    .iter()
+                    .take_while(|n| is_code_like(&code[n.start_byte()..n.end_byte()]))
+                    .count();
+            if end == start {
+                start += 1;
+                continue;
+            }
+            // Code can be preceded or followed by some prose
+            let run = [
+                (start, end),
+                (start + 1, end),
+                (start, end - 1),
+                (start + 1, end - 1),
+            ]
+            .into_iter()
+            .filter(|(start, end)| start + 1 < *end)
+            .find(|&(start, end)| is_code_in(start, end));
+            if let Some((start, end)) = run {
+                found[start..end].fill(true);
+            }
+            start = end;
+        }
+        res.extend(
+            group
+                .iter()
+                .zip(found)
+                .filter_map(|(node, found)| found.then_some(*node)),
+        );
+    }
+
+    res
+}
+
+fn dump_comments(comments: &Comments) -> std::io::Result<()> {
+    if comments.comments.is_empty() {
+        return Ok(());
+    }
+
+    let stdout = StandardStream::stdout(ColorChoice::Always);
+    let mut stdout = stdout.lock();
+
+    intense_color(&mut stdout, Color::Yellow)?;
+    writeln!(stdout, "In file {}", comments.file.display())?;
+
+    for comment in &comments.comments {
+        let node = &comment.node;
+        color(&mut stdout, Color::Green)?;
+        write!(
+            stdout,
+            "   ({}, {}) to ({}, {}) ",
+            node.start_line, node.start_column, node.end_line, node.end_column
+        )?;
+
+        intense_color(&mut stdout, Color::Magenta)?;
+        write!(stdout, "{:?}: ", comment.comment_kind)?;
+
+        color(&mut stdout, Color::White)?;
+        writeln!(stdout, "{}", node.text.lines().next().unwrap_or_default())?;
+    }
+
+    color(&mut stdout, Color::White)
+}
+
+/// Configuration options for extracting the comments of a code.
+#[derive(Debug)]
+pub struct CommentsCfg {
+    /// Path to the file containing the code
+    pub path: PathBuf,
+}
+
+/// Extracts the comments of a code, returning them as data.
+pub struct CommentsData {
+    _guard: (),
+}
+
+impl Callback for CommentsData {
+    type Res = Comments;
+    type Cfg = CommentsCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        Comments {
+            file: cfg.path,
+            comments: comments(parser),
+        }
+    }
+}
+
+/// Extracts and prints the comments of a code.
+pub struct CommentsDump {
+    _guard: (),
+}
+
+impl Callback for CommentsDump {
+    type Res = std::io::Result<()>;
+    type Cfg = CommentsCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        dump_comments(&CommentsData::call(cfg, parser))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn kinds<T: ParserTrait>(source: &str, path: &str) -> Vec<(CommentKind, String)> {
+        let parser = T::new(source.as_bytes().to_vec(), Path::new(path), None);
+        comments(&parser)
+            .into_iter()
+            .map(|c| (c.comment_kind, c.node.text))
+            .collect()
+    }
+
+    #[test]
+    fn rust_comments() {
+        let source = "// Copyright 2024 Foo\n\
+                      // Licensed under the MIT license\n\
+                      //! The crate\n\
+                      /// A function\n\
+                      fn foo() {\n\
+                      // Just a comment\n\
+                      /* A block */\n\
+                      }\n";
+        assert_eq!(
+            kinds::<RustParser>(source, "foo.rs"),
+            [
+                (CommentKind::License, "// Copyright 2024 Foo".to_owned()),
+                (
+                    CommentKind::License,
+                    "// Licensed under the MIT license".to_owned()
+                ),
+                (CommentKind::Doc, "//! The crate\n".to_owned()),
+                (CommentKind::Doc, "/// A function\n".to_owned()),
+                (CommentKind::Regular, "// Just a comment".to_owned()),
+                (CommentKind::Regular, "/* A block */".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn javascript_comments() {
+        let source = "/** JSDoc of foo */\n\
+                      function foo() {\n\
+                      // eslint-disable-next-line no-undef\n\
+                      return bar; // Just a comment\n\
+                      }\n";
+        assert_eq!(
+            kinds::<JavascriptParser>(source, "foo.js"),
+            [
+                (CommentKind::Doc, "/** JSDoc of foo */".to_owned()),
+                (
+                    CommentKind::Directive,
+                    "// eslint-disable-next-line no-undef".to_owned()
+                ),
+                (CommentKind::Regular, "// Just a comment".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn python_comments() {
+        let source = "# -*- coding: utf-8 -*-\n\
+                      \"\"\"The module\"\"\"\n\
+                      def foo():\n    \
+                          \"\"\"The function\"\"\"\n    \
+                          x = 1  # noqa: E501\n    \
+                          \"not a docstring\"\n";
+        assert_eq!(
+            kinds::<PythonParser>(source, "foo.py"),
+            [
+                (CommentKind::Directive, "# -*- coding: utf-8 -*-".to_owned()),
+                (CommentKind::Doc, "\"\"\"The module\"\"\"".to_owned()),
+                (CommentKind::Doc, "\"\"\"The function\"\"\"".to_owned()),
+                (CommentKind::Directive, "# noqa: E501".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn directive_comments() {
+        let source = "// eslint-disable-next-line no-undef\n\
+                      /* istanbul ignore next */\n\
+                      //#region Helpers\n\
+                      // We do not want eslint-disable here\n\
+                      /* Remove the noqa once fixed */\n\
+                      foo();\n";
+        assert_eq!(
+            kinds::<JavascriptParser>(source, "foo.js"),
+            [
+                (
+                    CommentKind::Directive,
+                    "// eslint-disable-next-line no-undef".to_owned()
+                ),
+                (
+                    CommentKind::Directive,
+                    "/* istanbul ignore next */".to_owned()
+                ),
+                (CommentKind::Directive, "//#region Helpers".to_owned()),
+                (
+                    CommentKind::Regular,
+                    "// We do not want eslint-disable here".to_owned()
+                ),
+                (
+                    CommentKind::Regular,
+                    "/* Remove the noqa once fixed */".to_owned()
+                ),
+            ]
+        );
+
+        let source = "x = 1  # type: ignore\n\
+                      #region Helpers\n\
+                      # Do not add noqa here\n";
+        assert_eq!(
+            kinds::<PythonParser>(source, "foo.py"),
+            [
+                (CommentKind::Directive, "# type: ignore".to_owned()),
+                (CommentKind::Directive, "#region Helpers".to_owned()),
+                (CommentKind::Regular, "# Do not add noqa here".to_owned()),
+            ]
+        );
+    }
+
+    fn code<T: ParserTrait>(source: &str, path: &str) -> Vec<String> {
+        let parser = T::new(source.as_bytes().to_vec(), Path::new(path), None);
+        let code = parser.get_code();
+        commented_code(&parser)
+            .iter()
+            .map(|n| String::from_utf8_lossy(&code[n.start_byte()..n.end_byte()]).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn rust_commented_code() {
+        let source = "// Computes the answer.\n\
+                      fn foo() -> i32 {\n    \
+                          // let x = bar(\n    \
+                          //     1,\n    \
+                          // );\n    \
+                          /// let y = 2;\n    \
+                          42 // or maybe (41)\n\
+                      }\n\
+                      /* fn old() {\n \
+                       *     baz();\n \
+                       * }\n \
+                       */\n";
+        assert_eq!(
+            code::<RustParser>(source, "foo.rs"),
+            [
+                "// let x = bar(",
+                "//     1,",
+                "// );",
+                "/* fn old() {\n *     baz();\n * }\n */",
+            ]
+        );
+    }
+
+    #[test]
+    fn python_commented_code() {
+        let source = "# The old implementation:\n\
+                      # if x:\n\
+                      #     print(x)\n\
+                      # TODO: remove this\n\
+                      x = 1  # this is (not) code\n";
+        assert_eq!(
+            code::<PythonParser>(source, "foo.py"),
+            ["# if x:", "#     print(x)"]
+        );
+    }
+}
@@ -1,22 +1,33 @@
+use std::any::Any;
 use std::collections::HashMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam::channel::{Receiver, Sender, unbounded};
 use globset::GlobSet;
 use walkdir::{DirEntry, WalkDir};
 
-type ProcFilesFunction<Config> = dyn Fn(PathBuf, &Config) -> std::io::Result<()> + Send + Sync;
+use crate::node::{ParseCancelled, ParseLimits, with_parse_limits};
+use crate::skip::{SkipCfg, SkipReason, skip_reason};
+
+type ProcFilesFunction<Config, T> = dyn Fn(PathBuf, &Config) -> std::io::Result<T> + Send + Sync;
 
 type ProcDirPathsFunction<Config> =
     dyn Fn(&mut HashMap<String, Vec<PathBuf>>, &Path, &Config) + Send + Sync;
 
 type ProcPathFunction<Config> = dyn Fn(&Path, &Config) + Send + Sync;
 
+type ProcSkippedFunction<Config> = dyn Fn(&Path, &SkipReason, &Config) + Send + Sync;
+
 // Null functions removed at compile time
 fn null_proc_dir_paths<Config>(_: &mut HashMap<String, Vec<PathBuf>>, _: &Path, _: &Config) {}
 fn null_proc_path<Config>(_: &Path, _: &Config) {}
+fn null_proc_skipped<Config>(_: &Path, _: &SkipReason, _: &Config) {}
 
 #[derive(Debug)]
 struct JobItem<Config> {
@@ -24,23 +35,59 @@ struct JobItem<Config> {
     cfg: Arc<Config>,
 }
 
-type JobReceiver<Config> = Receiver<Option<JobItem<Config>>>;
-type JobSender<Config> = Sender<Option<JobItem<Config>>>;
+type JobReceiver<Config> = Receiver<JobItem<Config>>;
+type JobSender<Config> = Sender<JobItem<Config>>;
 
-fn consumer<Config, ProcFiles>(receiver: JobReceiver<Config>, func: Arc<ProcFiles>)
-where
-    ProcFiles: Fn(PathBuf, &Config) -> std::io::Result<()> + Send + Sync,
+type ResultSender<T> = Sender<(PathBuf, Result<T, FileError>, Duration)>;
+
+// The files found by the producer, and the ones it skipped.
+type Explored = (HashMap<String, Vec<PathBuf>>, Vec<(PathBuf, SkipReason)>);
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic"
+    }
+}
+
+fn consumer<Config, T, ProcFiles>(
+    receiver: JobReceiver<Config>,
+    func: Arc<ProcFiles>,
+    timeout: Option<Duration>,
+    progress: Arc<RunProgress>,
+    results: ResultSender<T>,
+) where
+    ProcFiles: Fn(PathBuf, &Config) -> std::io::Result<T> + Send + Sync + ?Sized,
 {
+    // The loop ends once the producer has finished and all jobs are done.
     while let Ok(job) = receiver.recv() {
-        if job.is_none() {
-            break;
-        }
-        // Cannot panic because of the check immediately above.
-        let job = job.unwrap();
         let path = job.path.clone();
 
-        if let Err(err) = func(job.path, &job.cfg) {
-            eprintln!("{err:?} for file {path:?}");
+        let limits = ParseLimits {
+            timeout,
+            cancellation_flag: None,
+        };
+        let start = Instant::now();
+
+        // A panic or a pathological file must not abort the whole run,
+        // so they are reported and the consumer moves to the next job.
+        let res = match panic::catch_unwind(AssertUnwindSafe(|| {
+            with_parse_limits(limits, || func(job.path, &job.cfg))
+        })) {
+            Ok(Ok(Ok(value))) => Ok(value),
+            Ok(Ok(Err(err))) => Err(FileError::Io(err)),
+            Ok(Err(cancelled)) => Err(FileError::Cancelled(cancelled)),
+            Err(payload) => Err(FileError::Panic(panic_message(payload.as_ref()).to_owned())),
+        };
+
+        let elapsed = start.elapsed();
+
+        progress.processed.fetch_add(1, Ordering::Relaxed);
+        if results.send((path, res, elapsed)).is_err() {
+            break;
         }
     }
 }
@@ -48,13 +95,15 @@ where
 fn send_file<T>(
     path: PathBuf,
     cfg: &Arc<T>,
+    progress: &RunProgress,
     sender: &JobSender<T>,
 ) -> Result<(), ConcurrentErrors> {
+    progress.discovered.fetch_add(1, Ordering::Relaxed);
     sender
-        .send(Some(JobItem {
+        .send(JobItem {
             path,
             cfg: Arc::clone(cfg),
-        }))
+        })
         .map_err(|e| ConcurrentErrors::Sender(e.to_string()))
 }
 
@@ -66,16 +115,49 @@ fn is_hidden(entry: &DirEntry) -> bool {
         .unwrap_or(false)
 }
 
-fn explore<Config, ProcDirPaths, ProcPath>(
+// Returns true when a file must be skipped, after having processed it.
+fn is_skipped<Config, ProcSkipped>(
+    path: &Path,
+    cfg: &Config,
+    skip: Option<&SkipCfg>,
+    proc_skipped: &ProcSkipped,
+    progress: &RunProgress,
+    skipped: &mut Vec<(PathBuf, SkipReason)>,
+) -> bool
+where
+    ProcSkipped: Fn(&Path, &SkipReason, &Config) + Send + Sync,
+{
+    let Some(skip) = skip else {
+        return false;
+    };
+    match skip_reason(path, skip) {
+        Ok(Some(reason)) => {
+            proc_skipped(path, &reason, cfg);
+            progress.skipped.fetch_add(1, Ordering::Relaxed);
+            skipped.push((path.to_path_buf(), reason));
+            true
+        }
+        Ok(None) => false,
+        // The error is reported when the file is processed
+        Err(_) => false,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn explore<Config, ProcDirPaths, ProcPath, ProcSkipped>(
     files_data: FilesData,
     cfg: &Arc<Config>,
     proc_dir_paths: ProcDirPaths,
     proc_path: ProcPath,
+    skip: Option<SkipCfg>,
+    proc_skipped: ProcSkipped,
+    progress: &RunProgress,
     sender: &JobSender<Config>,
-) -> Result<HashMap<String, Vec<PathBuf>>, ConcurrentErrors>
+) -> Result<Explored, ConcurrentErrors>
 where
     ProcDirPaths: Fn(&mut HashMap<String, Vec<PathBuf>>, &Path, &Config) + Send + Sync,
     ProcPath: Fn(&Path, &Config) + Send + Sync,
+    ProcSkipped: Fn(&Path, &SkipReason, &Config) + Send + Sync,
 {
     let FilesData {
         mut paths,
@@ -84,6 +166,7 @@ where
     } = files_data;
 
     let mut all_files: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    let mut skipped = Vec::new();
 
     for path in paths.drain(..) {
         if !path.exists() {
@@ -103,21 +186,32 @@ where
                 if (include.is_empty() || include.is_match(&path))
                     && (exclude.is_empty() || !exclude.is_match(&path))
                     && path.is_file()
+                    && !is_skipped(
+                        &path,
+                        cfg.as_ref(),
+                        skip.as_ref(),
+                        &proc_skipped,
+                        progress,
+                        &mut skipped,
+                    )
                 {
                     proc_dir_paths(&mut all_files, &path, cfg);
-                    send_file(path, cfg, sender)?;
+                    send_file(path, cfg, progress, sender)?;
                 }
             }
         } else if (include.is_empty() || include.is_match(&path))
             && (exclude.is_empty() || !exclude.is_match(&path))
             && path.is_file()
         {
+            // The files named explicitly are always analyzed
             proc_path(&path, cfg);
-            send_file(path, cfg, sender)?;
+            send_file(path, cfg, progress, sender)?;
         }
     }
 
-    Ok(all_files)
+    progress.explored.store(true, Ordering::Relaxed);
+
+    Ok((all_files, skipped))
 }
 
 /// Series of errors that might happen when processing files concurrently.
@@ -141,6 +235,94 @@ pub enum ConcurrentErrors {
     Thread(String),
 }
 
+/// An error that happened while processing a single file.
+#[derive(Debug)]
+pub enum FileError {
+    /// I/O error.
+    ///
+    /// The function processing the file returned an error.
+    Io(std::io::Error),
+    /// Cancellation error.
+    ///
+    /// The parsing of the file has been interrupted.
+    Cancelled(ParseCancelled),
+    /// Panic error.
+    ///
+    /// The function processing the file panicked with the given message.
+    Panic(String),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Cancelled(cancelled) => write!(f, "{cancelled}"),
+            Self::Panic(msg) => write!(f, "panic \"{msg}\""),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+/// The results of processing files concurrently.
+#[derive(Debug)]
+pub struct RunResults<T> {
+    /// The values returned by the function processing the files,
+    /// along with the path of each file.
+    pub values: Vec<(PathBuf, T)>,
+    /// The errors that happened while processing the files,
+    /// along with the path of each file.
+    pub errors: Vec<(PathBuf, FileError)>,
+    /// The files skipped because generated, minified or oversized,
+    /// along with the reason.
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+    /// The time spent processing each file, along with its path.
+    pub durations: Vec<(PathBuf, Duration)>,
+    /// The paths collected by the function processing the paths
+    /// contained in a directory.
+    pub all_files: HashMap<String, Vec<PathBuf>>,
+}
+
+/// Counters describing the progress of a run.
+///
+/// They are updated while files are discovered and processed,
+/// so they can be read from another thread to report the progress.
+#[derive(Debug, Default)]
+pub struct RunProgress {
+    discovered: AtomicUsize,
+    processed: AtomicUsize,
+    skipped: AtomicUsize,
+    explored: AtomicBool,
+}
+
+impl RunProgress {
+    /// Creates new counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of files discovered so far which are going
+    /// to be processed.
+    pub fn discovered(&self) -> usize {
+        self.discovered.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of files processed so far.
+    pub fn processed(&self) -> usize {
+        self.processed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of files skipped so far.
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Checks whether all the files have been discovered.
+    pub fn is_explored(&self) -> bool {
+        self.explored.load(Ordering::Relaxed)
+    }
+}
+
 /// Data related to files.
 #[derive(Debug)]
 pub struct FilesData {
@@ -153,14 +335,24 @@ pub struct FilesData {
 }
 
 /// A runner to process files concurrently.
-pub struct ConcurrentRunner<Config> {
-    proc_files: Box<ProcFilesFunction<Config>>,
+///
+/// The function processing a file might return a value of type `T`,
+/// which is collected by [`collect`].
+///
+/// [`collect`]: #method.collect
+pub struct ConcurrentRunner<Config, T = ()> {
+    proc_files: Arc<ProcFilesFunction<Config, T>>,
     proc_dir_paths: Box<ProcDirPathsFunction<Config>>,
     proc_path: Box<ProcPathFunction<Config>>,
+    proc_skipped: Box<ProcSkippedFunction<Config>>,
     num_jobs: usize,
+    timeout: Option<Duration>,
+    skip: Option<SkipCfg>,
+    sorted: bool,
+    progress: Arc<RunProgress>,
 }
 
-impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
+impl<Config: 'static + Send + Sync, T: 'static + Send> ConcurrentRunner<Config, T> {
     /// Creates a new `ConcurrentRunner`.
     ///
     /// * `num_jobs` - Number of jobs utilized to process files concurrently.
@@ -168,14 +360,19 @@ impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
     ///   the search.
     pub fn new<ProcFiles>(num_jobs: usize, proc_files: ProcFiles) -> Self
     where
-        ProcFiles: 'static + Fn(PathBuf, &Config) -> std::io::Result<()> + Send + Sync,
+        ProcFiles: 'static + Fn(PathBuf, &Config) -> std::io::Result<T> + Send + Sync,
     {
         let num_jobs = std::cmp::max(2, num_jobs) - 1;
         Self {
-            proc_files: Box::new(proc_files),
+            proc_files: Arc::new(proc_files),
             proc_dir_paths: Box::new(null_proc_dir_paths),
             proc_path: Box::new(null_proc_path),
+            proc_skipped: Box::new(null_proc_skipped),
             num_jobs,
+            timeout: None,
+            skip: None,
+            sorted: false,
+            progress: Arc::default(),
         }
     }
 
@@ -199,9 +396,60 @@ impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
         self
     }
 
+    /// Sets the criteria used to skip generated, minified and oversized
+    /// files.
+    ///
+    /// Only the files found by exploring a directory can be skipped,
+    /// the files given explicitly are always processed.
+    /// By default, no file is skipped.
+    pub fn set_skip(mut self, skip: SkipCfg) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// Sets the function to process a skipped file.
+    pub fn set_proc_skipped<ProcSkipped>(mut self, proc_skipped: ProcSkipped) -> Self
+    where
+        ProcSkipped: 'static + Fn(&Path, &SkipReason, &Config) + Send + Sync,
+    {
+        self.proc_skipped = Box::new(proc_skipped);
+        self
+    }
+
+    /// Sets the time budget granted to each file.
+    ///
+    /// Parsing a file is interrupted once its budget is exhausted,
+    /// and the file is reported as an error.
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sorts the values and the errors returned by [`collect`]
+    /// by file path.
+    ///
+    /// By default, they are in the order in which files are processed,
+    /// which changes from run to run.
+    ///
+    /// [`collect`]: #method.collect
+    pub fn set_sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Sets the counters updated while files are discovered
+    /// and processed.
+    pub fn set_progress(mut self, progress: Arc<RunProgress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
     /// Runs the producer-consumer approach to process the files
     /// contained in a directory and in its own subdirectories.
     ///
+    /// The values returned by the function processing the files are
+    /// discarded, while errors are printed on stderr.
+    ///
     /// * `config` - Information used to process a file.
     /// * `files_data` - Information about the files to be included or excluded
     ///   from a search more the number of paths considered in the search.
@@ -210,13 +458,78 @@ impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
         config: Config,
         files_data: FilesData,
     ) -> Result<HashMap<String, Vec<PathBuf>>, ConcurrentErrors> {
+        let (all_files, _) = self.execute(config, files_data, |path, res, _| match res {
+            Ok(_) => {}
+            Err(FileError::Io(err)) => eprintln!("{err:?} for file {path:?}"),
+            Err(FileError::Cancelled(cancelled)) => {
+                eprintln!("Warning: {cancelled} for file {path:?}")
+            }
+            Err(err) => eprintln!("Error: {err} for file {path:?}"),
+        })?;
+
+        Ok(all_files)
+    }
+
+    /// Runs the producer-consumer approach to process the files
+    /// contained in a directory and in its own subdirectories,
+    /// collecting the values returned for each file and the errors.
+    ///
+    /// * `config` - Information used to process a file.
+    /// * `files_data` - Information about the files to be included or excluded
+    ///   from a search more the number of paths considered in the search.
+    pub fn collect(
+        self,
+        config: Config,
+        files_data: FilesData,
+    ) -> Result<RunResults<T>, ConcurrentErrors> {
+        let sorted = self.sorted;
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        let mut durations = Vec::new();
+
+        let (all_files, mut skipped) = self.execute(config, files_data, |path, res, elapsed| {
+            durations.push((path.clone(), elapsed));
+            match res {
+                Ok(value) => values.push((path, value)),
+                Err(err) => errors.push((path, err)),
+            }
+        })?;
+
+        if sorted {
+            values.sort_by(|(a, _), (b, _)| a.cmp(b));
+            errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+            skipped.sort_by(|(a, _), (b, _)| a.cmp(b));
+            durations.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+
+        Ok(RunResults {
+            values,
+            errors,
+            skipped,
+            durations,
+            all_files,
+        })
+    }
+
+    fn execute<ProcResult>(
+        self,
+        config: Config,
+        files_data: FilesData,
+        mut proc_result: ProcResult,
+    ) -> Result<Explored, ConcurrentErrors>
+    where
+        ProcResult: FnMut(PathBuf, Result<T, FileError>, Duration),
+    {
         let cfg = Arc::new(config);
 
         let (sender, receiver) = unbounded();
+        let (results_sender, results_receiver) = unbounded();
+        let progress = self.progress;
 
-        let producer = {
-            let sender = sender.clone();
-
+        // The producer owns the only sender, so the consumers stop
+        // once it has finished, even when it fails.
+        let producer_progress = progress.clone();
+        let producer =
             match thread::Builder::new()
                 .name(String::from("Producer"))
                 .spawn(move || {
@@ -225,24 +538,28 @@ impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
                         &cfg,
                         self.proc_dir_paths,
                         self.proc_path,
+                        self.skip,
+                        self.proc_skipped,
+                        &producer_progress,
                         &sender,
                     )
                 }) {
                 Ok(producer) => producer,
                 Err(e) => return Err(ConcurrentErrors::Thread(e.to_string())),
-            }
-        };
+            };
 
         let mut receivers = Vec::with_capacity(self.num_jobs);
-        let proc_files = Arc::new(self.proc_files);
+        let timeout = self.timeout;
         for i in 0..self.num_jobs {
             let receiver = receiver.clone();
-            let proc_files = proc_files.clone();
+            let proc_files = self.proc_files.clone();
+            let results_sender = results_sender.clone();
+            let progress = progress.clone();
 
             let t = match thread::Builder::new()
                 .name(format!("Consumer {i}"))
                 .spawn(move || {
-                    consumer(receiver, proc_files);
+                    consumer(receiver, proc_files, timeout, progress, results_sender);
                 }) {
                 Ok(receiver) => receiver,
                 Err(e) => return Err(ConcurrentErrors::Thread(e.to_string())),
@@ -250,8 +567,15 @@ impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
 
             receivers.push(t);
         }
+        drop(results_sender);
+
+        // Results are processed as soon as they are available, until all
+        // the consumers have finished.
+        for (path, res, elapsed) in results_receiver {
+            proc_result(path, res, elapsed);
+        }
 
-        let all_files = match producer.join() {
+        let explored = match producer.join() {
             Ok(res) => res,
             Err(_) => {
                 return Err(ConcurrentErrors::Producer(
@@ -260,13 +584,6 @@ impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
             }
         };
 
-        // Poison the receiver, now that the producer is finished.
-        for _ in 0..self.num_jobs {
-            if let Err(e) = sender.send(None) {
-                return Err(ConcurrentErrors::Sender(e.to_string()));
-            }
-        }
-
         for receiver in receivers {
             if receiver.join().is_err() {
                 return Err(ConcurrentErrors::Receiver(
@@ -275,6 +592,78 @@ impl<Config: 'static + Send + Sync> ConcurrentRunner<Config> {
             }
         }
 
-        all_files
+        explored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use globset::{Glob, GlobSetBuilder};
+
+    use super::*;
+
+    #[test]
+    fn collect_sorted_values() {
+        let mut include = GlobSetBuilder::new();
+        include.add(Glob::new("*.rs").unwrap());
+
+        let files_data = FilesData {
+            include: include.build().unwrap(),
+            exclude: GlobSet::empty(),
+            paths: vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("src/metrics")],
+        };
+
+        let results = ConcurrentRunner::new(4, |path: PathBuf, _: &()| {
+            if path.ends_with("mod.rs") {
+                Err(std::io::Error::other("unexpected file"))
+            } else {
+                Ok(std::fs::metadata(&path)?.len())
+            }
+        })
+        .set_sorted(true)
+        .collect((), files_data)
+        .unwrap();
+
+        assert!(results.values.len() > 1);
+        assert!(results.values.iter().all(|(_, len)| *len > 0));
+        assert!(results.values.windows(2).all(|v| v[0].0 < v[1].0));
+
+        assert_eq!(results.errors.len(), 1);
+        assert!(results.errors[0].0.ends_with("metrics/mod.rs"));
+        assert!(matches!(results.errors[0].1, FileError::Io(_)));
+    }
+
+    #[test]
+    fn skip_only_explored_files() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let explicit = root.join("src/lib.rs");
+
+        let files_data = FilesData {
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+            paths: vec![root.join("src/metrics"), explicit.clone()],
+        };
+
+        let skip = SkipCfg {
+            max_size: Some(1),
+            max_avg_line_length: None,
+            generated_markers: Vec::new(),
+        };
+
+        let results = ConcurrentRunner::new(4, |path: PathBuf, _: &()| Ok(path))
+            .set_skip(skip)
+            .collect((), files_data)
+            .unwrap();
+
+        assert_eq!(results.values.len(), 1);
+        assert_eq!(results.values[0].0, explicit);
+        assert!(results.skipped.len() > 1);
+        assert!(
+            results
+                .skipped
+                .iter()
+                .all(|(path, reason)| path.starts_with(root.join("src/metrics"))
+                    && matches!(reason, SkipReason::TooLarge(_)))
+        );
     }
 }
@@ -1,9 +1,14 @@
 extern crate num_format;
 
 use num_format::{Locale, ToFormattedString};
+use serde::ser::{Serialize, SerializeSeq, SerializeStruct, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
+use crate::find::{run_queries, split_queries};
 use crate::traits::*;
 
 /// Counts the types of nodes specified in the input slice
@@ -41,6 +46,9 @@ pub fn count<T: ParserTrait>(parser: &T, filters: &[String]) -> (usize, usize) {
 #[derive(Debug)]
 pub struct CountCfg {
     /// Types of nodes to count
+    ///
+    /// Filters starting with a parenthesis or with a bracket
+    /// are tree-sitter queries, whose matches are counted
     pub filters: Vec<String>,
     /// Number of nodes of a certain type counted by each thread
     pub stats: Arc<Mutex<Count>>,
@@ -60,7 +68,14 @@ impl Callback for Count {
     type Cfg = CountCfg;
 
     fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
-        let (good, total) = count(parser, &cfg.filters);
+        let (queries, filters) = split_queries(&cfg.filters);
+        let matches = run_queries(parser, &queries)?.len();
+        let (good, total) = count(parser, &filters);
+        let good = if filters.is_empty() && !queries.is_empty() {
+            matches
+        } else {
+            good + matches
+        };
         let mut results = cfg.stats.lock().unwrap();
         results.good += good;
         results.total += total;
@@ -68,6 +83,26 @@ impl Callback for Count {
     }
 }
 
+impl Count {
+    /// Returns the percentage of the nodes searched in a code.
+    pub fn percentage(&self) -> f64 {
+        (self.good as f64) / (self.total as f64) * 100.
+    }
+}
+
+impl Serialize for Count {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("count", 3)?;
+        st.serialize_field("good", &self.good)?;
+        st.serialize_field("total", &self.total)?;
+        st.serialize_field("percentage", &self.percentage())?;
+        st.end()
+    }
+}
+
 impl fmt::Display for Count {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(
@@ -80,10 +115,404 @@ impl fmt::Display for Count {
             "Found nodes: {}",
             self.good.to_formatted_string(&Locale::en)
         )?;
-        write!(
-            f,
-            "Percentage: {:.2}%",
-            (self.good as f64) / (self.total as f64) * 100.
-        )
+        write!(f, "Percentage: {:.2}%", self.percentage())
+    }
+}
+
+/// Number of nodes of each kind, and of nodes found by each filter.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    /// The number of nodes of each kind, among the ones found by
+    /// the filters which are not queries
+    pub kinds: HashMap<String, usize>,
+    /// The number of nodes found by each filter, or of matches
+    /// for the filters which are queries
+    pub filters: HashMap<String, usize>,
+    /// The total number of nodes in a code
+    pub total: usize,
+}
+
+impl Histogram {
+    /// Merges a histogram into this one.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (kind, count) in &other.kinds {
+            *self.kinds.entry(kind.clone()).or_default() += count;
+        }
+        for (filter, count) in &other.filters {
+            *self.filters.entry(filter.clone()).or_default() += count;
+        }
+        self.total += other.total;
+    }
+}
+
+/// The order of the entries of a histogram.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HistogramSort {
+    /// From the most frequent entry to the least frequent one
+    #[default]
+    Count,
+    /// In alphabetical order
+    Name,
+}
+
+impl HistogramSort {
+    /// Returns the entries of a map in this order.
+    pub fn sort<'a>(&self, map: &'a HashMap<String, usize>) -> Vec<(&'a str, usize)> {
+        let mut entries: Vec<_> = map.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        match self {
+            Self::Count => entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0))),
+            Self::Name => entries.sort(),
+        }
+        entries
+    }
+}
+
+impl FromStr for HistogramSort {
+    type Err = String;
+
+    fn from_str(sort: &str) -> Result<Self, Self::Err> {
+        match sort {
+            "count" => Ok(Self::Count),
+            "name" => Ok(Self::Name),
+            sort => Err(format!("{sort:?} is not a supported order")),
+        }
+    }
+}
+
+/// Computes the histogram of the nodes of a code.
+///
+/// When no filter is given, all the nodes are considered.
+pub fn histogram<T: ParserTrait>(parser: &T, filters: &[String]) -> std::io::Result<Histogram> {
+    let (queries, filters) = split_queries(filters);
+    let mut histogram = Histogram::default();
+
+    for query in queries {
+        let matches = run_queries(parser, std::slice::from_ref(&query))?.len();
+        histogram.filters.insert(query, matches);
+    }
+
+    let any = if filters.is_empty() && !histogram.filters.is_empty() {
+        None
+    } else {
+        Some(parser.get_filters(&filters))
+    };
+    let each: Vec<_> = filters
+        .iter()
+        .map(|f| (f, parser.get_filters(std::slice::from_ref(f))))
+        .collect();
+    for (filter, _) in &each {
+        histogram.filters.insert((*filter).clone(), 0);
+    }
+
+    let node = parser.get_root();
+    let mut cursor = node.cursor();
+    let mut stack = vec![node];
+
+    while let Some(node) = stack.pop() {
+        histogram.total += 1;
+        if any.as_ref().is_some_and(|any| any.any(&node)) {
+            *histogram.kinds.entry(node.kind().to_owned()).or_default() += 1;
+            for (filter, f) in &each {
+                if f.any(&node) {
+                    *histogram.filters.get_mut(filter.as_str()).unwrap() += 1;
+                }
+            }
+        }
+        cursor.reset(&node);
+        if cursor.goto_first_child() {
+            loop {
+                stack.push(cursor.node());
+                if !cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(histogram)
+}
+
+/// Histograms of the nodes of each file, and of all the files.
+#[derive(Debug, Default)]
+pub struct Histograms {
+    /// The histogram of each file
+    pub files: BTreeMap<PathBuf, Histogram>,
+    /// The histogram of all the files
+    pub total: Histogram,
+    /// The order of the entries, both when printed and serialized
+    pub sort: HistogramSort,
+}
+
+impl Histograms {
+    /// Adds the histogram of a file.
+    pub fn add(&mut self, path: PathBuf, histogram: Histogram) {
+        self.total.merge(&histogram);
+        self.files.insert(path, histogram);
+    }
+}
+
+/// Configuration options for computing the histogram
+/// of the nodes in a code.
+#[derive(Debug)]
+pub struct HistogramCfg {
+    /// Path to the file containing the code
+    pub path: PathBuf,
+    /// Types of nodes to count
+    ///
+    /// Filters starting with a parenthesis or with a bracket
+    /// are tree-sitter queries, whose matches are counted
+    pub filters: Vec<String>,
+    /// Histograms computed by each thread
+    pub stats: Arc<Mutex<Histograms>>,
+}
+
+/// Computes the histogram of the nodes of a code
+/// and adds it to the histograms of all the files.
+pub struct HistogramCount {
+    _guard: (),
+}
+
+impl Callback for HistogramCount {
+    type Res = std::io::Result<()>;
+    type Cfg = HistogramCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        let histogram = histogram(parser, &cfg.filters)?;
+        cfg.stats.lock().unwrap().add(cfg.path, histogram);
+        Ok(())
+    }
+}
+
+struct Entry<'a>(&'static str, &'a str, usize);
+
+impl Serialize for Entry<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("entry", 2)?;
+        st.serialize_field(self.0, self.1)?;
+        st.serialize_field("count", &self.2)?;
+        st.end()
+    }
+}
+
+struct Entries<'a>(&'static str, &'a HashMap<String, usize>, HistogramSort);
+
+impl Serialize for Entries<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.1.len()))?;
+        for (key, count) in self.2.sort(self.1) {
+            seq.serialize_element(&Entry(self.0, key, count))?;
+        }
+        seq.end()
+    }
+}
+
+struct SortedHistogram<'a>(Option<&'a PathBuf>, &'a Histogram, HistogramSort);
+
+impl Serialize for SortedHistogram<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let Self(path, histogram, sort) = *self;
+        let mut st = serializer.serialize_struct("histogram", 4)?;
+        if let Some(path) = path {
+            st.serialize_field("file", path)?;
+        }
+        st.serialize_field("total", &histogram.total)?;
+        st.serialize_field("kinds", &Entries("kind", &histogram.kinds, sort))?;
+        st.serialize_field("filters", &Entries("filter", &histogram.filters, sort))?;
+        st.end()
+    }
+}
+
+struct Files<'a>(&'a Histograms);
+
+impl Serialize for Files<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.0.files.len()))?;
+        for (path, histogram) in &self.0.files {
+            seq.serialize_element(&SortedHistogram(Some(path), histogram, self.0.sort))?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for Histograms {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("histograms", 2)?;
+        st.serialize_field("total", &SortedHistogram(None, &self.total, self.sort))?;
+        st.serialize_field("files", &Files(self))?;
+        st.end()
+    }
+}
+
+fn fmt_histogram(
+    f: &mut fmt::Formatter,
+    histogram: &Histogram,
+    sort: HistogramSort,
+) -> fmt::Result {
+    writeln!(
+        f,
+        "  Total nodes: {}",
+        histogram.total.to_formatted_string(&Locale::en)
+    )?;
+    for (title, map) in [("Kinds", &histogram.kinds), ("Filters", &histogram.filters)] {
+        if map.is_empty() {
+            continue;
+        }
+        writeln!(f, "  {title}:")?;
+        for (key, count) in sort.sort(map) {
+            writeln!(f, "{:>12} {key}", count.to_formatted_string(&Locale::en))?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Histograms {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (path, histogram) in &self.files {
+            writeln!(f, "In file {}", path.display())?;
+            fmt_histogram(f, histogram, self.sort)?;
+        }
+        writeln!(f, "In all files")?;
+        fmt_histogram(f, &self.total, self.sort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::RustParser;
+
+    use super::*;
+
+    #[test]
+    fn histogram_per_kind_and_filter() {
+        let source = "fn foo() { unsafe { bar(); } let f = || baz!(); unsafe { f() } }";
+        let parser = RustParser::new(source.as_bytes().to_vec(), Path::new("foo.rs"), None);
+
+        let filters = [
+            "unsafe_block".to_owned(),
+            "macro_invocation".to_owned(),
+            "closure_expression".to_owned(),
+            "(call_expression) @call".to_owned(),
+        ];
+        let histogram = histogram(&parser, &filters).unwrap();
+
+        let kinds = HistogramSort::Count.sort(&histogram.kinds);
+        assert_eq!(
+            kinds,
+            [
+                ("unsafe_block", 2),
+                ("closure_expression", 1),
+                ("macro_invocation", 1)
+            ]
+        );
+        assert_eq!(histogram.filters["(call_expression) @call"], 2);
+        assert_eq!(histogram.filters["unsafe_block"], 2);
+
+        let mut histograms = Histograms {
+            sort: HistogramSort::Name,
+            ..Default::default()
+        };
+        histograms.add(PathBuf::from("a.rs"), histogram.clone());
+        histograms.add(PathBuf::from("b.rs"), histogram);
+        assert_eq!(histograms.total.kinds["unsafe_block"], 4);
+        assert_eq!(histograms.total.filters["macro_invocation"], 2);
+
+        histograms.files.remove(Path::new("a.rs"));
+        insta::assert_json_snapshot!(
+            histograms,
+            @r###"
+            {
+              "total": {
+                "total": 98,
+                "kinds": [
+                  {
+                    "kind": "closure_expression",
+                    "count": 2
+                  },
+                  {
+                    "kind": "macro_invocation",
+                    "count": 2
+                  },
+                  {
+                    "kind": "unsafe_block",
+                    "count": 4
+                  }
+                ],
+                "filters": [
+                  {
+                    "filter": "(call_expression) @call",
+                    "count": 4
+                  },
+                  {
+                    "filter": "closure_expression",
+                    "count": 2
+                  },
+                  {
+                    "filter": "macro_invocation",
+                    "count": 2
+                  },
+                  {
+                    "filter": "unsafe_block",
+                    "count": 4
+                  }
+                ]
+              },
+              "files": [
+                {
+                  "file": "b.rs",
+                  "total": 49,
+                  "kinds": [
+                    {
+                      "kind": "closure_expression",
+                      "count": 1
+                    },
+                    {
+                      "kind": "macro_invocation",
+                      "count": 1
+                    },
+                    {
+                      "kind": "unsafe_block",
+                      "count": 2
+                    }
+                  ],
+                  "filters": [
+                    {
+                      "filter": "(call_expression) @call",
+                      "count": 2
+                    },
+                    {
+                      "filter": "closure_expression",
+                      "count": 1
+                    },
+                    {
+                      "filter": "macro_invocation",
+                      "count": 1
+                    },
+                    {
+                      "filter": "unsafe_block",
+                      "count": 2
+                    }
+                  ]
+                }
+              ]
+            }
+            "###
+        );
     }
 }
@@ -0,0 +1,549 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::spaces::{FuncSpace, SpaceKind};
+use crate::tools::normalize_path;
+
+static ATTRIBUTE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// The line coverage of a file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    // The number of executions of each executable line
+    hits: BTreeMap<usize, u64>,
+}
+
+impl FileCoverage {
+    fn add(&mut self, line: usize, hits: u64) {
+        let count = self.hits.entry(line).or_default();
+        *count = (*count).max(hits);
+    }
+
+    /// Returns the number of executable lines and of executed lines
+    /// between two lines, inclusive.
+    pub fn lines_in(&self, start_line: usize, end_line: usize) -> (usize, usize) {
+        if start_line > end_line {
+            return (0, 0);
+        }
+        self.hits
+            .range(start_line..=end_line)
+            .fold((0, 0), |(lines, covered), (_, hits)| {
+                (lines + 1, covered + usize::from(*hits > 0))
+            })
+    }
+
+    /// Computes the `Coverage` metric of a space and of its subspaces.
+    pub fn apply(&self, space: &mut FuncSpace) {
+        let cyclomatic = space.metrics.cyclomatic.cyclomatic();
+        let (lines, covered) = self.lines_in(space.start_line, space.end_line);
+        space.metrics.coverage.compute(
+            lines,
+            covered,
+            cyclomatic,
+            space.kind == SpaceKind::Function,
+        );
+        for subspace in &mut space.spaces {
+            self.apply(subspace);
+            space.metrics.coverage.merge(&subspace.metrics.coverage);
+        }
+    }
+}
+
+/// A coverage report, in the `LCOV` or in the `Cobertura` format.
+///
+/// The relative paths of a report are relative to a root directory.
+/// The absolute paths outside of the root, as the ones of a report
+/// produced on another machine, are matched with the analyzed files
+/// whose paths relative to the root are their last components.
+#[derive(Clone, Debug, Default)]
+pub struct CoverageReport {
+    // The coverage of each file, by path relative to the root,
+    // or by absolute path for the files outside of the root
+    files: HashMap<PathBuf, FileCoverage>,
+    // The absolute paths outside of the root ending with each relative path
+    suffixes: HashMap<PathBuf, Vec<PathBuf>>,
+    // The absolute root directory
+    root: PathBuf,
+}
+
+// Replaces the predefined entities of an `XML` text.
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+impl CoverageReport {
+    /// Parses a report whose relative paths are relative to a root directory.
+    pub fn new(report: &str, root: &Path) -> Result<Self, String> {
+        let files = if report.trim_start().starts_with('<') {
+            Self::from_cobertura(report)?
+        } else {
+            Self::from_lcov(report)?
+        };
+        if files.is_empty() {
+            return Err("the report does not contain any line coverage".to_string());
+        }
+
+        let root = std::path::absolute(root)
+            .map(normalize_path)
+            .map_err(|e| format!("invalid root directory {root:?}: {e}"))?;
+        let mut report = Self {
+            root,
+            ..Default::default()
+        };
+        for (path, coverage) in files {
+            let path = report.relative(&path);
+            if path.is_absolute() {
+                let mut suffix = path.components();
+                while suffix.next().is_some() && suffix.as_path() != Path::new("") {
+                    report
+                        .suffixes
+                        .entry(suffix.as_path().to_path_buf())
+                        .or_default()
+                        .push(path.clone());
+                }
+            }
+            let file = report.files.entry(path).or_default();
+            for (line, hits) in coverage.hits {
+                file.add(line, hits);
+            }
+        }
+        Ok(report)
+    }
+
+    // Returns a normalized path relative to the root,
+    // or an absolute one when it is outside of the root.
+    fn relative(&self, path: &Path) -> PathBuf {
+        let path = normalize_path(path);
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path,
+        }
+    }
+
+    // Parses a report in the `LCOV` format, as produced by `grcov`,
+    // `cargo llvm-cov`, `coverage.py` or `c8`.
+    fn from_lcov(report: &str) -> Result<BTreeMap<PathBuf, FileCoverage>, String> {
+        let mut files: BTreeMap<PathBuf, FileCoverage> = BTreeMap::new();
+        let mut file = None;
+
+        for (index, line) in report.lines().enumerate() {
+            let line = line.trim();
+            if let Some(path) = line.strip_prefix("SF:") {
+                file = Some(normalize_path(path));
+            } else if let Some(data) = line.strip_prefix("DA:") {
+                let error = || format!("invalid LCOV line data at line {}: {line:?}", index + 1);
+                let path = file.clone().ok_or_else(error)?;
+                let mut fields = data.split(',');
+                let number = fields.next().and_then(|n| n.parse().ok());
+                let hits = fields.next().and_then(|n| n.parse().ok());
+                let (Some(number), Some(hits)) = (number, hits) else {
+                    return Err(error());
+                };
+                files.entry(path).or_default().add(number, hits);
+            } else if line == "end_of_record" {
+                file = None;
+            }
+        }
+
+        Ok(files)
+    }
+
+    // Parses a report in the `Cobertura` XML format, as produced by
+    // `coverage.py`, `cargo tarpaulin` or `istanbul`.
+    //
+    // The file names are relative to the source directories of the report.
+    fn from_cobertura(report: &str) -> Result<BTreeMap<PathBuf, FileCoverage>, String> {
+        let attribute = ATTRIBUTE_REGEX
+            .get_or_init(|| Regex::new(r#"([\w-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+        let mut files: BTreeMap<PathBuf, FileCoverage> = BTreeMap::new();
+        let mut sources = Vec::new();
+        let mut file = None;
+
+        if !report.contains("<coverage") {
+            return Err("the XML report is not in the Cobertura format".to_string());
+        }
+
+        for tag in report.split('<').skip(1) {
+            let (tag, text) = tag.split_once('>').unwrap_or((tag, ""));
+            let name = tag.split_whitespace().next().unwrap_or_default();
+            let value = |name: &str| {
+                attribute.captures_iter(tag).find_map(|captures| {
+                    (&captures[1] == name)
+                        .then(|| captures.get(2).or(captures.get(3)))
+                        .flatten()
+                        .map(|value| unescape_xml(value.as_str()))
+                })
+            };
+            match name {
+                "source" if !text.trim().is_empty() => {
+                    sources.push(normalize_path(unescape_xml(text.trim())));
+                }
+                "class" => {
+                    file = value("filename").map(normalize_path);
+                }
+                "/class" => file = None,
+                "line" => {
+                    let (Some(path), Some(number), Some(hits)) =
+                        (&file, value("number"), value("hits"))
+                    else {
+                        continue;
+                    };
+                    let (Ok(number), Ok(hits)) = (number.parse(), hits.parse()) else {
+                        return Err(format!("invalid Cobertura line: <{tag}>"));
+                    };
+                    files.entry(path.clone()).or_default().add(number, hits);
+                }
+                _ => {}
+            }
+        }
+
+        if sources.is_empty() {
+            return Ok(files);
+        }
+        // A relative file name belongs to the first source directory containing it,
+        // or to the first source directory when the sources are not on this machine
+        let mut resolved: BTreeMap<PathBuf, FileCoverage> = BTreeMap::new();
+        for (path, coverage) in files {
+            let path = if path.is_absolute() {
+                path
+            } else {
+                sources
+                    .iter()
+                    .map(|source| normalize_path(source.join(&path)))
+                    .find(|path| path.exists())
+                    .unwrap_or_else(|| normalize_path(sources[0].join(&path)))
+            };
+            let file = resolved.entry(path).or_default();
+            for (line, hits) in coverage.hits {
+                file.add(line, hits);
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Returns the coverage of a file, if it is part of the report.
+    ///
+    /// Returns an error when the file is not in the report, but its path
+    /// relative to the root ends several paths of the report.
+    pub fn file(&self, path: &Path) -> Result<Option<&FileCoverage>, String> {
+        let path = self.relative(&std::path::absolute(path).unwrap_or(path.to_path_buf()));
+        if let Some(coverage) = self.files.get(&path) {
+            return Ok(Some(coverage));
+        }
+        match self.suffixes.get(&path).map(Vec::as_slice) {
+            Some([file]) => Ok(self.files.get(file)),
+            Some(files) => Err(format!(
+                "several files of the coverage report match: {}",
+                files
+                    .iter()
+                    .map(|file| file.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The coverage of the files of a directory.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DirectoryCoverage {
+    /// The directory
+    pub directory: PathBuf,
+    /// The number of files of the directory in the coverage report
+    pub files: usize,
+    /// The number of executable lines
+    pub lines: usize,
+    /// The number of executed lines
+    pub covered: usize,
+    /// The ratio of executed lines
+    pub coverage: f64,
+    /// The sum of the `CRAP` scores of the functions
+    pub crap_sum: f64,
+    /// The maximum `CRAP` score of the functions
+    pub crap_max: f64,
+}
+
+/// The coverage of the directories containing a series of files.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CoverageSummary {
+    /// The coverage of each directory, sorted by directory
+    pub directories: Vec<DirectoryCoverage>,
+}
+
+impl CoverageSummary {
+    /// Adds the coverage of a file, computed on its unit space,
+    /// to the coverage of its directory.
+    pub fn add(&mut self, path: &Path, space: &FuncSpace) {
+        let stats = &space.metrics.coverage;
+        if stats.is_disabled() {
+            return;
+        }
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let index = match self
+            .directories
+            .binary_search_by(|coverage| coverage.directory.as_path().cmp(directory))
+        {
+            Ok(index) => index,
+            Err(index) => {
+                self.directories.insert(
+                    index,
+                    DirectoryCoverage {
+                        directory: directory.to_path_buf(),
+                        ..Default::default()
+                    },
+                );
+                index
+            }
+        };
+        let coverage = &mut self.directories[index];
+        coverage.files += 1;
+        coverage.lines += stats.lines() as usize;
+        coverage.covered += stats.covered() as usize;
+        coverage.coverage = coverage.covered as f64 / coverage.lines as f64;
+        coverage.crap_sum += stats.crap_sum();
+        coverage.crap_max = coverage.crap_max.max(stats.crap_max());
+    }
+}
+
+impl fmt::Display for CoverageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for coverage in &self.directories {
+            writeln!(
+                f,
+                "{}: files: {}, lines: {}, covered: {}, coverage: {:.2}, crap sum: {:.2}, crap max: {:.2}",
+                coverage.directory.display(),
+                coverage.files,
+                coverage.lines,
+                coverage.covered,
+                coverage.coverage,
+                coverage.crap_sum,
+                coverage.crap_max
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParserTrait, PythonParser, RustParser, TypescriptParser, metrics};
+
+    use super::*;
+
+    fn space<T: ParserTrait>(path: &str, source: &str) -> FuncSpace {
+        let path = Path::new(path);
+        let parser = T::new(source.as_bytes().to_vec(), path, None);
+        metrics(&parser, path).unwrap()
+    }
+
+    #[test]
+    fn lcov_report() {
+        let report = CoverageReport::new(
+            "TN:\n\
+             SF:src/lib.rs\n\
+             DA:1,1\n\
+             DA:2,0\n\
+             end_of_record\n\
+             SF:/project/src/lib.rs\n\
+             DA:2,3\n\
+             DA:3,4\n\
+             end_of_record\n\
+             SF:/build/project/src/main.rs\n\
+             DA:1,0\n\
+             end_of_record\n",
+            Path::new("/project"),
+        )
+        .unwrap();
+
+        // The relative and the absolute paths of a file are merged
+        let lib = report.file(Path::new("/project/src/lib.rs")).unwrap();
+        assert_eq!(lib.unwrap().lines_in(1, 3), (3, 3));
+        // The paths outside of the root are matched by their last components
+        let main = report.file(Path::new("/project/src/main.rs")).unwrap();
+        assert_eq!(main.unwrap().lines_in(1, 3), (1, 0));
+        assert!(
+            report
+                .file(Path::new("/other/src/lib.rs"))
+                .unwrap()
+                .is_none()
+        );
+        assert!(report.file(Path::new("/project/lib.rs")).unwrap().is_none());
+        assert!(CoverageReport::new("SF:a.rs\nDA:x,1\n", Path::new("/")).is_err());
+        assert!(CoverageReport::new("def f():\n    pass\n", Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn ambiguous_report_paths() {
+        let report = CoverageReport::new(
+            "SF:/build/a/lib.rs\n\
+             DA:1,1\n\
+             end_of_record\n\
+             SF:/build/b/lib.rs\n\
+             DA:1,0\n\
+             end_of_record\n",
+            Path::new("/project"),
+        )
+        .unwrap();
+
+        let a = report.file(Path::new("/project/a/lib.rs")).unwrap();
+        assert_eq!(a.unwrap().lines_in(1, 1), (1, 1));
+        let b = report.file(Path::new("/project/b/lib.rs")).unwrap();
+        assert_eq!(b.unwrap().lines_in(1, 1), (1, 0));
+        assert_eq!(
+            report.file(Path::new("/project/lib.rs")).unwrap_err(),
+            "several files of the coverage report match: /build/a/lib.rs, /build/b/lib.rs"
+        );
+    }
+
+    #[test]
+    fn cobertura_report() {
+        let report = CoverageReport::new(
+            r#"<?xml version="1.0" ?>
+<coverage version="7.4" line-rate="0.5">
+  <sources><source>/project</source></sources>
+  <packages><package name="app"><classes>
+    <class name="util.py" filename="app/util.py" line-rate="0.5">
+      <methods/>
+      <lines>
+        <line number="1" hits="1"/>
+        <line number="2" hits="0"/>
+        <line number="4" hits="2" branch="true" condition-coverage="50% (1/2)"/>
+      </lines>
+    </class>
+  </classes></package></packages>
+</coverage>
+"#,
+            Path::new("/project"),
+        )
+        .unwrap();
+
+        let util = report
+            .file(Path::new("/project/app/util.py"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(util.lines_in(1, 4), (3, 2));
+        assert_eq!(util.lines_in(3, 3), (0, 0));
+        assert!(CoverageReport::new("<report></report>", Path::new("/project")).is_err());
+    }
+
+    #[test]
+    fn cobertura_sources() {
+        // A report produced in another directory, whose file names
+        // are relative to a subdirectory of the project
+        let report = CoverageReport::new(
+            r#"<coverage version="0.27">
+  <sources><source>/build/project/src</source></sources>
+  <packages><package name="app"><classes>
+    <class name="util.rs" filename="app/util.rs">
+      <lines><line number="1" hits="3"/><line number="2" hits="0"/></lines>
+    </class>
+  </classes></package></packages>
+</coverage>
+"#,
+            Path::new("/home/project"),
+        )
+        .unwrap();
+
+        let util = report
+            .file(Path::new("/home/project/src/app/util.rs"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(util.lines_in(1, 2), (2, 1));
+        assert!(
+            report
+                .file(Path::new("/home/project/src/app/other.rs"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn crap_scores() {
+        let report = CoverageReport::new(
+            "SF:app/util.py\n\
+             DA:1,1\nDA:2,1\nDA:3,0\nDA:4,1\nDA:6,1\nDA:7,1\n\
+             end_of_record\n",
+            Path::new("."),
+        )
+        .unwrap();
+        let mut space = space::<PythonParser>(
+            "app/util.py",
+            "def f(x):\n    if x:\n        return 1\n    return 0\n\ndef g():\n    return 2\n",
+        );
+        report
+            .file(Path::new("app/util.py"))
+            .unwrap()
+            .unwrap()
+            .apply(&mut space);
+
+        let f = &space.spaces[0].metrics.coverage;
+        assert_eq!((f.lines(), f.covered(), f.coverage()), (4., 3., 0.75));
+        // 2^2 * 0.25^3 + 2
+        assert_eq!(f.crap(), 2.0625);
+        let g = &space.spaces[1].metrics.coverage;
+        assert_eq!(g.crap(), 1.);
+        let unit = &space.metrics.coverage;
+        assert_eq!((unit.lines(), unit.covered()), (6., 5.));
+        assert_eq!((unit.crap_sum(), unit.crap_max()), (3.0625, 2.0625));
+    }
+
+    #[test]
+    fn coverage_summary() {
+        let report = CoverageReport::new(
+            "SF:src/lib.rs\n\
+             DA:1,1\nDA:2,0\nDA:3,0\n\
+             end_of_record\n\
+             SF:src/app.ts\n\
+             DA:1,1\nDA:2,1\n\
+             end_of_record\n\
+             SF:tools/main.rs\n\
+             DA:1,0\n\
+             end_of_record\n",
+            Path::new("."),
+        )
+        .unwrap();
+        let mut summary = CoverageSummary::default();
+        for (path, mut space) in [
+            (
+                "src/lib.rs",
+                space::<RustParser>(
+                    "src/lib.rs",
+                    "fn f(x: bool) -> u8 {\n    if x { 1 } else { 0 }\n}\n",
+                ),
+            ),
+            (
+                "src/app.ts",
+                space::<TypescriptParser>("src/app.ts", "function g() {\n  return 2;\n}\n"),
+            ),
+            (
+                "tools/main.rs",
+                space::<RustParser>("tools/main.rs", "fn main() {}\n"),
+            ),
+            (
+                "tools/other.rs",
+                space::<RustParser>("tools/other.rs", "fn main() {}\n"),
+            ),
+        ] {
+            let path = Path::new(path);
+            if let Some(coverage) = report.file(path).unwrap() {
+                coverage.apply(&mut space);
+            }
+            summary.add(path, &space);
+        }
+
+        insta::assert_snapshot!(summary.to_string(), @r#"
+            src: files: 2, lines: 5, covered: 3, coverage: 0.60, crap sum: 4.19, crap max: 3.19
+            tools: files: 1, lines: 1, covered: 0, coverage: 0.00, crap sum: 2.00, crap max: 2.00
+            "#);
+    }
+}
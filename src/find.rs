@@ -1,10 +1,82 @@
 use std::path::PathBuf;
 
+use serde::Serialize;
+
 use crate::node::Node;
 
 use crate::dump::*;
+use crate::query::*;
 use crate::traits::*;
 
+/// A node found in a code.
+#[derive(Clone, Debug, Serialize)]
+pub struct FoundNode {
+    /// The kind of the node
+    pub kind: String,
+    /// The code of the node, with its macros expanded
+    /// when the macro expansion is enabled
+    pub text: String,
+    /// The first byte of the node
+    pub start_byte: usize,
+    /// The byte following the last one of the node
+    pub end_byte: usize,
+    /// The first line of the node
+    pub start_line: usize,
+    /// The first column of the node
+    pub start_column: usize,
+    /// The last line of the node
+    pub end_line: usize,
+    /// The column following the last one of the node
+    pub end_column: usize,
+}
+
+impl FoundNode {
+    // The positions of a node in a code whose macros have been expanded
+    // are the positions of the invocations of the macros in the original code.
+    pub(crate) fn new<T: ParserTrait>(node: &Node, parser: &T) -> Self {
+        let code = parser.get_code();
+        let (start_row, start_column) = node.start_position();
+        let (end_row, end_column) = node.end_position();
+        let (start_byte, end_byte, start_column, end_column) = match parser.get_source_map() {
+            Some(source_map) => {
+                let original = source_map.original_range(node.start_byte()..node.end_byte());
+                // The expansions never change the lines, so a column is
+                // the offset from the start of its line in the original code
+                let line_start =
+                    |byte: usize, column: usize| source_map.original_offset(byte - column);
+                (
+                    original.start,
+                    original.end,
+                    original.start - line_start(node.start_byte(), start_column),
+                    original.end - line_start(node.end_byte(), end_column),
+                )
+            }
+            None => (node.start_byte(), node.end_byte(), start_column, end_column),
+        };
+        Self {
+            kind: node.kind().to_owned(),
+            text: String::from_utf8_lossy(&code[node.start_byte()..node.end_byte()]).into_owned(),
+            start_byte,
+            end_byte,
+            start_line: start_row + 1,
+            start_column: start_column + 1,
+            end_line: end_row + 1,
+            end_column: end_column + 1,
+        }
+    }
+}
+
+/// The nodes found in a file.
+#[derive(Debug, Serialize)]
+pub struct FindResults {
+    /// The path of the file
+    pub file: PathBuf,
+    /// The nodes of the types searched
+    pub nodes: Vec<FoundNode>,
+    /// The matches of the tree-sitter queries
+    pub matches: Vec<QueryMatch>,
+}
+
 /// Finds the types of nodes specified in the input slice.
 pub fn find<'a, T: ParserTrait>(parser: &'a T, filters: &[String]) -> Option<Vec<Node<'a>>> {
     let filters = parser.get_filters(filters);
@@ -36,6 +108,25 @@ pub fn find<'a, T: ParserTrait>(parser: &'a T, filters: &[String]) -> Option<Vec
     Some(good)
 }
 
+// Splits the filters into tree-sitter queries and node types.
+pub(crate) fn split_queries(filters: &[String]) -> (Vec<String>, Vec<String>) {
+    filters.iter().cloned().partition(|f| is_query(f))
+}
+
+pub(crate) fn run_queries<T: ParserTrait>(
+    parser: &T,
+    queries: &[String],
+) -> std::io::Result<Vec<QueryMatch>> {
+    let mut matches = Vec::new();
+    for source in queries {
+        matches.extend(
+            query_if_compatible(parser, source)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+        );
+    }
+    Ok(matches)
+}
+
 /// Configuration options for finding different
 /// types of nodes in a code.
 #[derive(Debug)]
@@ -43,6 +134,9 @@ pub struct FindCfg {
     /// Path to the file containing the code
     pub path: PathBuf,
     /// Types of nodes to find
+    ///
+    /// Filters starting with a parenthesis or with a bracket
+    /// are tree-sitter queries
     pub filters: Vec<String>,
     /// The first line of code considered in the search
     ///
@@ -60,20 +154,119 @@ pub struct Find {
     _guard: (),
 }
 
+fn in_lines(line: usize, line_start: Option<usize>, line_end: Option<usize>) -> bool {
+    line_start.is_none_or(|start| line >= start) && line_end.is_none_or(|end| line <= end)
+}
+
+/// Finds the nodes of a code, returning them as [`FindResults`].
+///
+/// [`FindResults`]: struct.FindResults.html
+pub fn find_results<T: ParserTrait>(parser: &T, cfg: FindCfg) -> std::io::Result<FindResults> {
+    let (queries, filters) = split_queries(&cfg.filters);
+
+    let mut matches = run_queries(parser, &queries)?;
+    matches.retain(|m| {
+        m.captures
+            .first()
+            .is_some_and(|c| in_lines(c.node.start_line, cfg.line_start, cfg.line_end))
+    });
+
+    let nodes = if filters.is_empty() && !queries.is_empty() {
+        Vec::new()
+    } else {
+        find(parser, &filters)
+            .unwrap_or_default()
+            .iter()
+            .filter(|node| in_lines(node.start_row() + 1, cfg.line_start, cfg.line_end))
+            .map(|node| FoundNode::new(node, parser))
+            .collect()
+    };
+
+    Ok(FindResults {
+        file: cfg.path,
+        nodes,
+        matches,
+    })
+}
+
+/// Finds the nodes of a code, returning them as data
+/// instead of printing them.
+pub struct FindData {
+    _guard: (),
+}
+
+impl Callback for FindData {
+    type Res = std::io::Result<FindResults>;
+    type Cfg = FindCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        find_results(parser, cfg)
+    }
+}
+
 impl Callback for Find {
     type Res = std::io::Result<()>;
     type Cfg = FindCfg;
 
     fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
-        if let Some(good) = find(parser, &cfg.filters)
-            && !good.is_empty()
-        {
+        let (queries, filters) = split_queries(&cfg.filters);
+        let matches = run_queries(parser, &queries)?;
+        let good = if filters.is_empty() && !queries.is_empty() {
+            Vec::new()
+        } else {
+            find(parser, &filters).unwrap_or_default()
+        };
+
+        if !good.is_empty() || !matches.is_empty() {
             println!("In file {}", cfg.path.to_str().unwrap());
             for node in good {
                 dump_node(parser.get_code(), &node, 1, cfg.line_start, cfg.line_end)?;
             }
+            dump_query_matches(&matches, cfg.line_start, cfg.line_end)?;
             println!();
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::RustParser;
+
+    use super::*;
+
+    #[test]
+    fn find_results_in_lines() {
+        let source = "fn foo() {\n    bar();\n}\n\nfn baz() {\n    bar();\n}\n";
+        let path = Path::new("foo.rs");
+        let parser = RustParser::new(source.as_bytes().to_vec(), path, None);
+
+        let results = find_results(
+            &parser,
+            FindCfg {
+                path: path.to_path_buf(),
+                filters: vec!["call_expression".to_owned(), "(identifier) @id".to_owned()],
+                line_start: Some(5),
+                line_end: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(results.nodes.len(), 1);
+        let node = &results.nodes[0];
+        assert_eq!(node.kind, "call_expression");
+        assert_eq!(node.text, "bar()");
+        assert_eq!((node.start_line, node.start_column), (6, 5));
+        assert_eq!((node.end_line, node.end_column), (6, 10));
+        assert_eq!(&source[node.start_byte..node.end_byte], "bar()");
+
+        let ids: Vec<_> = results
+            .matches
+            .iter()
+            .map(|m| m.captures[0].node.text.as_str())
+            .collect();
+        assert_eq!(ids, ["baz", "bar"]);
+    }
+}
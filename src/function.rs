@@ -4,6 +4,7 @@ use std::path::PathBuf;
 use serde::Serialize;
 use termcolor::{Color, ColorChoice, StandardStream, StandardStreamLock};
 
+use crate::find::FoundNode;
 use crate::traits::*;
 
 use crate::checker::Checker;
@@ -59,6 +60,52 @@ pub fn function<T: ParserTrait>(parser: &T) -> Vec<FunctionSpan> {
     spans
 }
 
+/// A function found in a code.
+#[derive(Debug, Serialize)]
+pub struct FunctionNode {
+    /// The function name
+    pub name: String,
+    /// If `true`, an error is occurred in determining the name
+    /// of a function
+    pub error: bool,
+    /// The node of the function
+    #[serde(flatten)]
+    pub node: FoundNode,
+}
+
+/// The functions found in a file.
+#[derive(Debug, Serialize)]
+pub struct FunctionResults {
+    /// The path of the file
+    pub file: PathBuf,
+    /// The functions in the file
+    pub functions: Vec<FunctionNode>,
+}
+
+/// Detects the functions of a code, returning them as [`FunctionResults`].
+///
+/// [`FunctionResults`]: struct.FunctionResults.html
+pub fn function_results<T: ParserTrait>(parser: &T, path: PathBuf) -> FunctionResults {
+    let root = parser.get_root();
+    let code = parser.get_code();
+    let mut functions = Vec::new();
+    root.act_on_node(&mut |n| {
+        if T::Checker::is_func(n) {
+            let name = T::Getter::get_func_name(n, code);
+            functions.push(FunctionNode {
+                name: name.unwrap_or_default().to_string(),
+                error: name.is_none(),
+                node: FoundNode::new(n, parser),
+            });
+        }
+    });
+
+    FunctionResults {
+        file: path,
+        functions,
+    }
+}
+
 fn dump_span(
     span: FunctionSpan,
     stdout: &mut StandardStreamLock,
@@ -131,3 +178,18 @@ impl Callback for Function {
         dump_spans(function(parser), cfg.path)
     }
 }
+
+/// Detects the functions of a code, returning them as data
+/// instead of printing them.
+pub struct FunctionData {
+    _guard: (),
+}
+
+impl Callback for FunctionData {
+    type Res = FunctionResults;
+    type Cfg = FunctionCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        function_results(parser, cfg.path)
+    }
+}
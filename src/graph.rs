@@ -0,0 +1,199 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use petgraph::{graph::DiGraph, graph::NodeIndex, visit::EdgeRef};
+
+/// The formats a graph can be exported to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// The `Graphviz` DOT language
+    #[default]
+    Dot,
+    /// The XML-based `GraphML` format
+    Graphml,
+    /// A JSON object containing the nodes and the edges
+    Json,
+}
+
+impl GraphFormat {
+    /// Returns the extension of a file in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Dot => "dot",
+            Self::Graphml => "graphml",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "dot" => Ok(Self::Dot),
+            "graphml" => Ok(Self::Graphml),
+            "json" => Ok(Self::Json),
+            format => Err(format!("{format:?} is not a supported graph format")),
+        }
+    }
+}
+
+// Escapes a string to be used as a quoted DOT identifier.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Escapes a string to be used as an XML attribute or text.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const GRAPHML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+     <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n";
+
+// The value of an attribute of a node or of an edge.
+#[derive(Clone)]
+pub(crate) enum Value {
+    Text(String),
+    Int(usize),
+    Double(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn dot(&self) -> String {
+        match self {
+            Self::Text(text) => format!("\"{}\"", dot_escape(text)),
+            Self::Int(n) => n.to_string(),
+            Self::Double(x) => x.to_string(),
+            Self::Bool(b) => b.to_string(),
+        }
+    }
+
+    fn xml(&self) -> String {
+        match self {
+            Self::Text(text) => xml_escape(text),
+            Self::Int(n) => n.to_string(),
+            Self::Double(x) => x.to_string(),
+            Self::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+// An exported edge, with its attributes and whether it is dashed.
+type Edge = (NodeIndex, NodeIndex, Vec<Value>, bool);
+
+// Returns the attributes of a node or of an edge.
+type Attributes<'a, T> = Box<dyn Fn(T) -> Vec<Value> + 'a>;
+
+// Exports a graph in the DOT and `GraphML` formats.
+//
+// The attributes of the nodes and of the edges are returned by closures,
+// in the order of their keys, which are pairs of names and `GraphML` types.
+// In DOT, the first attribute of a node is its label.
+pub(crate) struct GraphExport<'a, N, E> {
+    pub(crate) name: &'static str,
+    pub(crate) graph: &'a DiGraph<N, E>,
+    pub(crate) node_keys: &'static [(&'static str, &'static str)],
+    pub(crate) edge_keys: &'static [(&'static str, &'static str)],
+    pub(crate) node_attributes: Attributes<'a, NodeIndex>,
+    pub(crate) edge_attributes: Attributes<'a, &'a E>,
+    // The edges which are not in the graph, drawn as dashed edges in DOT
+    pub(crate) dashed_edges: Vec<(NodeIndex, NodeIndex, Vec<Value>)>,
+}
+
+impl<N, E> GraphExport<'_, N, E> {
+    // The edges of the graph followed by the dashed ones.
+    fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.graph
+            .edge_references()
+            .map(|edge| {
+                let attributes = (self.edge_attributes)(edge.weight());
+                (edge.source(), edge.target(), attributes, false)
+            })
+            .chain(
+                self.dashed_edges
+                    .iter()
+                    .map(|(source, target, attributes)| {
+                        (*source, *target, attributes.clone(), true)
+                    }),
+            )
+    }
+
+    pub(crate) fn to_dot(&self) -> String {
+        let mut dot = format!("digraph {} {{\n", self.name);
+        for index in self.graph.node_indices() {
+            let attributes: Vec<_> = self
+                .node_keys
+                .iter()
+                .zip((self.node_attributes)(index))
+                .enumerate()
+                .map(|(i, ((key, _), value))| {
+                    let key = if i == 0 { "label" } else { key };
+                    format!("{key}={}", value.dot())
+                })
+                .collect();
+            let _ = writeln!(dot, "    n{} [{}];", index.index(), attributes.join(", "));
+        }
+        for (source, target, attributes, dashed) in self.edges() {
+            let mut attributes: Vec<_> = self
+                .edge_keys
+                .iter()
+                .zip(attributes)
+                .map(|((key, _), value)| format!("{key}={}", value.dot()))
+                .collect();
+            if dashed {
+                attributes.push("style=dashed".to_string());
+            }
+            let _ = write!(dot, "    n{} -> n{}", source.index(), target.index());
+            if !attributes.is_empty() {
+                let _ = write!(dot, " [{}]", attributes.join(", "));
+            }
+            dot.push_str(";\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub(crate) fn to_graphml(&self) -> String {
+        let mut xml = String::from(GRAPHML_HEADER);
+        let node_keys = self.node_keys.iter().map(|key| (key, "node"));
+        let edge_keys = self.edge_keys.iter().map(|key| (key, "edge"));
+        for ((id, typ), domain) in node_keys.chain(edge_keys) {
+            let _ = writeln!(
+                xml,
+                "  <key id=\"{id}\" for=\"{domain}\" attr.name=\"{id}\" attr.type=\"{typ}\"/>"
+            );
+        }
+        let _ = writeln!(
+            xml,
+            "  <graph id=\"{}\" edgedefault=\"directed\">",
+            self.name
+        );
+        for index in self.graph.node_indices() {
+            let _ = writeln!(xml, "    <node id=\"n{}\">", index.index());
+            for ((key, _), value) in self.node_keys.iter().zip((self.node_attributes)(index)) {
+                let _ = writeln!(xml, "      <data key=\"{key}\">{}</data>", value.xml());
+            }
+            xml.push_str("    </node>\n");
+        }
+        for (source, target, attributes, _) in self.edges() {
+            let _ = write!(
+                xml,
+                "    <edge source=\"n{}\" target=\"n{}\">",
+                source.index(),
+                target.index()
+            );
+            for ((key, _), value) in self.edge_keys.iter().zip(attributes) {
+                let _ = write!(xml, "<data key=\"{key}\">{}</data>", value.xml());
+            }
+            xml.push_str("</edge>\n");
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+}
@@ -0,0 +1,1131 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use petgraph::{
+    algo::kosaraju_scc,
+    graph::{DiGraph, NodeIndex},
+    visit::EdgeRef,
+};
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+
+use crate::checker::Checker;
+use crate::graph::{GraphExport, Value};
+use crate::langs::*;
+use crate::node::Node;
+use crate::packages::{FileTypes, file_types};
+use crate::tools::{get_paths_dist, normalize_path};
+use crate::traits::*;
+use crate::{Csharp, Java, Javascript, Kotlin, Mozjs, Php, Python, Rust, Tsx, Typescript};
+
+// The extensions tried for a relative `JavaScript/TypeScript` module.
+const JS_EXTENSIONS: &[&str] = &["js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts"];
+
+// The extensions of the files whose classes can be imported by `Java/Kotlin` files.
+const JVM_EXTENSIONS: &[&str] = &["java", "kt"];
+
+const CSHARP_EXTENSIONS: &[&str] = &["cs"];
+
+/// A module imported by a file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Import {
+    /// The imported module, as written in the code
+    pub module: String,
+    /// The line of the import
+    pub line: usize,
+    // The files the import may refer to, in order of preference
+    #[serde(skip)]
+    targets: Vec<Target>,
+}
+
+// A file, or a set of files, an import may refer to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Target {
+    // The file at this path
+    File(PathBuf),
+    // The files whose path ends with this one
+    Suffix(PathBuf),
+    // The files with one of the extensions in the directories
+    // whose path ends with this one
+    Directory(PathBuf, &'static [&'static str]),
+}
+
+// The statements importing a module.
+enum Statement {
+    RustUse,
+    RustMod,
+    PythonImport,
+    PythonFrom,
+    JsImport,
+    JsExport,
+    JsCall,
+    JvmImport,
+    CsharpUsing,
+    PhpUse,
+    PhpInclude,
+}
+
+fn statement(lang: LANG, id: u16) -> Option<Statement> {
+    use Statement::*;
+
+    let statement = match lang {
+        LANG::Rust if id == Rust::UseDeclaration => RustUse,
+        LANG::Rust if id == Rust::ModItem => RustMod,
+        LANG::Python if id == Python::ImportStatement => PythonImport,
+        LANG::Python if id == Python::ImportFromStatement => PythonFrom,
+        LANG::Javascript if id == Javascript::ImportStatement => JsImport,
+        LANG::Javascript if id == Javascript::ExportStatement => JsExport,
+        LANG::Javascript
+            if id == Javascript::CallExpression || id == Javascript::CallExpression2 =>
+        {
+            JsCall
+        }
+        LANG::Mozjs if id == Mozjs::ImportStatement => JsImport,
+        LANG::Mozjs if id == Mozjs::ExportStatement => JsExport,
+        LANG::Mozjs if id == Mozjs::CallExpression || id == Mozjs::CallExpression2 => JsCall,
+        LANG::Typescript if id == Typescript::ImportStatement => JsImport,
+        LANG::Typescript if id == Typescript::ExportStatement => JsExport,
+        LANG::Typescript
+            if id == Typescript::CallExpression
+                || id == Typescript::CallExpression2
+                || id == Typescript::CallExpression3
+                || id == Typescript::CallExpression4 =>
+        {
+            JsCall
+        }
+        LANG::Tsx if id == Tsx::ImportStatement => JsImport,
+        LANG::Tsx if id == Tsx::ExportStatement => JsExport,
+        LANG::Tsx
+            if id == Tsx::CallExpression
+                || id == Tsx::CallExpression2
+                || id == Tsx::CallExpression3
+                || id == Tsx::CallExpression4 =>
+        {
+            JsCall
+        }
+        LANG::Java if id == Java::ImportDeclaration => JvmImport,
+        LANG::Kotlin if id == Kotlin::ImportHeader => JvmImport,
+        LANG::Csharp if id == Csharp::UsingDirective => CsharpUsing,
+        LANG::Php if id == Php::NamespaceUseDeclaration => PhpUse,
+        LANG::Php
+            if id == Php::RequireExpression
+                || id == Php::RequireOnceExpression
+                || id == Php::IncludeExpression
+                || id == Php::IncludeOnceExpression =>
+        {
+            PhpInclude
+        }
+        _ => return None,
+    };
+    Some(statement)
+}
+
+// Returns the text of a node with its whitespace collapsed.
+fn text(node: &Node, code: &[u8]) -> String {
+    node.utf8_text(code)
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Returns the content of a string literal, unless it contains interpolations.
+fn string_content(node: &Node, code: &[u8]) -> Option<String> {
+    let text = node.utf8_text(code)?;
+    if !text.starts_with(['"', '\'', '`']) {
+        return None;
+    }
+    let content = text.trim_matches(['"', '\'', '`']);
+    (!content.is_empty() && !content.contains("${")).then(|| content.to_string())
+}
+
+// Drops the alias of an imported path, as `b` in `a as b`.
+fn strip_alias(path: &str) -> &str {
+    path.split(" as ").next().unwrap_or_default().trim()
+}
+
+// Splits a list at the commas which are not nested in braces.
+fn split_list(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                items.push(list[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(list[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+// Expands a tree of imported paths, as `a::{b, c::{self, d}}`,
+// into the paths it contains, dropping their aliases.
+fn expand_tree(tree: &str, separator: &str) -> Vec<String> {
+    let tree = tree.trim();
+    let (Some(open), true) = (tree.find('{'), tree.ends_with('}')) else {
+        return vec![strip_alias(tree).to_string()];
+    };
+    let prefix = &tree[..open];
+    split_list(&tree[open + 1..tree.len() - 1])
+        .into_iter()
+        .flat_map(|item| {
+            if item == "self" {
+                vec![prefix.trim_end_matches(separator).to_string()]
+            } else {
+                expand_tree(&format!("{prefix}{item}"), separator)
+            }
+        })
+        .collect()
+}
+
+// Joins some path components.
+fn join(segments: &[&str]) -> PathBuf {
+    segments.iter().collect()
+}
+
+// Appends an extension to a path, keeping its existing one.
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+// The directory containing the submodules of a `Rust` module,
+// that is the one of `foo/mod.rs` or `foo.rs`.
+fn rust_module_dir(path: &Path, node: &Node, code: &[u8]) -> PathBuf {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut dir = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some("mod" | "lib" | "main") | None => dir.to_path_buf(),
+        Some(stem) => dir.join(stem),
+    };
+
+    // The inline modules containing the node
+    let mut modules = Vec::new();
+    let mut parent = node.parent();
+    while let Some(node) = parent {
+        if node.kind_id() == Rust::ModItem
+            && let Some(name) = node.child_by_field_name("name")
+        {
+            modules.extend(name.utf8_text(code).map(str::to_owned));
+        }
+        parent = node.parent();
+    }
+    dir.extend(modules.iter().rev());
+    dir
+}
+
+fn rust_targets(module_dir: &Path, module: &str) -> Vec<Target> {
+    let mut segments: Vec<_> = module
+        .trim_start_matches("::")
+        .split("::")
+        .filter(|segment| *segment != "*")
+        .collect();
+
+    // A path starting with `crate` or with the name of a crate
+    // is searched among all the files
+    let mut base = None;
+    match segments.first() {
+        Some(&"crate") => {
+            segments.remove(0);
+        }
+        Some(&"self") => {
+            segments.remove(0);
+            base = Some(module_dir.to_path_buf());
+        }
+        Some(&"super") => {
+            let mut dir = module_dir;
+            while segments.first() == Some(&"super") {
+                segments.remove(0);
+                dir = dir.parent().unwrap_or(dir);
+            }
+            base = Some(dir.to_path_buf());
+        }
+        _ => {}
+    }
+
+    // The last segments may be items of the module rather than modules
+    (1..=segments.len())
+        .rev()
+        .flat_map(|len| {
+            let path = join(&segments[..len]);
+            [with_extension(&path, "rs"), path.join("mod.rs")]
+        })
+        .map(|path| match &base {
+            Some(base) => Target::File(normalize_path(base.join(path))),
+            None => Target::Suffix(path),
+        })
+        .collect()
+}
+
+fn python_targets(path: &Path, module: &str) -> Vec<Target> {
+    let relative = module.len() - module.trim_start_matches('.').len();
+    let segments: Vec<_> = module[relative..]
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let module = join(&segments);
+    let candidates = if segments.is_empty() {
+        vec![PathBuf::from("__init__.py")]
+    } else {
+        vec![with_extension(&module, "py"), module.join("__init__.py")]
+    };
+
+    if relative == 0 {
+        return candidates.into_iter().map(Target::Suffix).collect();
+    }
+    // A leading dot is the package of the file, each other one its parent
+    let mut base = path.parent().unwrap_or(Path::new(""));
+    for _ in 1..relative {
+        base = base.parent().unwrap_or(base);
+    }
+    candidates
+        .into_iter()
+        .map(|candidate| Target::File(normalize_path(base.join(candidate))))
+        .collect()
+}
+
+fn javascript_targets(path: &Path, module: &str) -> Vec<Target> {
+    // Only the relative modules are files, the other ones are packages
+    if !(module.starts_with("./") || module.starts_with("../") || module == "." || module == "..") {
+        return Vec::new();
+    }
+    let base = normalize_path(path.parent().unwrap_or(Path::new("")).join(module));
+    let with_extension = JS_EXTENSIONS
+        .iter()
+        .map(|extension| with_extension(&base, extension));
+    let index = JS_EXTENSIONS
+        .iter()
+        .map(|extension| base.join(format!("index.{extension}")));
+    std::iter::once(base.clone())
+        .chain(with_extension)
+        .chain(index)
+        .map(Target::File)
+        .collect()
+}
+
+fn jvm_targets(module: &str) -> Vec<Target> {
+    let segments: Vec<_> = module.split('.').collect();
+    if segments.last() == Some(&"*") {
+        return vec![Target::Directory(
+            join(&segments[..segments.len() - 1]),
+            JVM_EXTENSIONS,
+        )];
+    }
+    // A static import names a member of a class
+    (1..=segments.len())
+        .rev()
+        .flat_map(|len| {
+            let path = join(&segments[..len]);
+            JVM_EXTENSIONS
+                .iter()
+                .map(move |extension| Target::Suffix(with_extension(&path, extension)))
+        })
+        .collect()
+}
+
+fn csharp_targets(module: &str) -> Vec<Target> {
+    // A namespace is a directory, while a type is a file
+    let path = join(&module.split('.').collect::<Vec<_>>());
+    vec![
+        Target::Suffix(with_extension(&path, "cs")),
+        Target::Directory(path, CSHARP_EXTENSIONS),
+    ]
+}
+
+fn php_use_targets(module: &str) -> Vec<Target> {
+    let segments: Vec<_> = module.split('\\').filter(|s| !s.is_empty()).collect();
+    // The leading namespaces are often mapped to another directory,
+    // as `App\` to `src/`, so they are dropped one by one
+    (0..segments.len().saturating_sub(1).max(1))
+        .map(|start| Target::Suffix(with_extension(&join(&segments[start..]), "php")))
+        .collect()
+}
+
+fn php_include_targets(path: &Path, module: &str) -> Vec<Target> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    // A path starting with a slash is often appended to `__DIR__`
+    let relative = module.trim_start_matches('/');
+    let mut targets = vec![Target::File(normalize_path(dir.join(relative)))];
+    if Path::new(module).is_absolute() {
+        targets.push(Target::File(normalize_path(module)));
+    }
+    targets.push(Target::Suffix(normalize_path(relative)));
+    targets
+}
+
+fn add_imports(
+    imports: &mut Vec<Import>,
+    line: usize,
+    modules: impl IntoIterator<Item = (String, Vec<Target>)>,
+) {
+    imports.extend(modules.into_iter().map(|(module, targets)| Import {
+        module,
+        line,
+        targets,
+    }));
+}
+
+// Returns the first string literal contained in a node.
+fn first_string<T: ParserTrait>(node: &Node, code: &[u8]) -> Option<String> {
+    let mut string = None;
+    node.act_on_node(&mut |n| {
+        if string.is_none() && T::Checker::is_string(n) {
+            string = string_content(n, code);
+        }
+    });
+    string
+}
+
+fn statement_imports<T: ParserTrait>(
+    statement: Statement,
+    node: &Node,
+    code: &[u8],
+    path: &Path,
+    imports: &mut Vec<Import>,
+) {
+    let line = node.start_row() + 1;
+    match statement {
+        Statement::RustUse => {
+            let Some(argument) = node.child_by_field_name("argument") else {
+                return;
+            };
+            let module_dir = rust_module_dir(path, node, code);
+            let modules = expand_tree(&text(&argument, code), "::")
+                .into_iter()
+                .map(|module| {
+                    let targets = rust_targets(&module_dir, &module);
+                    (module, targets)
+                });
+            add_imports(imports, line, modules);
+        }
+        Statement::RustMod => {
+            // Only `mod foo;` refers to another file
+            if node.child_by_field_name("body").is_some() {
+                return;
+            }
+            let Some(name) = node
+                .child_by_field_name("name")
+                .and_then(|name| name.utf8_text(code))
+            else {
+                return;
+            };
+            let module_dir = rust_module_dir(path, node, code);
+            let targets = vec![
+                Target::File(normalize_path(module_dir.join(format!("{name}.rs")))),
+                Target::File(normalize_path(module_dir.join(name).join("mod.rs"))),
+            ];
+            add_imports(imports, line, [(name.to_string(), targets)]);
+        }
+        Statement::PythonImport => {
+            let text = text(node, code).replace('\\', " ");
+            let Some(names) = text.strip_prefix("import ") else {
+                return;
+            };
+            let modules = names.split(',').map(|name| {
+                let module = strip_alias(name).to_string();
+                let targets = python_targets(path, &module);
+                (module, targets)
+            });
+            add_imports(imports, line, modules);
+        }
+        Statement::PythonFrom => {
+            let text = text(node, code).replace(['\\', '(', ')'], " ");
+            let Some((module, names)) = text
+                .strip_prefix("from ")
+                .and_then(|text| text.split_once(" import "))
+            else {
+                return;
+            };
+            let module = module.trim();
+            let names: Vec<_> = names
+                .split(',')
+                .map(strip_alias)
+                .filter(|name| !name.is_empty() && *name != "*")
+                .collect();
+            if names.is_empty() {
+                add_imports(
+                    imports,
+                    line,
+                    [(module.to_string(), python_targets(path, module))],
+                );
+                return;
+            }
+            // An imported name is either a submodule or an item of the module
+            let modules = names.into_iter().map(|name| {
+                let separator = if module.ends_with('.') { "" } else { "." };
+                let mut targets = python_targets(path, &format!("{module}{separator}{name}"));
+                targets.extend(python_targets(path, module));
+                (module.to_string(), targets)
+            });
+            add_imports(imports, line, modules);
+        }
+        Statement::JsImport | Statement::JsExport => {
+            let source = match node.child_by_field_name("source") {
+                Some(source) => string_content(&source, code),
+                // As `import x = require("y")` in `TypeScript`
+                None if matches!(statement, Statement::JsImport) => first_string::<T>(node, code),
+                None => None,
+            };
+            if let Some(module) = source {
+                let targets = javascript_targets(path, &module);
+                add_imports(imports, line, [(module, targets)]);
+            }
+        }
+        Statement::JsCall => {
+            if let Some(module) = required_module(node, code) {
+                let targets = javascript_targets(path, &module);
+                add_imports(imports, line, [(module, targets)]);
+            }
+        }
+        Statement::JvmImport => {
+            let text = text(node, code);
+            let module = text
+                .trim_start_matches("import ")
+                .trim_start_matches("static ")
+                .trim_end_matches(';');
+            let module = strip_alias(module).replace(' ', "");
+            let targets = jvm_targets(&module);
+            add_imports(imports, line, [(module, targets)]);
+        }
+        Statement::CsharpUsing => {
+            let text = text(node, code);
+            let module = text
+                .trim_start_matches("global ")
+                .trim_start_matches("using ")
+                .trim_start_matches("static ")
+                .trim_end_matches(';');
+            // Drop the alias of `using Alias = Namespace.Type;`
+            let module = module.rsplit('=').next().unwrap_or_default().trim();
+            let module = module.replace(' ', "");
+            let targets = csharp_targets(&module);
+            add_imports(imports, line, [(module, targets)]);
+        }
+        Statement::PhpUse => {
+            let text = text(node, code);
+            let list = text
+                .trim_start_matches("use ")
+                .trim_start_matches("function ")
+                .trim_start_matches("const ")
+                .trim_end_matches(';');
+            let modules = split_list(list)
+                .into_iter()
+                .flat_map(|tree| expand_tree(tree, "\\"))
+                .map(|module| {
+                    let module = module
+                        .trim_start_matches("function ")
+                        .trim_start_matches("const ")
+                        .trim_start_matches('\\')
+                        .to_string();
+                    let targets = php_use_targets(&module);
+                    (module, targets)
+                });
+            add_imports(imports, line, modules);
+        }
+        Statement::PhpInclude => {
+            if let Some(module) = first_string::<T>(node, code) {
+                let targets = php_include_targets(path, &module);
+                add_imports(imports, line, [(module, targets)]);
+            }
+        }
+    }
+}
+
+// Returns the module loaded by a call to `require` or to `import`.
+fn required_module(node: &Node, code: &[u8]) -> Option<String> {
+    let function = node.child_by_field_name("function")?;
+    if !matches!(function.utf8_text(code)?, "require" | "import") {
+        return None;
+    }
+    let arguments = node.child_by_field_name("arguments")?;
+    // The first argument, after the opening parenthesis
+    string_content(&arguments.child(1)?, code)
+}
+
+/// Finds the modules imported by a code.
+///
+/// The supported imports are the `Rust` `use` and `mod` declarations, the `Python`
+/// `import` statements, the `JavaScript/TypeScript` `import` and `export` statements
+/// and `require` calls, the `Java/Kotlin` `import` declarations, the `C#` `using`
+/// directives and the `PHP` `use` declarations and `require/include` expressions.
+pub fn file_imports<T: ParserTrait>(parser: &T, path: &Path) -> Vec<Import> {
+    let code = parser.get_code();
+    let lang = parser.get_language();
+    let mut imports = Vec::new();
+
+    parser.get_root().act_on_node(&mut |node| {
+        if let Some(statement) = statement(lang, node.kind_id()) {
+            statement_imports::<T>(statement, node, code, path, &mut imports);
+        }
+    });
+
+    imports
+}
+
+/// Collects the imports of a set of files.
+#[derive(Debug, Default)]
+pub struct ModuleGraphBuilder {
+    files: BTreeMap<PathBuf, Vec<Import>>,
+    types: BTreeMap<PathBuf, FileTypes>,
+}
+
+impl ModuleGraphBuilder {
+    /// Adds the imports of a file.
+    pub fn add(&mut self, path: PathBuf, imports: Vec<Import>) {
+        self.files.insert(path, imports);
+    }
+
+    /// Adds the types declared in a file.
+    pub fn add_types(&mut self, path: PathBuf, types: FileTypes) {
+        self.types.insert(path, types);
+    }
+
+    /// Builds the module graph, resolving the imports to the added files.
+    ///
+    /// Relative imports are resolved against the directory of the importing
+    /// file, while the other ones are resolved to the files whose path ends
+    /// with the path of the module, the closest files being preferred.
+    /// The imports which are not resolved, as the ones of external
+    /// packages, are kept for each file.
+    pub fn build(self) -> ModuleGraph {
+        let mut graph = DiGraph::new();
+        let mut paths = HashMap::new();
+        let mut names: HashMap<OsString, Vec<NodeIndex>> = HashMap::new();
+        let mut dirs: HashMap<OsString, Vec<NodeIndex>> = HashMap::new();
+
+        for path in self.files.keys() {
+            let index = graph.add_node(path.clone());
+            paths.insert(normalize_path(path), index);
+            if let Some(name) = path.file_name() {
+                names.entry(name.to_owned()).or_default().push(index);
+            }
+            if let Some(dir) = path.parent().and_then(|dir| dir.file_name()) {
+                dirs.entry(dir.to_owned()).or_default().push(index);
+            }
+        }
+
+        let resolve =
+            |graph: &DiGraph<PathBuf, Vec<Import>>, current: NodeIndex, target: &Target| {
+                let mut found: Vec<_> = match target {
+                    Target::File(path) => paths.get(path).copied().into_iter().collect(),
+                    Target::Suffix(path) => {
+                        let found: Vec<_> = path
+                            .file_name()
+                            .and_then(|name| names.get(name))
+                            .into_iter()
+                            .flatten()
+                            .copied()
+                            .filter(|&index| graph[index].ends_with(path))
+                            .collect();
+                        // The files closest to the importing one are preferred
+                        let distance = |index: &NodeIndex| {
+                            get_paths_dist(&graph[current], &graph[*index]).unwrap_or(usize::MAX)
+                        };
+                        let min = found.iter().map(distance).min();
+                        found
+                            .into_iter()
+                            .filter(|index| Some(distance(index)) == min)
+                            .collect()
+                    }
+                    Target::Directory(path, extensions) => path
+                        .file_name()
+                        .and_then(|name| dirs.get(name))
+                        .into_iter()
+                        .flatten()
+                        .copied()
+                        .filter(|&index| {
+                            let file = &graph[index];
+                            file.parent().is_some_and(|dir| dir.ends_with(path))
+                                && file
+                                    .extension()
+                                    .and_then(|extension| extension.to_str())
+                                    .is_some_and(|extension| extensions.contains(&extension))
+                        })
+                        .collect(),
+                };
+                found.retain(|&index| index != current);
+                found
+            };
+
+        let types = self
+            .files
+            .keys()
+            .map(|path| self.types.get(path).copied().unwrap_or_default())
+            .collect();
+        let mut external = Vec::with_capacity(self.files.len());
+        for (source, imports) in self.files.into_values().enumerate() {
+            let source = NodeIndex::new(source);
+            let mut unresolved = Vec::new();
+            for import in imports {
+                let Some(targets) = import
+                    .targets
+                    .iter()
+                    .map(|target| resolve(&graph, source, target))
+                    .find(|targets| !targets.is_empty())
+                else {
+                    if !unresolved.contains(&import) {
+                        unresolved.push(import);
+                    }
+                    continue;
+                };
+                for target in targets {
+                    let edge = match graph.find_edge(source, target) {
+                        Some(edge) => edge,
+                        None => graph.add_edge(source, target, Vec::new()),
+                    };
+                    if !graph[edge].contains(&import) {
+                        graph[edge].push(import.clone());
+                    }
+                }
+            }
+            external.push(unresolved);
+        }
+
+        let mut cycles: Vec<Vec<PathBuf>> = kosaraju_scc(&graph)
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                let mut paths: Vec<_> = component.iter().map(|c| graph[*c].clone()).collect();
+                paths.sort();
+                paths
+            })
+            .collect();
+        cycles.sort();
+
+        ModuleGraph {
+            graph,
+            external,
+            types,
+            cycles,
+        }
+    }
+}
+
+/// The module dependency graph of a series of files.
+///
+/// The nodes are the files and the edges are the imports
+/// written in each file which are resolved to another file.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    /// The graph, whose edges contain the imports of the target by the source
+    pub graph: DiGraph<PathBuf, Vec<Import>>,
+    /// The imports of each node which are not resolved, ordered by index
+    pub external: Vec<Vec<Import>>,
+    /// The types declared in each node, ordered by index
+    pub types: Vec<FileTypes>,
+    /// The import cycles, each one containing the sorted paths of
+    /// the files which import each other
+    pub cycles: Vec<Vec<PathBuf>>,
+}
+
+impl ModuleGraph {
+    // Describes the nodes and the edges of the graph for exporting it.
+    fn export(&self) -> GraphExport<'_, PathBuf, Vec<Import>> {
+        GraphExport {
+            name: "modules",
+            graph: &self.graph,
+            node_keys: &[("path", "string"), ("external", "int")],
+            edge_keys: &[("imports", "int")],
+            node_attributes: Box::new(|index| {
+                vec![
+                    Value::Text(self.graph[index].to_string_lossy().into_owned()),
+                    Value::Int(self.external[index.index()].len()),
+                ]
+            }),
+            edge_attributes: Box::new(|imports| vec![Value::Int(imports.len())]),
+            dashed_edges: Vec::new(),
+        }
+    }
+
+    /// Exports the graph in the `Graphviz` DOT language.
+    pub fn to_dot(&self) -> String {
+        self.export().to_dot()
+    }
+
+    /// Exports the graph in the `GraphML` format.
+    pub fn to_graphml(&self) -> String {
+        self.export().to_graphml()
+    }
+}
+
+#[derive(Serialize)]
+struct ModuleNode<'a> {
+    id: usize,
+    path: &'a Path,
+    external: &'a [Import],
+}
+
+#[derive(Serialize)]
+struct ModuleEdge<'a> {
+    source: usize,
+    target: usize,
+    imports: &'a [Import],
+}
+
+impl Serialize for ModuleGraph {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let nodes: Vec<_> = self
+            .graph
+            .node_indices()
+            .map(|index| ModuleNode {
+                id: index.index(),
+                path: &self.graph[index],
+                external: &self.external[index.index()],
+            })
+            .collect();
+        let edges: Vec<_> = self
+            .graph
+            .edge_references()
+            .map(|edge| ModuleEdge {
+                source: edge.source().index(),
+                target: edge.target().index(),
+                imports: edge.weight(),
+            })
+            .collect();
+
+        let mut st = serializer.serialize_struct("module_graph", 3)?;
+        st.serialize_field("nodes", &nodes)?;
+        st.serialize_field("edges", &edges)?;
+        st.serialize_field("cycles", &self.cycles)?;
+        st.end()
+    }
+}
+
+/// Configuration options for building the module graph of a set of files.
+#[derive(Debug)]
+pub struct ImportsCfg {
+    /// Path to the file containing the code
+    pub path: PathBuf,
+    /// The builder collecting the imports of each file
+    pub builder: Arc<Mutex<ModuleGraphBuilder>>,
+    /// If `true`, the types declared in the file are counted too
+    pub types: bool,
+}
+
+/// Collects the imports of a code.
+pub struct ImportsCollect {
+    _guard: (),
+}
+
+impl Callback for ImportsCollect {
+    type Res = std::io::Result<()>;
+    type Cfg = ImportsCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        let imports = file_imports(parser, &cfg.path);
+        let types = cfg.types.then(|| file_types(parser));
+        let mut builder = cfg.builder.lock().unwrap();
+        if let Some(types) = types {
+            builder.add_types(cfg.path.clone(), types);
+        }
+        builder.add(cfg.path, imports);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CsharpParser, JavaParser, JavascriptParser, PhpParser, PythonParser, RustParser,
+        TypescriptParser,
+    };
+
+    use super::*;
+
+    fn modules<T: ParserTrait>(source: &str, path: &str) -> Vec<(String, usize)> {
+        let parser = T::new(source.as_bytes().to_vec(), Path::new(path), None);
+        file_imports(&parser, Path::new(path))
+            .into_iter()
+            .map(|import| (import.module, import.line))
+            .collect()
+    }
+
+    fn module_graph(files: Vec<(&str, Vec<Import>)>) -> Vec<(String, String, Vec<usize>)> {
+        let mut builder = ModuleGraphBuilder::default();
+        for (path, imports) in files {
+            builder.add(PathBuf::from(path), imports);
+        }
+        let graph = builder.build();
+        graph
+            .graph
+            .edge_references()
+            .map(|edge| {
+                (
+                    graph.graph[edge.source()].to_string_lossy().into_owned(),
+                    graph.graph[edge.target()].to_string_lossy().into_owned(),
+                    edge.weight().iter().map(|import| import.line).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn imports<T: ParserTrait>(source: &str, path: &'static str) -> (&'static str, Vec<Import>) {
+        let parser = T::new(source.as_bytes().to_vec(), Path::new(path), None);
+        (path, file_imports(&parser, Path::new(path)))
+    }
+
+    #[test]
+    fn expand_use_tree() {
+        assert_eq!(
+            expand_tree("a::{b as c, d::{self, e}, f::*}", "::"),
+            ["a::b", "a::d", "a::d::e", "a::f::*"]
+        );
+        assert_eq!(expand_tree("App\\{A, B\\C}", "\\"), ["App\\A", "App\\B\\C"]);
+    }
+
+    #[test]
+    fn rust_imports() {
+        let lib = "mod spaces;\n\
+                   pub mod metrics;\n\
+                   use crate::spaces::{FuncSpace, metrics as m};\n\
+                   use std::path::Path;\n\
+                   mod tests {\n    \
+                       use super::*;\n\
+                   }\n";
+        let metrics = "use super::spaces::FuncSpace;\n\
+                       mod loc;\n";
+        let loc = "use crate::metrics::*;\n";
+
+        assert_eq!(
+            modules::<RustParser>(lib, "src/lib.rs"),
+            [
+                ("spaces".to_string(), 1),
+                ("metrics".to_string(), 2),
+                ("crate::spaces::FuncSpace".to_string(), 3),
+                ("crate::spaces::metrics".to_string(), 3),
+                ("std::path::Path".to_string(), 4),
+                ("super::*".to_string(), 6),
+            ]
+        );
+        assert_eq!(
+            module_graph(vec![
+                imports::<RustParser>(lib, "src/lib.rs"),
+                imports::<RustParser>(metrics, "src/metrics/mod.rs"),
+                imports::<RustParser>(loc, "src/metrics/loc.rs"),
+                imports::<RustParser>("", "src/spaces.rs"),
+            ]),
+            [
+                ("src/lib.rs".into(), "src/spaces.rs".into(), vec![1, 3, 3]),
+                ("src/lib.rs".into(), "src/metrics/mod.rs".into(), vec![2]),
+                (
+                    "src/metrics/loc.rs".into(),
+                    "src/metrics/mod.rs".into(),
+                    vec![1]
+                ),
+                ("src/metrics/mod.rs".into(), "src/spaces.rs".into(), vec![1]),
+                (
+                    "src/metrics/mod.rs".into(),
+                    "src/metrics/loc.rs".into(),
+                    vec![2]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn python_imports() {
+        let main = "import os, pkg.util as u\n\
+                    from . import helper\n\
+                    from .pkg import (util,\n    other)\n\
+                    from __future__ import annotations\n";
+
+        assert_eq!(
+            modules::<PythonParser>(main, "app/main.py"),
+            [
+                ("os".to_string(), 1),
+                ("pkg.util".to_string(), 1),
+                (".".to_string(), 2),
+                (".pkg".to_string(), 3),
+                (".pkg".to_string(), 3),
+            ]
+        );
+        assert_eq!(
+            module_graph(vec![
+                imports::<PythonParser>(main, "app/main.py"),
+                imports::<PythonParser>("", "app/helper.py"),
+                imports::<PythonParser>("", "app/pkg/__init__.py"),
+                imports::<PythonParser>("", "app/pkg/util.py"),
+            ]),
+            [
+                ("app/main.py".into(), "app/pkg/util.py".into(), vec![1, 3]),
+                ("app/main.py".into(), "app/helper.py".into(), vec![2]),
+                ("app/main.py".into(), "app/pkg/__init__.py".into(), vec![3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn javascript_imports() {
+        let main = "import React from 'react';\n\
+                    import { a } from './lib/a';\n\
+                    export * from \"./lib\";\n\
+                    const b = require('../b.js');\n\
+                    const c = await import(`./c`);\n";
+        let ts = "import x = require('./main');\n\
+                  import type { T } from './types';\n";
+
+        assert_eq!(
+            modules::<JavascriptParser>(main, "src/main.js"),
+            [
+                ("react".to_string(), 1),
+                ("./lib/a".to_string(), 2),
+                ("./lib".to_string(), 3),
+                ("../b.js".to_string(), 4),
+                ("./c".to_string(), 5),
+            ]
+        );
+        assert_eq!(
+            module_graph(vec![
+                imports::<JavascriptParser>(main, "src/main.js"),
+                imports::<JavascriptParser>("", "src/lib/a.js"),
+                imports::<JavascriptParser>("", "src/lib/index.js"),
+                imports::<JavascriptParser>("", "b.js"),
+                imports::<TypescriptParser>("", "src/c.ts"),
+                imports::<TypescriptParser>(ts, "src/d.ts"),
+            ]),
+            [
+                ("src/d.ts".into(), "src/main.js".into(), vec![1]),
+                ("src/main.js".into(), "src/lib/a.js".into(), vec![2]),
+                ("src/main.js".into(), "src/lib/index.js".into(), vec![3]),
+                ("src/main.js".into(), "b.js".into(), vec![4]),
+                ("src/main.js".into(), "src/c.ts".into(), vec![5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn java_csharp_php_imports() {
+        let java = "package com.app;\n\
+                    import com.app.model.User;\n\
+                    import static com.app.util.Strings.trim;\n\
+                    import com.app.service.*;\n\
+                    import java.util.List;\n\
+                    class Main {}\n";
+        let csharp = "using System;\n\
+                      using App.Models;\n\
+                      using Util = App.Helpers.Util;\n\
+                      class Program {}\n";
+        let php = "<?php\n\
+                   use App\\Models\\{User, Post as P};\n\
+                   require_once __DIR__ . '/config.php';\n";
+
+        assert_eq!(
+            modules::<JavaParser>(java, "src/com/app/Main.java"),
+            [
+                ("com.app.model.User".to_string(), 2),
+                ("com.app.util.Strings.trim".to_string(), 3),
+                ("com.app.service.*".to_string(), 4),
+                ("java.util.List".to_string(), 5),
+            ]
+        );
+        assert_eq!(
+            module_graph(vec![
+                imports::<JavaParser>(java, "src/com/app/Main.java"),
+                imports::<JavaParser>("", "src/com/app/model/User.java"),
+                imports::<JavaParser>("", "src/com/app/util/Strings.java"),
+                imports::<JavaParser>("", "src/com/app/service/A.java"),
+                imports::<JavaParser>("", "src/com/app/service/B.java"),
+                imports::<CsharpParser>(csharp, "App/Program.cs"),
+                imports::<CsharpParser>("", "App/Models/User.cs"),
+                imports::<CsharpParser>("", "App/Helpers/Util.cs"),
+                imports::<PhpParser>(php, "app/index.php"),
+                imports::<PhpParser>("", "app/config.php"),
+                imports::<PhpParser>("", "app/Models/User.php"),
+                imports::<PhpParser>("", "app/Models/Post.php"),
+            ]),
+            [
+                (
+                    "App/Program.cs".into(),
+                    "App/Models/User.cs".into(),
+                    vec![2]
+                ),
+                (
+                    "App/Program.cs".into(),
+                    "App/Helpers/Util.cs".into(),
+                    vec![3]
+                ),
+                (
+                    "app/index.php".into(),
+                    "app/Models/User.php".into(),
+                    vec![2]
+                ),
+                (
+                    "app/index.php".into(),
+                    "app/Models/Post.php".into(),
+                    vec![2]
+                ),
+                ("app/index.php".into(), "app/config.php".into(), vec![3]),
+                (
+                    "src/com/app/Main.java".into(),
+                    "src/com/app/model/User.java".into(),
+                    vec![2]
+                ),
+                (
+                    "src/com/app/Main.java".into(),
+                    "src/com/app/util/Strings.java".into(),
+                    vec![3]
+                ),
+                (
+                    "src/com/app/Main.java".into(),
+                    "src/com/app/service/A.java".into(),
+                    vec![4]
+                ),
+                (
+                    "src/com/app/Main.java".into(),
+                    "src/com/app/service/B.java".into(),
+                    vec![4]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn module_graph_cycles() {
+        let graph = {
+            let mut builder = ModuleGraphBuilder::default();
+            for (path, source) in [
+                ("a.py", "import b\nimport requests\n"),
+                ("b.py", "from c import f\n"),
+                ("c.py", "import a\n"),
+                ("d.py", "import a\n"),
+            ] {
+                let (path, imports) = imports::<PythonParser>(source, path);
+                builder.add(PathBuf::from(path), imports);
+            }
+            builder.build()
+        };
+
+        assert_eq!(
+            graph.cycles,
+            [vec![
+                PathBuf::from("a.py"),
+                PathBuf::from("b.py"),
+                PathBuf::from("c.py")
+            ]]
+        );
+        insta::assert_snapshot!(graph.to_dot(), @r#"
+            digraph modules {
+                n0 [label="a.py", external=1];
+                n1 [label="b.py", external=0];
+                n2 [label="c.py", external=0];
+                n3 [label="d.py", external=0];
+                n0 -> n1 [imports=1];
+                n1 -> n2 [imports=1];
+                n2 -> n0 [imports=1];
+                n3 -> n0 [imports=1];
+            }
+            "#);
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use petgraph::{
+    algo::kosaraju_scc,
+    graph::{DiGraph, NodeIndex},
+    visit::{Dfs, EdgeRef},
+};
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+
+use crate::graph::{GraphExport, Value};
+use crate::include_paths::IncludeResolver;
+use crate::preproc::PreprocFile;
+
+// The extensions of the files compiled as translation units.
+const TRANSLATION_UNIT_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx", "c++", "m", "mm"];
+
+/// Returns `true` if a file is compiled as a translation unit,
+/// that is when it is a source file and not a header.
+pub fn is_translation_unit(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| TRANSLATION_UNIT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// The include graph of a series of `C/C++` files.
+///
+/// The nodes are the files and the edges are the resolved
+/// include directives written in each file.
+///
+/// It is built by [`fix_includes`].
+///
+/// [`fix_includes`]: fn.fix_includes.html
+#[derive(Debug, Default)]
+pub struct IncludeGraph {
+    /// The graph of the direct include directives
+    pub graph: DiGraph<PathBuf, ()>,
+    /// The number of translation units which transitively include each node
+    pub fan_in: Vec<usize>,
+    /// The include cycles, each one containing the sorted paths of
+    /// the files which include each other
+    pub cycles: Vec<Vec<PathBuf>>,
+    /// If `true`, the transitive includes are exported
+    /// along with the direct ones
+    pub transitive_edges: bool,
+}
+
+impl IncludeGraph {
+    // Builds the include graph of the preprocessed files, returning it
+    // with the include directives which do not refer to any analyzed file.
+    pub(crate) fn new<S: ::std::hash::BuildHasher>(
+        files: &HashMap<PathBuf, PreprocFile, S>,
+        resolver: &IncludeResolver<S>,
+    ) -> (Self, Vec<(PathBuf, String)>) {
+        let mut graph = DiGraph::new();
+        let mut nodes = HashMap::new();
+        let mut system_includes = Vec::new();
+
+        // Sort the files and the includes to get a reproducible graph
+        let mut paths: Vec<_> = files.keys().collect();
+        paths.sort();
+        for path in paths.iter() {
+            nodes.insert((*path).clone(), graph.add_node((*path).clone()));
+        }
+
+        for path in paths {
+            let node = nodes[path];
+            let file = &files[path];
+            let direct_includes = file.direct_includes.iter().map(|i| (i, false));
+            let angle_includes = file.angle_includes.iter().map(|i| (i, true));
+            let mut includes: Vec<_> = direct_includes.chain(angle_includes).collect();
+            includes.sort();
+            for (include, angle) in includes {
+                let mut possibilities = resolver.resolve(path, include, angle);
+                if possibilities.is_empty() {
+                    system_includes.push((path.clone(), include.clone()));
+                }
+                possibilities.sort();
+                for included in possibilities {
+                    if &included == path {
+                        // TODO: add an option to display warning
+                        eprintln!("Warning: possible self inclusion {path:?}");
+                        continue;
+                    }
+                    let target = *nodes
+                        .entry(included.clone())
+                        .or_insert_with(|| graph.add_node(included));
+                    graph.update_edge(node, target, ());
+                }
+            }
+        }
+
+        let mut include_graph = Self {
+            graph,
+            ..Default::default()
+        };
+
+        // The closure of a translation unit is only needed while it is visited
+        let mut fan_in = vec![0; include_graph.graph.node_count()];
+        for node in include_graph.graph.node_indices() {
+            if is_translation_unit(&include_graph.graph[node]) {
+                for included in include_graph.transitive_includes(node) {
+                    fan_in[included.index()] += 1;
+                }
+            }
+        }
+        include_graph.fan_in = fan_in;
+
+        let graph = &include_graph.graph;
+        let mut cycles: Vec<Vec<PathBuf>> = kosaraju_scc(graph)
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                let mut paths: Vec<_> = component.iter().map(|c| graph[*c].clone()).collect();
+                paths.sort();
+                paths
+            })
+            .collect();
+        cycles.sort();
+        include_graph.cycles = cycles;
+
+        (include_graph, system_includes)
+    }
+
+    /// Returns the files transitively included by a node, ordered by index.
+    ///
+    /// A file is not part of its own includes, even when it is part of a cycle.
+    pub fn transitive_includes(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut dfs = Dfs::new(&self.graph, node);
+        let mut reached = BTreeSet::new();
+        while let Some(next) = dfs.next(&self.graph) {
+            if next != node {
+                reached.insert(next);
+            }
+        }
+        reached.into_iter().collect()
+    }
+
+    // The transitive edges which are not direct include directives.
+    fn indirect_edges(&self) -> impl Iterator<Item = (NodeIndex, NodeIndex)> + '_ {
+        self.graph
+            .node_indices()
+            .filter(|_| self.transitive_edges)
+            .flat_map(move |source| {
+                self.transitive_includes(source)
+                    .into_iter()
+                    .filter(move |target| !self.graph.contains_edge(source, *target))
+                    .map(move |target| (source, target))
+            })
+    }
+
+    // Describes the nodes and the edges of the graph for exporting it.
+    fn export(&self) -> GraphExport<'_, PathBuf, ()> {
+        GraphExport {
+            name: "includes",
+            graph: &self.graph,
+            node_keys: &[
+                ("path", "string"),
+                ("translation_unit", "boolean"),
+                ("fan_in", "int"),
+            ],
+            edge_keys: &[("direct", "boolean")],
+            node_attributes: Box::new(|index| {
+                vec![
+                    Value::Text(self.graph[index].to_string_lossy().into_owned()),
+                    Value::Bool(is_translation_unit(&self.graph[index])),
+                    Value::Int(self.fan_in[index.index()]),
+                ]
+            }),
+            edge_attributes: Box::new(|_| vec![Value::Bool(true)]),
+            dashed_edges: self
+                .indirect_edges()
+                .map(|(source, target)| (source, target, vec![Value::Bool(false)]))
+                .collect(),
+        }
+    }
+
+    /// Exports the graph in the `Graphviz` DOT language.
+    ///
+    /// The direct includes are solid edges while the transitive
+    /// ones, when exported, are dashed edges.
+    pub fn to_dot(&self) -> String {
+        self.export().to_dot()
+    }
+
+    /// Exports the graph in the `GraphML` format.
+    pub fn to_graphml(&self) -> String {
+        self.export().to_graphml()
+    }
+}
+
+#[derive(Serialize)]
+struct IncludeNode<'a> {
+    id: usize,
+    path: &'a Path,
+    translation_unit: bool,
+    fan_in: usize,
+}
+
+#[derive(Serialize)]
+struct IncludeEdge {
+    source: usize,
+    target: usize,
+    direct: bool,
+}
+
+impl Serialize for IncludeGraph {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let nodes: Vec<_> = self
+            .graph
+            .node_indices()
+            .map(|index| IncludeNode {
+                id: index.index(),
+                path: &self.graph[index],
+                translation_unit: is_translation_unit(&self.graph[index]),
+                fan_in: self.fan_in[index.index()],
+            })
+            .collect();
+        let direct = self.graph.edge_references().map(|edge| IncludeEdge {
+            source: edge.source().index(),
+            target: edge.target().index(),
+            direct: true,
+        });
+        let indirect = self.indirect_edges().map(|(source, target)| IncludeEdge {
+            source: source.index(),
+            target: target.index(),
+            direct: false,
+        });
+        let edges: Vec<_> = direct.chain(indirect).collect();
+
+        let mut st = serializer.serialize_struct("include_graph", 3)?;
+        st.serialize_field("nodes", &nodes)?;
+        st.serialize_field("edges", &edges)?;
+        st.serialize_field("cycles", &self.cycles)?;
+        st.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::include_paths::IncludePaths;
+    use crate::preproc::fix_includes;
+
+    use super::*;
+
+    fn include_graph(files: &[(&str, &[&str])]) -> IncludeGraph {
+        let mut preproc = HashMap::new();
+        let mut all_files: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for (path, includes) in files {
+            let path = PathBuf::from(path);
+            let file = PreprocFile {
+                direct_includes: includes.iter().map(|i| i.to_string()).collect(),
+                ..Default::default()
+            };
+            all_files
+                .entry(path.file_name().unwrap().to_str().unwrap().to_string())
+                .or_default()
+                .push(path.clone());
+            preproc.insert(path, file);
+        }
+        let mut graph = fix_includes(&mut preproc, &all_files, &IncludePaths::default());
+        graph.transitive_edges = true;
+        graph
+    }
+
+    #[test]
+    fn include_graph_fan_in() {
+        let graph = include_graph(&[
+            ("/src/main.cpp", &["a.h"]),
+            ("/src/other.cpp", &["a.h", "b.h"]),
+            ("/src/a.h", &["b.h"]),
+            ("/src/b.h", &["c.h"]),
+            ("/src/c.h", &[]),
+        ]);
+
+        let fan_in: Vec<_> = graph
+            .graph
+            .node_indices()
+            .map(|index| {
+                (
+                    graph.graph[index].to_str().unwrap(),
+                    graph.fan_in[index.index()],
+                )
+            })
+            .collect();
+        assert_eq!(
+            fan_in,
+            [
+                ("/src/a.h", 2),
+                ("/src/b.h", 2),
+                ("/src/c.h", 2),
+                ("/src/main.cpp", 0),
+                ("/src/other.cpp", 0),
+            ]
+        );
+        assert!(graph.cycles.is_empty());
+
+        insta::assert_snapshot!(graph.to_dot(), @r#"
+        digraph includes {
+            n0 [label="/src/a.h", translation_unit=false, fan_in=2];
+            n1 [label="/src/b.h", translation_unit=false, fan_in=2];
+            n2 [label="/src/c.h", translation_unit=false, fan_in=2];
+            n3 [label="/src/main.cpp", translation_unit=true, fan_in=0];
+            n4 [label="/src/other.cpp", translation_unit=true, fan_in=0];
+            n0 -> n1 [direct=true];
+            n1 -> n2 [direct=true];
+            n3 -> n0 [direct=true];
+            n4 -> n0 [direct=true];
+            n4 -> n1 [direct=true];
+            n0 -> n2 [direct=false, style=dashed];
+            n3 -> n1 [direct=false, style=dashed];
+            n3 -> n2 [direct=false, style=dashed];
+            n4 -> n2 [direct=false, style=dashed];
+        }
+        "#);
+    }
+
+    #[test]
+    fn include_graph_cycles() {
+        let graph = include_graph(&[
+            ("/src/main.c", &["a.h"]),
+            ("/src/a.h", &["b.h"]),
+            ("/src/b.h", &["a.h", "b.h"]),
+        ]);
+
+        assert_eq!(
+            graph.cycles,
+            [vec![PathBuf::from("/src/a.h"), PathBuf::from("/src/b.h")]]
+        );
+        // A file does not include itself even when it is part of a cycle
+        assert_eq!(
+            graph.transitive_includes(NodeIndex::new(0)),
+            [NodeIndex::new(1)]
+        );
+        assert_eq!(graph.fan_in, [1, 1, 0]);
+    }
+}
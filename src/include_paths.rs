@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fs;
+use std::hash::BuildHasher;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::tools::{guess_file, normalize_path};
+
+/// An entry of a `compile_commands.json` compilation database.
+#[derive(Debug, Default, Deserialize)]
+pub struct CompileCommand {
+    /// The working directory of the compilation
+    pub directory: PathBuf,
+    /// The compiled file
+    pub file: PathBuf,
+    /// The compilation command as a single shell-escaped string
+    pub command: Option<String>,
+    /// The compilation command as a list of arguments
+    pub arguments: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default)]
+struct SearchDirs {
+    // The directories searched only for the quoted includes
+    quote: Vec<PathBuf>,
+    // The directories searched for all the includes
+    all: Vec<PathBuf>,
+}
+
+/// The directories where the files included by `C/C++` files are searched.
+///
+/// The directories are either given for all the files or extracted
+/// from the compilation command of each file.
+#[derive(Debug, Default)]
+pub struct IncludePaths {
+    dirs: SearchDirs,
+    files: HashMap<PathBuf, SearchDirs>,
+}
+
+impl IncludePaths {
+    /// Adds a directory searched for the includes of all the files.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.dirs.all.push(absolute(dir.as_ref()));
+    }
+
+    /// Adds the include directories of a compilation command,
+    /// that is the `-I`, `-isystem`, `-idirafter` and `-iquote` options.
+    pub fn add_compile_command(&mut self, command: &CompileCommand) {
+        let arguments = match (&command.arguments, &command.command) {
+            (Some(arguments), _) => arguments.clone(),
+            (None, Some(command)) => split_command(command),
+            (None, None) => Vec::new(),
+        };
+
+        let mut dirs = SearchDirs::default();
+        let mut arguments = arguments.iter();
+        while let Some(argument) = arguments.next() {
+            for (option, quote) in [
+                ("-iquote", true),
+                ("-isystem", false),
+                ("-idirafter", false),
+                ("-I", false),
+            ] {
+                let Some(dir) = argument.strip_prefix(option) else {
+                    continue;
+                };
+                let dir = if dir.is_empty() {
+                    match arguments.next() {
+                        Some(dir) => dir.as_str(),
+                        None => break,
+                    }
+                } else {
+                    dir
+                };
+                let dir = absolute(&command.directory.join(dir));
+                if quote {
+                    dirs.quote.push(dir);
+                } else {
+                    dirs.all.push(dir);
+                }
+                break;
+            }
+        }
+
+        let file = absolute(&command.directory.join(&command.file));
+        self.files.insert(file, dirs);
+    }
+
+    /// Returns `true` if no include directory has been added.
+    pub fn is_empty(&self) -> bool {
+        self.dirs.quote.is_empty() && self.dirs.all.is_empty() && self.files.is_empty()
+    }
+
+    // The directories to search for an include of a file,
+    // the ones of its compilation command coming first.
+    fn search_dirs<'a>(&'a self, file: &Path, angle: bool) -> impl Iterator<Item = &'a Path> {
+        let own = self.files.get(file);
+        let quote = (!angle)
+            .then(|| own.into_iter().chain([&self.dirs]))
+            .into_iter()
+            .flatten()
+            .flat_map(|dirs| dirs.quote.iter());
+        let all = own
+            .into_iter()
+            .chain([&self.dirs])
+            .flat_map(|dirs| dirs.all.iter());
+        quote.chain(all).map(PathBuf::as_path)
+    }
+}
+
+/// Resolves the include directives of `C/C++` files to the analyzed files.
+pub(crate) struct IncludeResolver<'a, S> {
+    all_files: &'a HashMap<String, Vec<PathBuf>, S>,
+    include_paths: &'a IncludePaths,
+    // The analyzed files indexed by their absolute path
+    files: HashMap<PathBuf, &'a PathBuf>,
+}
+
+impl<'a, S: BuildHasher> IncludeResolver<'a, S> {
+    pub(crate) fn new(
+        all_files: &'a HashMap<String, Vec<PathBuf>, S>,
+        include_paths: &'a IncludePaths,
+    ) -> Self {
+        let files = if include_paths.is_empty() {
+            HashMap::new()
+        } else {
+            all_files
+                .values()
+                .flatten()
+                .map(|path| (absolute(path), path))
+                .collect()
+        };
+        Self {
+            all_files,
+            include_paths,
+            files,
+        }
+    }
+
+    /// Returns the analyzed files an include directive of `current` refers to,
+    /// an empty vector meaning that it is a system or an unknown header.
+    ///
+    /// Without include directories, the included file is guessed from its name
+    /// and from its distance to `current`.
+    pub(crate) fn resolve(&self, current: &Path, include: &str, angle: bool) -> Vec<PathBuf> {
+        if self.include_paths.is_empty() {
+            return guess_file(current, include, self.all_files);
+        }
+
+        let current = absolute(current);
+        // A quoted include is searched in the directory of the including file first
+        let current_dir = current.parent().filter(|_| !angle);
+        current_dir
+            .into_iter()
+            .chain(self.include_paths.search_dirs(&current, angle))
+            .find_map(|dir| self.files.get(&absolute(&dir.join(include))))
+            .map(|path| vec![(*path).clone()])
+            .unwrap_or_default()
+    }
+}
+
+// Returns the absolute path of a file, resolving the symbolic links when it exists.
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| {
+        normalize_path(std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()))
+    })
+}
+
+// Splits a shell command line into its arguments.
+fn split_command(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut argument = String::new();
+    let mut in_argument = false;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => argument.push(c),
+            ('\\', _) => {
+                argument.extend(chars.next());
+                in_argument = true;
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_argument = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if in_argument {
+                    arguments.push(mem::take(&mut argument));
+                    in_argument = false;
+                }
+            }
+            (c, _) => {
+                argument.push(c);
+                in_argument = true;
+            }
+        }
+    }
+    if in_argument {
+        arguments.push(argument);
+    }
+    arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParserTrait, PreprocParser, PreprocResults, preprocess};
+
+    use super::*;
+
+    fn all_files(paths: &[&str]) -> HashMap<String, Vec<PathBuf>> {
+        let mut all_files: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for path in paths {
+            let path = PathBuf::from(path);
+            all_files
+                .entry(path.file_name().unwrap().to_str().unwrap().to_string())
+                .or_default()
+                .push(path);
+        }
+        all_files
+    }
+
+    #[test]
+    fn split_compile_command() {
+        assert_eq!(
+            split_command(r#"cc -I "my dir" -DNAME='"a b"' -Iinc\ lude -c  a.c"#),
+            [
+                "cc",
+                "-I",
+                "my dir",
+                "-DNAME=\"a b\"",
+                "-Iinc lude",
+                "-c",
+                "a.c"
+            ]
+        );
+    }
+
+    #[test]
+    fn preprocess_angle_includes() {
+        let code = "#include \"a.h\"\n#include <stdio.h>\n#include < sys/types.h >\n";
+        let path = PathBuf::from("/project/main.c");
+        let parser = PreprocParser::new(code.as_bytes().to_vec(), &path, None);
+        let mut results = PreprocResults::default();
+        preprocess(&parser, &path, &mut results);
+
+        let file = &results.files[&path];
+        let mut direct: Vec<_> = file.direct_includes.iter().collect();
+        direct.sort();
+        let mut angle: Vec<_> = file.angle_includes.iter().collect();
+        angle.sort();
+        assert_eq!(direct, ["a.h"]);
+        assert_eq!(angle, ["stdio.h", "sys/types.h"]);
+    }
+
+    #[test]
+    fn resolve_with_include_paths() {
+        let all_files = all_files(&[
+            "/project/src/main.c",
+            "/project/src/config.h",
+            "/project/include/config.h",
+            "/project/include/lib/util.h",
+        ]);
+        let mut include_paths = IncludePaths::default();
+        include_paths.add_compile_command(&CompileCommand {
+            directory: PathBuf::from("/project/build"),
+            file: PathBuf::from("../src/main.c"),
+            command: Some("cc -I ../include -isystem /usr/include -c ../src/main.c".into()),
+            arguments: None,
+        });
+        let resolver = IncludeResolver::new(&all_files, &include_paths);
+        let main = Path::new("/project/src/main.c");
+
+        // A quoted include prefers the directory of the including file
+        assert_eq!(
+            resolver.resolve(main, "config.h", false),
+            [PathBuf::from("/project/src/config.h")]
+        );
+        // While an angle include only looks in the include directories
+        assert_eq!(
+            resolver.resolve(main, "config.h", true),
+            [PathBuf::from("/project/include/config.h")]
+        );
+        assert_eq!(
+            resolver.resolve(main, "lib/util.h", true),
+            [PathBuf::from("/project/include/lib/util.h")]
+        );
+        assert!(resolver.resolve(main, "util.h", true).is_empty());
+        assert!(resolver.resolve(main, "stdio.h", true).is_empty());
+    }
+}
@@ -78,6 +78,9 @@ pub use crate::ops::*;
 mod find;
 pub use crate::find::*;
 
+mod query;
+pub use crate::query::*;
+
 mod function;
 pub use crate::function::*;
 
@@ -88,6 +91,7 @@ mod count;
 pub use crate::count::*;
 
 mod preproc;
+pub use crate::c_macro::SourceMap;
 pub use crate::preproc::*;
 
 mod vue_extract;
@@ -102,6 +106,9 @@ pub use crate::tools::*;
 mod concurrent_files;
 pub use crate::concurrent_files::*;
 
+mod skip;
+pub use crate::skip::*;
+
 mod traits;
 pub use crate::traits::*;
 
@@ -111,6 +118,42 @@ pub use crate::parser::*;
 mod comment_rm;
 pub use crate::comment_rm::*;
 
+mod comments;
+pub use crate::comments::*;
+
+mod todo;
+pub use crate::todo::*;
+
+mod clones;
+pub use crate::clones::*;
+
+mod graph;
+pub use crate::graph::*;
+
+mod call_graph;
+pub use crate::call_graph::*;
+
+mod include_graph;
+pub use crate::include_graph::*;
+
+mod imports;
+pub use crate::imports::*;
+
+mod packages;
+pub use crate::packages::*;
+
+mod architecture;
+pub use crate::architecture::*;
+
+mod churn;
+pub use crate::churn::*;
+
+mod coverage_report;
+pub use crate::coverage_report::*;
+
+mod include_paths;
+pub use crate::include_paths::*;
+
 /// Compute metrics for a Vue Single File Component by analyzing all sections
 /// and combining the LoC metrics.
 pub fn get_vue_metrics(source: Vec<u8>, path: &std::path::Path) -> Option<FuncSpace> {
@@ -124,6 +167,7 @@ pub fn get_vue_metrics(source: Vec<u8>, path: &std::path::Path) -> Option<FuncSp
         start_line: 1,
         end_line: source.iter().filter(|&&b| b == b'\n').count() + 1,
         kind: SpaceKind::Unit,
+        is_test: false,
         spaces: Vec::new(),
         metrics: CodeMetrics::default(),
     };
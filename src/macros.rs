@@ -51,6 +51,20 @@ macro_rules! implement_metric_trait {
            }
         )+
     );
+    (Docs, $($code:ident),+) => (
+        $(
+           impl Docs for $code {
+               fn compute(_node: &Node, _code: &[u8], _stats: &mut Stats) {}
+           }
+        )+
+    );
+    (Ifdef, $($code:ident),+) => (
+        $(
+           impl Ifdef for $code {
+               fn compute(_node: &Node, _code: &[u8], _stats: &mut Stats) {}
+           }
+        )+
+    );
     (Wmc, $($code:ident),+) => (
         $(
            impl Wmc for $code {
@@ -202,6 +216,20 @@ macro_rules! mk_action {
             }
         }
 
+        /// Returns all function spaces data of a code, computed with the given
+        /// options.
+        #[inline(always)]
+        pub fn get_function_spaces_with_opts(lang: &LANG, source: Vec<u8>, path: &Path, pr: Option<Arc<PreprocResults>>, opts: &MetricsOpts) -> Option<FuncSpace> {
+            match lang {
+                $(
+                    LANG::$camel => {
+                        let parser = $parser::new(source, &path, pr);
+                        metrics_with_opts(&parser, &path, opts)
+                    },
+                )*
+            }
+        }
+
         /// Returns all operators and operands of each space in a code.
         ///
         /// # Examples
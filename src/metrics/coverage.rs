@@ -0,0 +1,131 @@
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+use std::fmt;
+
+/// The `Coverage` metric.
+///
+/// This metric measures the line coverage of a space, read from
+/// a coverage report, and its `CRAP` (Change Risk Anti-Patterns) score,
+/// that is `comp^2 * (1 - cov)^3 + comp`, where `comp` is the cyclomatic
+/// complexity of the space and `cov` its coverage.
+///
+/// A space only has a coverage when its file is part of the report.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    lines: usize,
+    covered: usize,
+    crap: f64,
+    crap_sum: f64,
+    crap_max: f64,
+    is_enabled: bool,
+}
+
+impl Serialize for Stats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("coverage", 6)?;
+        st.serialize_field("lines", &self.lines())?;
+        st.serialize_field("covered", &self.covered())?;
+        st.serialize_field("coverage", &self.coverage())?;
+        st.serialize_field("crap", &self.crap())?;
+        st.serialize_field("crap_sum", &self.crap_sum())?;
+        st.serialize_field("crap_max", &self.crap_max())?;
+        st.end()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "lines: {}, covered: {}, coverage: {}, crap: {}, crap_sum: {}, crap_max: {}",
+            self.lines(),
+            self.covered(),
+            self.coverage(),
+            self.crap(),
+            self.crap_sum(),
+            self.crap_max()
+        )
+    }
+}
+
+impl Stats {
+    /// Merges the `CRAP` scores of a subspace into the ones of a space
+    pub fn merge(&mut self, other: &Stats) {
+        self.crap_sum += other.crap_sum;
+        self.crap_max = self.crap_max.max(other.crap_max);
+    }
+
+    /// Returns the number of executable lines of a space
+    /// in the coverage report.
+    #[inline(always)]
+    pub fn lines(&self) -> f64 {
+        self.lines as f64
+    }
+
+    /// Returns the number of executed lines of a space.
+    #[inline(always)]
+    pub fn covered(&self) -> f64 {
+        self.covered as f64
+    }
+
+    /// Returns the ratio of executed lines of a space.
+    ///
+    /// If there are no executable lines in a space, its value is `NAN`.
+    #[inline(always)]
+    pub fn coverage(&self) -> f64 {
+        self.covered() / self.lines()
+    }
+
+    /// Returns the `CRAP` score of a space.
+    #[inline(always)]
+    pub fn crap(&self) -> f64 {
+        self.crap
+    }
+
+    /// Returns the sum of the `CRAP` scores of the functions of a space,
+    /// including the space itself when it is a function.
+    #[inline(always)]
+    pub fn crap_sum(&self) -> f64 {
+        self.crap_sum
+    }
+
+    /// Returns the maximum `CRAP` score among the functions of a space,
+    /// including the space itself when it is a function.
+    #[inline(always)]
+    pub fn crap_max(&self) -> f64 {
+        self.crap_max
+    }
+
+    /// Computes the metric of a space from its executable and executed lines
+    /// and from its cyclomatic complexity.
+    pub(crate) fn compute(
+        &mut self,
+        lines: usize,
+        covered: usize,
+        cyclomatic: f64,
+        function: bool,
+    ) {
+        // A space without executable lines has nothing to cover
+        let uncovered = if lines == 0 {
+            0.
+        } else {
+            1. - covered as f64 / lines as f64
+        };
+        self.lines = lines;
+        self.covered = covered;
+        self.crap = cyclomatic.powi(2) * uncovered.powi(3) + cyclomatic;
+        let crap = if function { self.crap } else { 0. };
+        self.crap_sum = crap;
+        self.crap_max = crap;
+        self.is_enabled = true;
+    }
+
+    // Checks if the `Coverage` metric is disabled
+    #[inline(always)]
+    pub(crate) fn is_disabled(&self) -> bool {
+        !self.is_enabled
+    }
+}
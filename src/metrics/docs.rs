@@ -0,0 +1,600 @@
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+use std::fmt;
+
+use crate::checker::Checker;
+use crate::comments::{is_doc, is_docstring};
+use crate::langs::*;
+use crate::macros::implement_metric_trait;
+use crate::node::Node;
+use crate::npm::is_java_public;
+use crate::*;
+
+/// The `Docs` metric.
+///
+/// This metric counts the public functions, classes and methods
+/// of a space, and how many of them are documented.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    public: usize,
+    documented: usize,
+    public_sum: usize,
+    documented_sum: usize,
+    is_enabled: bool,
+}
+
+impl Serialize for Stats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("docs", 3)?;
+        st.serialize_field("public", &self.public_sum())?;
+        st.serialize_field("documented", &self.documented_sum())?;
+        st.serialize_field("coverage", &self.coverage())?;
+        st.end()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "public: {}, documented: {}, coverage: {}",
+            self.public_sum(),
+            self.documented_sum(),
+            self.coverage()
+        )
+    }
+}
+
+impl Stats {
+    /// Merges a second `Docs` metric into the first one
+    pub fn merge(&mut self, other: &Stats) {
+        self.public_sum += other.public_sum;
+        self.documented_sum += other.documented_sum;
+    }
+
+    /// Returns the number of public items in a space.
+    #[inline(always)]
+    pub fn public(&self) -> f64 {
+        self.public as f64
+    }
+
+    /// Returns the number of documented public items in a space.
+    #[inline(always)]
+    pub fn documented(&self) -> f64 {
+        self.documented as f64
+    }
+
+    /// Returns the number of public items sum in a space.
+    #[inline(always)]
+    pub fn public_sum(&self) -> f64 {
+        self.public_sum as f64
+    }
+
+    /// Returns the number of documented public items sum in a space.
+    #[inline(always)]
+    pub fn documented_sum(&self) -> f64 {
+        self.documented_sum as f64
+    }
+
+    /// Returns the documentation coverage of a space
+    ///
+    /// This value is computed dividing the number of documented
+    /// public items by the number of public items.
+    ///
+    /// If there are no public items in a space, its value is `NAN`.
+    #[inline(always)]
+    pub fn coverage(&self) -> f64 {
+        self.documented_sum() / self.public_sum()
+    }
+
+    #[inline(always)]
+    pub(crate) fn compute_sum(&mut self) {
+        self.public_sum += self.public;
+        self.documented_sum += self.documented;
+    }
+
+    // Checks if the `Docs` metric is disabled, or if there are
+    // no public items in a space, so no coverage to report
+    #[inline(always)]
+    pub(crate) fn is_disabled(&self) -> bool {
+        !self.is_enabled || self.public_sum == 0
+    }
+
+    #[inline(always)]
+    fn add(&mut self, is_documented: bool) {
+        self.public += 1;
+        if is_documented {
+            self.documented += 1;
+        }
+    }
+}
+
+pub trait Docs
+where
+    Self: Checker,
+{
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats);
+}
+
+// Checks whether an item is preceded by a doc comment,
+// skipping its attributes and the regular comments.
+fn has_doc_comment<T: Checker>(node: &Node, code: &[u8], attributes: &[u16]) -> bool {
+    let mut prev = node.previous_sibling();
+    while let Some(node) = prev {
+        let text = &code[node.start_byte()..node.end_byte()];
+        if T::is_comment(&node) {
+            if is_doc(text) {
+                return true;
+            }
+        } else if attributes.contains(&node.kind_id()) {
+            // Rust items can also be documented with the `doc` attribute
+            if text.starts_with(b"#[doc") {
+                return true;
+            }
+        } else {
+            return false;
+        }
+        prev = node.previous_sibling();
+    }
+    false
+}
+
+impl Docs for RustCode {
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats) {
+        use Rust::*;
+
+        stats.is_enabled = true;
+
+        let is_pub = |node: &Node| {
+            node.first_child(|id| id == VisibilityModifier)
+                .is_some_and(|v| &code[v.start_byte()..v.end_byte()] == b"pub")
+        };
+        let is_item = |node: &Node| {
+            matches!(
+                node.kind_id().into(),
+                FunctionItem | StructItem | EnumItem | UnionItem | TraitItem
+            )
+        };
+        let is_method =
+            |node: &Node| matches!(node.kind_id().into(), FunctionItem | FunctionSignatureItem);
+
+        let parent = node.parent();
+        let is_parent = |id: Rust| parent.is_some_and(|p| p.kind_id() == id);
+        let in_list = node.kind_id() == DeclarationList;
+
+        let in_module = node.kind_id() == SourceFile || (in_list && is_parent(ModItem));
+        // The methods of a trait are as public as the trait
+        let in_trait = in_list && is_parent(TraitItem) && parent.is_some_and(|p| is_pub(&p));
+        // The methods of a trait implementation are documented in the trait,
+        // so only inherent implementations are considered
+        let in_impl = in_list
+            && is_parent(ImplItem)
+            && parent.is_some_and(|p| p.child_by_field_name("trait").is_none());
+        if !(in_module || in_trait || in_impl) {
+            return;
+        }
+
+        for item in node.children() {
+            let is_public = if in_module {
+                is_item(&item) && is_pub(&item)
+            } else {
+                is_method(&item) && (in_trait || is_pub(&item))
+            };
+            if is_public {
+                stats.add(has_doc_comment::<Self>(
+                    &item,
+                    code,
+                    &[AttributeItem as u16],
+                ));
+            }
+        }
+    }
+}
+
+impl Docs for PythonCode {
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats) {
+        use Python::*;
+
+        stats.is_enabled = true;
+
+        let is_container = match node.kind_id().into() {
+            Module => true,
+            Block | Block2 => node
+                .parent()
+                .is_some_and(|p| p.kind_id() == ClassDefinition),
+            _ => false,
+        };
+        if !is_container {
+            return;
+        }
+
+        for item in node.children() {
+            let definition = if item.kind_id() == DecoratedDefinition {
+                let Some(definition) = item.child_by_field_name("definition") else {
+                    continue;
+                };
+                definition
+            } else {
+                item
+            };
+            if !matches!(
+                definition.kind_id().into(),
+                FunctionDefinition | ClassDefinition
+            ) {
+                continue;
+            }
+            // Names starting with an underscore are private by convention
+            let is_public = definition
+                .child_by_field_name("name")
+                .is_some_and(|name| !code[name.start_byte()..].starts_with(b"_"));
+            if is_public {
+                let is_documented = definition.child_by_field_name("body").is_some_and(|body| {
+                    body.children()
+                        .find(|n| n.kind_id() != Comment)
+                        .is_some_and(|n| is_docstring(&n))
+                });
+                stats.add(is_documented);
+            }
+        }
+    }
+}
+
+impl Docs for JavaCode {
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats) {
+        use Java::*;
+
+        stats.is_enabled = true;
+
+        // All the members of an interface are implicitly public
+        // Source: https://docs.oracle.com/javase/tutorial/java/IandI/interfaceDef.html
+        let is_interface = match node.kind_id().into() {
+            Program | ClassBody | EnumBodyDeclarations => false,
+            InterfaceBody => true,
+            _ => return,
+        };
+
+        for item in node.children() {
+            if matches!(
+                item.kind_id().into(),
+                MethodDeclaration
+                    | ConstructorDeclaration
+                    | ClassDeclaration
+                    | InterfaceDeclaration
+                    | EnumDeclaration
+                    | RecordDeclaration
+            ) && (is_interface || is_java_public(&item))
+            {
+                stats.add(has_doc_comment::<Self>(&item, code, &[]));
+            }
+        }
+    }
+}
+
+impl Docs for CsharpCode {
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats) {
+        use Csharp::*;
+
+        stats.is_enabled = true;
+
+        // All the members of an interface are implicitly public
+        let is_interface = match node.kind_id().into() {
+            CompilationUnit | FileScopedNamespaceDeclaration => false,
+            DeclarationList => node
+                .parent()
+                .is_some_and(|p| p.kind_id() == InterfaceDeclaration),
+            _ => return,
+        };
+
+        for item in node.children() {
+            let is_public = || {
+                item.children().any(|modifier| {
+                    modifier.kind_id() == Modifier
+                        && modifier.first_child(|id| id == Public).is_some()
+                })
+            };
+            if matches!(
+                item.kind_id().into(),
+                MethodDeclaration
+                    | ConstructorDeclaration
+                    | ClassDeclaration
+                    | InterfaceDeclaration
+                    | StructDeclaration
+                    | EnumDeclaration
+                    | RecordDeclaration
+            ) && (is_interface || is_public())
+            {
+                stats.add(has_doc_comment::<Self>(&item, code, &[]));
+            }
+        }
+    }
+}
+
+impl Docs for KotlinCode {
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats) {
+        use Kotlin::*;
+
+        stats.is_enabled = true;
+
+        if !matches!(node.kind_id().into(), SourceFile | ClassBody) {
+            return;
+        }
+
+        for item in node.children() {
+            if !matches!(
+                item.kind_id().into(),
+                ClassDeclaration | ObjectDeclaration | FunctionDeclaration
+            ) {
+                continue;
+            }
+            // Declarations are public by default
+            let is_public = item
+                .first_child(|id| id == Modifiers)
+                .is_none_or(|modifiers| {
+                    !modifiers.children().any(|modifier| {
+                        modifier.kind_id() == VisibilityModifier
+                            && modifier
+                                .first_child(|id| {
+                                    matches!(id.into(), Private | Internal | Protected)
+                                })
+                                .is_some()
+                    })
+                });
+            if is_public {
+                stats.add(has_doc_comment::<Self>(&item, code, &[]));
+            }
+        }
+    }
+}
+
+macro_rules! impl_docs_for_exports {
+    ($code:ident, $lang:ident) => {
+        impl Docs for $code {
+            fn compute(node: &Node, code: &[u8], stats: &mut Stats) {
+                use $lang::*;
+
+                stats.is_enabled = true;
+
+                if node.kind_id() != Program {
+                    return;
+                }
+
+                // Only the exported declarations are part of the public API
+                for item in node.children() {
+                    if item.kind_id() == ExportStatement
+                        && (item.child_by_field_name("declaration").is_some()
+                            || item
+                                .first_child(|id| {
+                                    matches!(
+                                        id.into(),
+                                        FunctionDeclaration
+                                            | ClassDeclaration
+                                            | AbstractClassDeclaration
+                                    )
+                                })
+                                .is_some())
+                    {
+                        stats.add(has_doc_comment::<Self>(&item, code, &[]));
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl_docs_for_exports!(TypescriptCode, Typescript);
+impl_docs_for_exports!(TsxCode, Tsx);
+
+implement_metric_trait!(
+    Docs,
+    MozjsCode,
+    JavascriptCode,
+    CppCode,
+    PreprocCode,
+    CcommentCode,
+    PerlCode,
+    PhpCode,
+    HtmlCode,
+    CssCode
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::tools::check_metrics;
+
+    use super::*;
+
+    #[test]
+    fn rust_docs() {
+        check_metrics::<RustParser>(
+            "/// Documented
+            pub fn a() {}
+            pub fn b() {}
+            fn c() {}
+            #[doc = \"Documented\"]
+            pub struct S;
+            /// Documented
+            #[derive(Debug)]
+            pub enum E {}
+            pub(crate) fn d() {}
+            impl S {
+                /// Documented
+                pub fn new() -> Self { S }
+                pub fn e(&self) {}
+                fn f(&self) {}
+            }
+            impl Clone for S {
+                fn clone(&self) -> Self { S }
+            }",
+            "foo.rs",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.docs,
+                    @r###"
+                {
+                  "public": 6.0,
+                  "documented": 4.0,
+                  "coverage": 0.6666666666666666
+                }
+                "###
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn python_docs() {
+        check_metrics::<PythonParser>(
+            "def a():
+                '''Documented'''
+                pass
+
+            def _b():
+                pass
+
+            @decorator
+            class C:
+                '''Documented'''
+
+                def m(self):
+                    pass
+
+                def _n(self):
+                    pass",
+            "foo.py",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.docs,
+                    @r###"
+                {
+                  "public": 3.0,
+                  "documented": 2.0,
+                  "coverage": 0.6666666666666666
+                }
+                "###
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn java_docs() {
+        check_metrics::<JavaParser>(
+            "/** Documented */
+            public class X {
+                /** Documented */
+                public X() {}
+                // Not a Javadoc
+                public void a() {}
+                /** Documented */
+                @Override
+                public String toString() { return \"\"; }
+                private void b() {}
+            }
+            interface I {
+                void c();
+            }",
+            "foo.java",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.docs,
+                    @r###"
+                {
+                  "public": 5.0,
+                  "documented": 3.0,
+                  "coverage": 0.6
+                }
+                "###
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn csharp_docs() {
+        check_metrics::<CsharpParser>(
+            "namespace N {
+                /// <summary>Documented</summary>
+                public class A {
+                    /// <summary>Documented</summary>
+                    public void F() {}
+                    public void G() {}
+                    private void H() {}
+                }
+            }",
+            "foo.cs",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.docs,
+                    @r###"
+                {
+                  "public": 3.0,
+                  "documented": 2.0,
+                  "coverage": 0.6666666666666666
+                }
+                "###
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn kotlin_docs() {
+        check_metrics::<KotlinParser>(
+            "/** Documented */
+            class A {
+                /** Documented */
+                fun a() {}
+                fun b() {}
+                private fun c() {}
+                // Not a KDoc
+                protected fun d() {}
+            }
+            /** Documented */
+            internal fun e() {}
+            fun f() {}
+            object O",
+            "foo.kt",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.docs,
+                    @r###"
+                {
+                  "public": 5.0,
+                  "documented": 2.0,
+                  "coverage": 0.4
+                }
+                "###
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn typescript_docs() {
+        check_metrics::<TypescriptParser>(
+            "/** Documented */
+            export function a() {}
+            export class B {}
+            function c() {}
+            /** Documented */
+            export default function d() {}
+            export { c };",
+            "foo.ts",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.docs,
+                    @r###"
+                {
+                  "public": 3.0,
+                  "documented": 2.0,
+                  "coverage": 0.6666666666666666
+                }
+                "###
+                );
+            },
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::checker::Checker;
+use crate::langs::*;
+use crate::macros::implement_metric_trait;
+use crate::node::Node;
+use crate::*;
+
+/// The `Ifdef` metric.
+///
+/// This metric measures the preprocessor complexity of a space,
+/// counting its conditional compilation blocks and the distinct
+/// configuration macros referenced by their conditions.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    blocks: usize,
+    blocks_sum: usize,
+    macros: HashSet<String>,
+    macros_sum: HashSet<String>,
+    is_enabled: bool,
+}
+
+impl Serialize for Stats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("ifdef", 2)?;
+        st.serialize_field("blocks", &self.blocks_sum())?;
+        st.serialize_field("macros", &self.macros_sum())?;
+        st.end()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "blocks: {}, macros: {}",
+            self.blocks_sum(),
+            self.macros_sum()
+        )
+    }
+}
+
+impl Stats {
+    /// Merges a second `Ifdef` metric into the first one
+    pub fn merge(&mut self, other: &Stats) {
+        self.blocks_sum += other.blocks_sum;
+        self.macros_sum.extend(other.macros_sum.iter().cloned());
+    }
+
+    /// Returns the number of conditional compilation blocks in a space.
+    #[inline(always)]
+    pub fn blocks(&self) -> f64 {
+        self.blocks as f64
+    }
+
+    /// Returns the number of distinct macros referenced
+    /// by the conditions of a space.
+    #[inline(always)]
+    pub fn macros(&self) -> f64 {
+        self.macros.len() as f64
+    }
+
+    /// Returns the number of conditional compilation blocks sum in a space.
+    #[inline(always)]
+    pub fn blocks_sum(&self) -> f64 {
+        self.blocks_sum as f64
+    }
+
+    /// Returns the number of distinct macros referenced by the conditions
+    /// of a space and of its subspaces.
+    #[inline(always)]
+    pub fn macros_sum(&self) -> f64 {
+        self.macros_sum.len() as f64
+    }
+
+    #[inline(always)]
+    pub(crate) fn compute_sum(&mut self) {
+        self.blocks_sum += self.blocks;
+        self.macros_sum.extend(self.macros.iter().cloned());
+    }
+
+    // Checks if the `Ifdef` metric is disabled
+    #[inline(always)]
+    pub(crate) fn is_disabled(&self) -> bool {
+        !self.is_enabled
+    }
+}
+
+pub trait Ifdef
+where
+    Self: Checker,
+{
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats);
+}
+
+impl Ifdef for CppCode {
+    fn compute(node: &Node, code: &[u8], stats: &mut Stats) {
+        use Cpp::*;
+
+        stats.is_enabled = true;
+
+        // `#ifndef` directives are `preproc_ifdef` nodes too
+        let condition = match node.kind_id().into() {
+            PreprocIf | PreprocIf2 | PreprocIf3 | PreprocIf4 => {
+                stats.blocks += 1;
+                node.child_by_field_name("condition")
+            }
+            PreprocIfdef | PreprocIfdef2 | PreprocIfdef3 | PreprocIfdef4 => {
+                stats.blocks += 1;
+                node.child_by_field_name("name")
+            }
+            PreprocElif | PreprocElif2 | PreprocElif3 | PreprocElif4 => {
+                node.child_by_field_name("condition")
+            }
+            PreprocElifdef | PreprocElifdef2 | PreprocElifdef3 | PreprocElifdef4 => {
+                node.child_by_field_name("name")
+            }
+            _ => return,
+        };
+
+        if let Some(condition) = condition {
+            condition.act_on_node(&mut |n| {
+                if n.kind_id() == Identifier
+                    && let Some(name) = n.utf8_text(code)
+                    // Skip the feature checking macros such as `__has_include`
+                    && !name.starts_with("__has_")
+                {
+                    stats.macros.insert(name.to_string());
+                }
+            });
+        }
+    }
+}
+
+implement_metric_trait!(
+    Ifdef,
+    MozjsCode,
+    JavascriptCode,
+    JavaCode,
+    KotlinCode,
+    RustCode,
+    PythonCode,
+    TsxCode,
+    TypescriptCode,
+    PreprocCode,
+    CcommentCode,
+    PerlCode,
+    PhpCode,
+    HtmlCode,
+    CssCode,
+    CsharpCode
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::tools::check_metrics;
+
+    use super::*;
+
+    #[test]
+    fn cpp_ifdef() {
+        check_metrics::<CppParser>(
+            "#ifdef DEBUG
+            #define LOG 1
+            #endif
+
+            void f() {
+            #if defined(LINUX) && VERSION > 2
+                a();
+            #elif WINDOWS
+                b();
+            #else
+                c();
+            #endif
+            }
+
+            #ifndef DEBUG
+            void g() {}
+            #endif",
+            "foo.cpp",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.ifdef,
+                    @r###"
+                {
+                  "blocks": 3.0,
+                  "macros": 4.0
+                }
+                "###
+                );
+            },
+        );
+    }
+}
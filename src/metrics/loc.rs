@@ -134,6 +134,8 @@ impl Ploc {
 pub struct Cloc {
     only_comment_lines: usize,
     code_comment_lines: usize,
+    // Only computed on demand, since it parses the comments
+    commented_code_lines: Option<usize>,
     comment_line_end: Option<usize>,
     cloc_min: usize,
     cloc_max: usize,
@@ -144,6 +146,7 @@ impl Default for Cloc {
         Self {
             only_comment_lines: 0,
             code_comment_lines: 0,
+            commented_code_lines: None,
             comment_line_end: Option::default(),
             cloc_min: usize::MAX,
             cloc_max: 0,
@@ -159,6 +162,12 @@ impl Cloc {
         (self.only_comment_lines + self.code_comment_lines) as f64
     }
 
+    #[inline(always)]
+    pub fn cloc_code(&self) -> f64 {
+        // Comments containing code are counted in cloc as well
+        self.commented_code_lines.unwrap_or_default() as f64
+    }
+
     /// The `Ploc` metric minimum value.
     #[inline(always)]
     pub fn cloc_min(&self) -> f64 {
@@ -176,6 +185,9 @@ impl Cloc {
         // Merge cloc lines
         self.only_comment_lines += other.only_comment_lines;
         self.code_comment_lines += other.code_comment_lines;
+        if let Some(lines) = other.commented_code_lines {
+            *self.commented_code_lines.get_or_insert(0) += lines;
+        }
 
         self.cloc_min = self.cloc_min.min(other.cloc() as usize);
         self.cloc_max = self.cloc_max.max(other.cloc() as usize);
@@ -276,11 +288,15 @@ impl Serialize for Stats {
     where
         S: Serializer,
     {
-        let mut st = serializer.serialize_struct("loc", 20)?;
+        let has_cloc_code = self.has_cloc_code();
+        let mut st = serializer.serialize_struct("loc", 20 + has_cloc_code as usize)?;
         st.serialize_field("sloc", &self.sloc())?;
         st.serialize_field("ploc", &self.ploc())?;
         st.serialize_field("lloc", &self.lloc())?;
         st.serialize_field("cloc", &self.cloc())?;
+        if has_cloc_code {
+            st.serialize_field("cloc_code", &self.cloc_code())?;
+        }
         st.serialize_field("blank", &self.blank())?;
         st.serialize_field("sloc_average", &self.sloc_average())?;
         st.serialize_field("ploc_average", &self.ploc_average())?;
@@ -305,11 +321,18 @@ impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "sloc: {}, ploc: {}, lloc: {}, cloc: {}, blank: {}, sloc_average: {}, ploc_average: {}, lloc_average: {}, cloc_average: {}, blank_average: {}, sloc_min: {}, sloc_max: {}, cloc_min: {}, cloc_max: {}, ploc_min: {}, ploc_max: {}, lloc_min: {}, lloc_max: {}, blank_min: {}, blank_max: {}",
+            "sloc: {}, ploc: {}, lloc: {}, cloc: {}, ",
             self.sloc(),
             self.ploc(),
             self.lloc(),
             self.cloc(),
+        )?;
+        if self.has_cloc_code() {
+            write!(f, "cloc_code: {}, ", self.cloc_code())?;
+        }
+        write!(
+            f,
+            "blank: {}, sloc_average: {}, ploc_average: {}, lloc_average: {}, cloc_average: {}, blank_average: {}, sloc_min: {}, sloc_max: {}, cloc_min: {}, cloc_max: {}, ploc_min: {}, ploc_max: {}, lloc_min: {}, lloc_max: {}, blank_min: {}, blank_max: {}",
             self.blank(),
             self.sloc_average(),
             self.ploc_average(),
@@ -379,6 +402,15 @@ impl Stats {
         self.cloc.cloc()
     }
 
+    /// The `ClocCode` metric.
+    ///
+    /// Counts the number of comment lines containing
+    /// commented-out code in a scope
+    #[inline(always)]
+    pub fn cloc_code(&self) -> f64 {
+        self.cloc.cloc_code()
+    }
+
     /// The `Blank` metric.
     ///
     /// Counts the number of blank lines in a scope
@@ -499,6 +531,34 @@ impl Stats {
             self.blank_max = self.blank_max.max(self.blank() as usize);
         }
     }
+
+    // Counts the lines of a comment containing commented-out code
+    #[inline(always)]
+    pub(crate) fn add_commented_code(&mut self, node: &Node) {
+        *self.cloc.commented_code_lines.get_or_insert(0) += node.end_row() - node.start_row() + 1;
+    }
+
+    // Enables the `ClocCode` metric, which is only computed on demand
+    #[inline(always)]
+    pub(crate) fn enable_cloc_code(&mut self) {
+        self.cloc.commented_code_lines.get_or_insert(0);
+    }
+
+    // Counts the instruction lines between two lines, both included
+    #[inline(always)]
+    pub(crate) fn ploc_between(&self, start_line: usize, end_line: usize) -> f64 {
+        self.ploc
+            .lines
+            .iter()
+            .filter(|&&row| (start_line..=end_line).contains(&(row + 1)))
+            .count() as f64
+    }
+
+    // Checks if the `ClocCode` metric has been computed
+    #[inline(always)]
+    pub(crate) fn has_cloc_code(&self) -> bool {
+        self.cloc.commented_code_lines.is_some()
+    }
 }
 
 pub trait Loc
@@ -941,7 +1001,8 @@ implement_metric_trait!(Loc, PreprocCode, CcommentCode, KotlinCode, PerlCode);
 
 #[cfg(test)]
 mod tests {
-    use crate::tools::check_metrics;
+    use crate::MetricsOpts;
+    use crate::tools::{check_metrics, check_metrics_with_opts};
 
     use super::*;
 
@@ -1754,6 +1815,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rust_cloc_code() {
+        check_metrics_with_opts::<RustParser>(
+            "// The answer
+            fn foo() -> i32 {
+                // let x = bar(
+                //     1,
+                // );
+                /* baz(); */
+                42
+            }",
+            "foo.rs",
+            &MetricsOpts::default().commented_code(true),
+            |metric| {
+                // Spaces: 2
+                insta::assert_json_snapshot!(
+                    metric.loc,
+                    @r###"
+                    {
+                      "sloc": 8.0,
+                      "ploc": 3.0,
+                      "lloc": 0.0,
+                      "cloc": 5.0,
+                      "cloc_code": 4.0,
+                      "blank": 0.0,
+                      "sloc_average": 4.0,
+                      "ploc_average": 1.5,
+                      "lloc_average": 0.0,
+                      "cloc_average": 2.5,
+                      "blank_average": 0.0,
+                      "sloc_min": 7.0,
+                      "sloc_max": 7.0,
+                      "cloc_min": 4.0,
+                      "cloc_max": 4.0,
+                      "ploc_min": 3.0,
+                      "ploc_max": 3.0,
+                      "lloc_min": 0.0,
+                      "lloc_max": 0.0,
+                      "blank_min": 0.0,
+                      "blank_max": 0.0
+                    }"###
+                );
+            },
+        );
+    }
+
     #[test]
     fn python_lloc() {
         check_metrics::<PythonParser>(
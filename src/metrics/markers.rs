@@ -0,0 +1,277 @@
+use regex::bytes::{Captures, Regex};
+use serde::Serialize;
+use serde::ser::{SerializeMap, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::checker::Checker;
+use crate::node::Node;
+
+/// The markers counted by default.
+pub const DEFAULT_MARKERS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+
+static DEFAULT_REGEX: OnceLock<Regex> = OnceLock::new();
+static ISSUE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// The `Markers` metric.
+///
+/// This metric counts the tech-debt markers, such as `TODO` and `FIXME`,
+/// contained in the comments of a space.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    markers: BTreeMap<String, usize>,
+}
+
+impl Serialize for Stats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_map(Some(self.markers.len() + 1))?;
+        for (marker, count) in &self.markers {
+            st.serialize_entry(marker, &(*count as f64))?;
+        }
+        st.serialize_entry("total", &self.total())?;
+        st.end()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (marker, count) in &self.markers {
+            write!(f, "{marker}: {count}, ")?;
+        }
+        write!(f, "total: {}", self.total())
+    }
+}
+
+impl Stats {
+    /// Merges a second `Markers` metric into the first one
+    pub fn merge(&mut self, other: &Stats) {
+        for (marker, count) in &other.markers {
+            *self.markers.entry(marker.clone()).or_default() += count;
+        }
+    }
+
+    /// Returns the number of occurrences of a marker
+    pub fn marker(&self, marker: &str) -> f64 {
+        self.markers.get(marker).copied().unwrap_or_default() as f64
+    }
+
+    /// Returns the number of occurrences of each marker
+    pub fn markers(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.markers
+            .iter()
+            .map(|(marker, count)| (marker.as_str(), *count as f64))
+    }
+
+    /// Returns the total number of markers
+    pub fn total(&self) -> f64 {
+        self.markers.values().sum::<usize>() as f64
+    }
+
+    /// Checks whether there are no markers
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+}
+
+/// A marker found in the text of a comment.
+#[derive(Debug)]
+pub(crate) struct MarkerMatch {
+    pub(crate) marker: String,
+    pub(crate) author: Option<String>,
+    pub(crate) issue: Option<String>,
+    pub(crate) text: String,
+    /// Byte offset of the marker in the comment
+    pub(crate) offset: usize,
+}
+
+/// Builds the regex matching the given markers, followed by an optional
+/// author or issue in parentheses, as in `TODO(bob): fix me`.
+///
+/// A marker starting or ending with a word character, as `TODO`,
+/// does not match inside a word, as `TODOS`.
+pub(crate) fn marker_regex(markers: &[String]) -> Regex {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let markers: Vec<_> = markers
+        .iter()
+        .map(|m| {
+            let start = if is_word(m.chars().next()) { r"\b" } else { "" };
+            let end = if is_word(m.chars().last()) { r"\b" } else { "" };
+            format!("{start}{}{end}", regex::escape(m))
+        })
+        .collect();
+    Regex::new(&format!(
+        r"({})(?:\(([^)\n]*)\))?:?[ \t]*([^\n]*)",
+        markers.join("|")
+    ))
+    .unwrap()
+}
+
+pub(crate) fn default_marker_regex() -> &'static Regex {
+    DEFAULT_REGEX.get_or_init(|| {
+        marker_regex(
+            &DEFAULT_MARKERS
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>(),
+        )
+    })
+}
+
+fn to_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn get_marker(caps: Captures) -> MarkerMatch {
+    let issue_regex =
+        ISSUE_REGEX.get_or_init(|| Regex::new(r"#\d+|\b[A-Z][A-Z0-9]+-\d+\b").unwrap());
+
+    let mut author = caps
+        .get(2)
+        .map(|a| a.as_bytes().trim_ascii())
+        .filter(|a| !a.is_empty());
+    let text = caps[3].trim_ascii();
+    // The end of a block comment is not part of the text
+    let text = text.strip_suffix(b"*/").unwrap_or(text).trim_ascii_end();
+
+    // An issue can be given in place of the author, as in `TODO(#123)`
+    let issue = match author.and_then(|a| issue_regex.find(a)) {
+        Some(issue) if issue.len() == author.unwrap().len() => {
+            author = None;
+            Some(issue)
+        }
+        _ => issue_regex.find(text),
+    };
+
+    MarkerMatch {
+        marker: to_string(&caps[1]),
+        author: author.map(to_string),
+        issue: issue.map(|i| to_string(i.as_bytes())),
+        text: to_string(text),
+        offset: caps.get(0).unwrap().start(),
+    }
+}
+
+/// Finds all the markers contained in the text of a comment.
+pub(crate) fn find_markers<'a>(
+    text: &'a [u8],
+    regex: &'a Regex,
+) -> impl Iterator<Item = MarkerMatch> + 'a {
+    regex.captures_iter(text).map(get_marker)
+}
+
+pub(crate) fn compute<T: Checker>(node: &Node, code: &[u8], regex: &Regex, stats: &mut Stats) {
+    if T::is_comment(node) {
+        let text = &code[node.start_byte()..node.end_byte()];
+        for caps in regex.captures_iter(text) {
+            *stats.markers.entry(to_string(&caps[1])).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tools::{check_metrics, check_metrics_with_opts};
+    use crate::{MetricsOpts, PythonParser};
+
+    use super::*;
+
+    #[test]
+    fn marker_author_and_issue() {
+        let regex = default_marker_regex();
+
+        let markers: Vec<_> = find_markers(
+            b"/* TODO(bob): #123 remove this\n * FIXME handle errors */",
+            regex,
+        )
+        .collect();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].marker, "TODO");
+        assert_eq!(markers[0].author.as_deref(), Some("bob"));
+        assert_eq!(markers[0].issue.as_deref(), Some("#123"));
+        assert_eq!(markers[0].text, "#123 remove this");
+        assert_eq!(markers[0].offset, 3);
+        assert_eq!(markers[1].marker, "FIXME");
+        assert_eq!(markers[1].author, None);
+        assert_eq!(markers[1].text, "handle errors");
+
+        let markers: Vec<_> = find_markers(b"# HACK(JIRA-42): workaround", regex).collect();
+        assert_eq!(markers[0].author, None);
+        assert_eq!(markers[0].issue.as_deref(), Some("JIRA-42"));
+
+        // Markers are whole words
+        assert_eq!(find_markers(b"// TODOS and XXXL", regex).count(), 0);
+
+        let regex = marker_regex(&["NOTE".to_owned()]);
+        assert_eq!(find_markers(b"// NOTE: a note, TODO", &regex).count(), 1);
+    }
+
+    #[test]
+    fn markers_with_symbols() {
+        let regex = marker_regex(&["@todo".to_owned(), "TODO:".to_owned(), "!!!".to_owned()]);
+
+        let markers: Vec<_> = find_markers(b"/** @todo check it */", &regex).collect();
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].marker, "@todo");
+        assert_eq!(markers[0].text, "check it");
+
+        let markers: Vec<_> = find_markers(b"/* TODO: first\n * !!! second */", &regex).collect();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].marker, "TODO:");
+        assert_eq!(markers[1].marker, "!!!");
+        assert_eq!(markers[1].text, "second");
+
+        // The word characters of a marker are still whole words
+        assert_eq!(find_markers(b"// @todos and MYTODO: it", &regex).count(), 0);
+    }
+
+    #[test]
+    fn python_markers() {
+        check_metrics::<PythonParser>(
+            "# TODO: remove
+            def f():
+                # FIXME(bob) this is broken
+                # TODO: this too
+                return 1",
+            "foo.py",
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.markers,
+                    @r###"
+                {
+                  "FIXME": 1.0,
+                  "TODO": 2.0,
+                  "total": 3.0
+                }
+                "###
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn python_custom_markers() {
+        check_metrics_with_opts::<PythonParser>(
+            "# TODO: remove
+            # NOTE: a note
+            # SAFETY: it is fine",
+            "foo.py",
+            &MetricsOpts::default().markers(&["NOTE".to_owned(), "SAFETY".to_owned()]),
+            |metric| {
+                insta::assert_json_snapshot!(
+                    metric.markers,
+                    @r###"
+                {
+                  "NOTE": 1.0,
+                  "SAFETY": 1.0,
+                  "total": 2.0
+                }
+                "###
+                );
+            },
+        );
+    }
+}
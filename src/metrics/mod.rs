@@ -1,12 +1,17 @@
 pub mod abc;
 pub mod cognitive;
+pub mod coverage;
 pub mod cyclomatic;
+pub mod docs;
 pub mod exit;
 pub mod halstead;
+pub mod ifdef;
 pub mod loc;
+pub mod markers;
 pub mod mi;
 pub mod nargs;
 pub mod nom;
 pub mod npa;
 pub mod npm;
+pub mod test_code;
 pub mod wmc;
@@ -219,6 +219,18 @@ impl Npm for CsharpCode {
     fn compute(_node: &Node, _stats: &mut Stats) {}
 }
 
+// Checks whether a Java declaration has the `public` modifier.
+pub(crate) fn is_java_public(node: &Node) -> bool {
+    // The first child node contains the list of declaration modifiers
+    // There are several modifiers that may be part of a method declaration
+    // Source: https://docs.oracle.com/javase/tutorial/reflect/member/methodModifiers.html
+    node.child(0).is_some_and(|modifiers| {
+        // Looks for the `public` keyword in the list of modifiers
+        matches!(modifiers.kind_id().into(), Java::Modifiers)
+            && modifiers.first_child(|id| id == Java::Public).is_some()
+    })
+}
+
 impl Npm for JavaCode {
     fn compute(node: &Node, stats: &mut Stats) {
         use Java::*;
@@ -234,16 +246,8 @@ impl Npm for JavaCode {
                     .children()
                     .filter(|node| Self::is_func(node))
                     .map(|method| {
-                        // The first child node contains the list of method modifiers
-                        // There are several modifiers that may be part of a method declaration
-                        // Source: https://docs.oracle.com/javase/tutorial/reflect/member/methodModifiers.html
-                        if let Some(modifiers) = method.child(0) {
-                            // Looks for the `public` keyword in the list of method modifiers
-                            if matches!(modifiers.kind_id().into(), Modifiers)
-                                && modifiers.first_child(|id| id == Public).is_some()
-                            {
-                                stats.class_npm += 1;
-                            }
+                        if is_java_public(&method) {
+                            stats.class_npm += 1;
                         }
                     })
                     .count();
@@ -0,0 +1,566 @@
+use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
+use std::fmt;
+use std::path::Path;
+
+use crate::langs::LANG;
+use crate::node::Node;
+use crate::spaces::FuncSpace;
+
+/// The size of a part of the code of a unit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Size {
+    /// The number of lines
+    pub sloc: f64,
+    /// The number of instruction lines
+    pub ploc: f64,
+    /// The number of statements
+    pub lloc: f64,
+    /// The number of functions and closures
+    pub functions: f64,
+    /// The sum of the cyclomatic complexity
+    pub cyclomatic: f64,
+    /// The sum of the cognitive complexity
+    pub cognitive: f64,
+}
+
+impl Size {
+    fn new(space: &FuncSpace) -> Self {
+        let metrics = &space.metrics;
+        Self {
+            sloc: metrics.loc.sloc(),
+            ploc: metrics.loc.ploc(),
+            lloc: metrics.loc.lloc(),
+            functions: metrics.nom.total(),
+            cyclomatic: metrics.cyclomatic.cyclomatic_sum(),
+            cognitive: metrics.cognitive.cognitive_sum(),
+        }
+    }
+
+    fn add(&mut self, other: &Size) {
+        self.sloc += other.sloc;
+        self.ploc += other.ploc;
+        self.lloc += other.lloc;
+        self.functions += other.functions;
+        self.cyclomatic += other.cyclomatic;
+        self.cognitive += other.cognitive;
+    }
+
+    fn sub(&self, other: &Size) -> Self {
+        Self {
+            sloc: self.sloc - other.sloc,
+            ploc: self.ploc - other.ploc,
+            lloc: self.lloc - other.lloc,
+            functions: self.functions - other.functions,
+            cyclomatic: self.cyclomatic - other.cyclomatic,
+            cognitive: self.cognitive - other.cognitive,
+        }
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "sloc: {}, ploc: {}, lloc: {}, functions: {}, cyclomatic: {}, cognitive: {}",
+            self.sloc, self.ploc, self.lloc, self.functions, self.cyclomatic, self.cognitive
+        )
+    }
+}
+
+/// The `TestCode` metric.
+///
+/// This metric splits the code of a unit between its test code,
+/// that is the spaces flagged as tests, and its production code.
+///
+/// It is only computed for the units containing some test code.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    all: Size,
+    tests: Size,
+    is_enabled: bool,
+}
+
+impl Serialize for Stats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut st = serializer.serialize_struct("test_code", 6)?;
+        st.serialize_field("all", &self.all())?;
+        st.serialize_field("code", &self.code())?;
+        st.serialize_field("tests", &self.tests())?;
+        st.serialize_field("sloc_ratio", &self.sloc_ratio())?;
+        st.serialize_field("lloc_ratio", &self.lloc_ratio())?;
+        st.serialize_field("functions_ratio", &self.functions_ratio())?;
+        st.end()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "all: [{}], code: [{}], tests: [{}], sloc_ratio: {}, lloc_ratio: {}, functions_ratio: {}",
+            self.all(),
+            self.code(),
+            self.tests(),
+            self.sloc_ratio(),
+            self.lloc_ratio(),
+            self.functions_ratio()
+        )
+    }
+}
+
+impl Stats {
+    /// Returns the size of the whole unit, tests included.
+    #[inline(always)]
+    pub fn all(&self) -> Size {
+        self.all
+    }
+
+    /// Returns the size of the production code of the unit, tests excluded.
+    #[inline(always)]
+    pub fn code(&self) -> Size {
+        self.all.sub(&self.tests)
+    }
+
+    /// Returns the size of the test code of the unit.
+    #[inline(always)]
+    pub fn tests(&self) -> Size {
+        self.tests
+    }
+
+    /// Returns the ratio between the lines of test code and of production code.
+    ///
+    /// If there is no production code in a unit, its value is `INFINITY`.
+    #[inline(always)]
+    pub fn sloc_ratio(&self) -> f64 {
+        self.tests.sloc / self.code().sloc
+    }
+
+    /// Returns the ratio between the statements of test code and of production code.
+    #[inline(always)]
+    pub fn lloc_ratio(&self) -> f64 {
+        self.tests.lloc / self.code().lloc
+    }
+
+    /// Returns the ratio between the test functions and the production functions.
+    #[inline(always)]
+    pub fn functions_ratio(&self) -> f64 {
+        self.tests.functions / self.code().functions
+    }
+
+    /// Computes the metric of a unit from the test spaces it contains
+    /// and from the lines of its test items which are not spaces,
+    /// as the `Rust` test modules.
+    pub(crate) fn compute(&mut self, unit: &FuncSpace, test_items: &[(usize, usize)]) {
+        fn add_tests(
+            space: &FuncSpace,
+            test_items: &[(usize, usize)],
+            tests: &mut Size,
+            found: &mut bool,
+        ) {
+            for space in &space.spaces {
+                if test_items
+                    .iter()
+                    .any(|&(start, end)| start <= space.start_line && space.end_line <= end)
+                {
+                    // Already counted with its test item
+                    continue;
+                }
+                if space.is_test {
+                    // The metrics of a space include the ones of its subspaces
+                    tests.add(&Size::new(space));
+                    *found = true;
+                } else {
+                    add_tests(space, test_items, tests, found);
+                }
+            }
+        }
+
+        fn add_item_spaces(space: &FuncSpace, (start, end): (usize, usize), tests: &mut Size) {
+            for space in &space.spaces {
+                if start <= space.start_line && space.end_line <= end {
+                    let size = Size::new(space);
+                    tests.lloc += size.lloc;
+                    tests.functions += size.functions;
+                    tests.cyclomatic += size.cyclomatic;
+                    tests.cognitive += size.cognitive;
+                } else {
+                    add_item_spaces(space, (start, end), tests);
+                }
+            }
+        }
+
+        self.all = Size::new(unit);
+        if unit.is_test {
+            self.tests = self.all;
+            self.is_enabled = true;
+            return;
+        }
+        for &(start, end) in test_items {
+            // The lines of an item are test code, the statements and
+            // the complexity are the ones of the spaces it contains
+            self.tests.sloc += (end - start + 1) as f64;
+            self.tests.ploc += unit.metrics.loc.ploc_between(start, end);
+            add_item_spaces(unit, (start, end), &mut self.tests);
+            self.is_enabled = true;
+        }
+        add_tests(unit, test_items, &mut self.tests, &mut self.is_enabled);
+    }
+
+    // Checks if the `TestCode` metric is disabled
+    #[inline(always)]
+    pub(crate) fn is_disabled(&self) -> bool {
+        !self.is_enabled
+    }
+}
+
+/// Returns `true` if a path is the one of a test file, that is a file
+/// in a `tests`, `test` or `__tests__` directory, or a file named
+/// as a test, such as `test_foo.py`, `foo_test.py`, `foo.test.ts`,
+/// `foo.spec.js` or `FooTest.java`.
+pub fn is_test_path(path: &Path) -> bool {
+    let in_tests_dir = path.parent().is_some_and(|dir| {
+        dir.components().any(|component| {
+            matches!(
+                component.as_os_str().to_str(),
+                Some("tests" | "test" | "__tests__")
+            )
+        })
+    });
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return in_tests_dir;
+    };
+    let stem = name.split('.').next().unwrap_or_default();
+    in_tests_dir
+        || (name.ends_with(".py") && (stem.starts_with("test_") || stem.ends_with("_test")))
+        || name.contains(".test.")
+        || name.contains(".spec.")
+        || (name.ends_with(".java") && (stem.ends_with("Test") || stem.ends_with("Tests")))
+}
+
+// Returns the text of a child of a node, as a field.
+fn field_text<'a>(node: &'a Node<'a>, field: &str, code: &'a [u8]) -> Option<&'a str> {
+    node.child_by_field_name(field)?.utf8_text(code)
+}
+
+// Returns `true` if a `Rust` attribute marks a test function or test module,
+// as `#[test]`, `#[tokio::test]` or `#[cfg(test)]`.
+fn is_rust_test_attribute(text: &str) -> bool {
+    let text: String = text.split_whitespace().collect();
+    let Some(attribute) = text
+        .strip_prefix("#[")
+        .and_then(|text| text.strip_suffix(']'))
+    else {
+        return false;
+    };
+    let path = attribute.split('(').next().unwrap_or_default();
+    attribute == "cfg(test)" || path.rsplit("::").next() == Some("test")
+}
+
+// Returns `true` if a `Rust` item, or one of its ancestors, has a test attribute.
+fn is_rust_test(node: &Node, code: &[u8]) -> bool {
+    let mut item = Some(*node);
+    while let Some(node) = item {
+        let mut sibling = node.previous_sibling();
+        while let Some(attribute) = sibling {
+            match attribute.kind() {
+                "attribute_item" => {
+                    if attribute
+                        .utf8_text(code)
+                        .is_some_and(is_rust_test_attribute)
+                    {
+                        return true;
+                    }
+                }
+                "line_comment" | "block_comment" => {}
+                _ => break,
+            }
+            sibling = attribute.previous_sibling();
+        }
+        item = node.parent();
+    }
+    false
+}
+
+// Returns `true` if a `Python` function is a test, as `test_foo`,
+// or if a class is a test case, as `TestFoo` or `FooTest(TestCase)`.
+fn is_python_test(node: &Node, code: &[u8]) -> bool {
+    let name = field_text(node, "name", code).unwrap_or_default();
+    match node.kind() {
+        "function_definition" => name == "test" || name.starts_with("test_"),
+        "class_definition" => {
+            name.starts_with("Test")
+                || field_text(node, "superclasses", code)
+                    .is_some_and(|superclasses| superclasses.contains("TestCase"))
+        }
+        _ => false,
+    }
+}
+
+// Returns `true` if a `JavaScript` function is the callback of a test block,
+// as `describe("...", () => {})` or `it.each(...)("...", function () {})`.
+fn is_javascript_test(node: &Node, code: &[u8]) -> bool {
+    const BLOCKS: &[&str] = &[
+        "describe",
+        "context",
+        "suite",
+        "it",
+        "test",
+        "beforeAll",
+        "beforeEach",
+        "afterAll",
+        "afterEach",
+    ];
+
+    let Some(call) = node
+        .parent()
+        .filter(|arguments| arguments.kind() == "arguments")
+        .and_then(|arguments| arguments.parent())
+        .filter(|call| call.kind() == "call_expression")
+    else {
+        return false;
+    };
+    field_text(&call, "function", code).is_some_and(|function| {
+        let name = function.split(['.', '(', '<']).next().unwrap_or_default();
+        BLOCKS.contains(&name.trim())
+    })
+}
+
+// Returns `true` if a `Java` method has a `JUnit` test annotation,
+// or if a class is named as a test.
+fn is_java_test(node: &Node, code: &[u8]) -> bool {
+    const ANNOTATIONS: &[&str] = &[
+        "Test",
+        "ParameterizedTest",
+        "RepeatedTest",
+        "TestFactory",
+        "TestTemplate",
+    ];
+
+    match node.kind() {
+        "method_declaration" => node
+            .children()
+            .filter(|child| child.kind() == "modifiers")
+            .flat_map(|modifiers| modifiers.children())
+            .filter(|modifier| matches!(modifier.kind(), "marker_annotation" | "annotation"))
+            .any(|annotation| {
+                field_text(&annotation, "name", code).is_some_and(|name| {
+                    ANNOTATIONS.contains(&name.rsplit('.').next().unwrap_or_default())
+                })
+            }),
+        "class_declaration" => field_text(node, "name", code)
+            .is_some_and(|name| name.ends_with("Test") || name.ends_with("Tests")),
+        _ => false,
+    }
+}
+
+/// Returns `true` if the node of a space is test code.
+pub(crate) fn is_test_space(lang: LANG, node: &Node, code: &[u8]) -> bool {
+    match lang {
+        LANG::Rust => is_rust_test(node, code),
+        LANG::Python => is_python_test(node, code),
+        LANG::Javascript | LANG::Mozjs | LANG::Typescript | LANG::Tsx => {
+            is_javascript_test(node, code)
+        }
+        LANG::Java => is_java_test(node, code),
+        _ => false,
+    }
+}
+
+/// Returns the lines of a test item which is not a space, that is
+/// a `Rust` test module along with its attributes.
+pub(crate) fn test_item_lines(lang: LANG, node: &Node, code: &[u8]) -> Option<(usize, usize)> {
+    if lang != LANG::Rust || node.kind() != "mod_item" || !is_rust_test(node, code) {
+        return None;
+    }
+    let mut start = node.start_row();
+    let mut sibling = node.previous_sibling();
+    while let Some(attribute) = sibling.filter(|sibling| sibling.kind() == "attribute_item") {
+        start = attribute.start_row();
+        sibling = attribute.previous_sibling();
+    }
+    Some((start + 1, node.end_row() + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        JavaParser, JavascriptParser, MetricsOpts, ParserTrait, PythonParser, RustParser,
+        TypescriptParser, metrics, metrics_with_opts,
+    };
+
+    use super::*;
+
+    // Returns the names of the test spaces of a code.
+    fn test_spaces<T: ParserTrait>(path: &str, source: &str) -> Vec<String> {
+        fn collect(space: &FuncSpace, names: &mut Vec<String>) {
+            for space in &space.spaces {
+                if space.is_test {
+                    names.push(space.name.clone().unwrap_or_default());
+                }
+                collect(space, names);
+            }
+        }
+
+        let path = PathBuf::from(path);
+        let parser = T::new(source.as_bytes().to_vec(), &path, None);
+        let mut names = Vec::new();
+        collect(&metrics(&parser, &path).unwrap(), &mut names);
+        names
+    }
+
+    #[test]
+    fn test_paths() {
+        for path in [
+            "tests/integration.rs",
+            "src/test/java/app/Foo.java",
+            "app/__tests__/view.js",
+            "app/test_util.py",
+            "app/util_test.py",
+            "app/view.test.ts",
+            "app/view.spec.js",
+            "app/FooTest.java",
+        ] {
+            assert!(is_test_path(Path::new(path)), "{path}");
+        }
+        for path in ["src/lib.rs", "app/testing.py", "app/latest.ts", "tests"] {
+            assert!(!is_test_path(Path::new(path)), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_paths_below_root() {
+        let is_test = |path: &str, opts: &MetricsOpts| {
+            let path = Path::new(path);
+            let parser = PythonParser::new(b"def f():\n    pass\n".to_vec(), path, None);
+            metrics_with_opts(&parser, path, opts).unwrap().is_test
+        };
+        let opts = MetricsOpts::default().root(Path::new("/tmp/test/project"));
+
+        // The test directories above the root are ignored
+        assert!(is_test(
+            "/tmp/test/project/src/util.py",
+            &MetricsOpts::default()
+        ));
+        assert!(!is_test("/tmp/test/project/src/util.py", &opts));
+        assert!(is_test("/tmp/test/project/tests/util.py", &opts));
+        assert!(is_test("/tmp/test/project/src/test_util.py", &opts));
+        // A file analyzed alone is its own root
+        let opts = MetricsOpts::default().root(Path::new("/tmp/tests/test_util.py"));
+        assert!(is_test("/tmp/tests/test_util.py", &opts));
+        let opts = MetricsOpts::default().root(Path::new("/tmp/tests/util.py"));
+        assert!(!is_test("/tmp/tests/util.py", &opts));
+    }
+
+    #[test]
+    fn rust_tests() {
+        let names = test_spaces::<RustParser>(
+            "src/lib.rs",
+            "fn code() {}\n\
+             #[test]\n\
+             fn unit() {}\n\
+             #[tokio::test]\n\
+             async fn asynchronous() {}\n\
+             #[cfg(test)]\n\
+             mod tests {\n\
+                 fn helper() {}\n\
+                 impl Foo {\n\
+                     fn method() {}\n\
+                 }\n\
+             }\n\
+             #[cfg(not(test))]\n\
+             fn not_test() {}\n",
+        );
+        assert_eq!(names, ["unit", "asynchronous", "helper", "Foo", "method"]);
+    }
+
+    #[test]
+    fn python_tests() {
+        let names = test_spaces::<PythonParser>(
+            "app/util.py",
+            "def code():\n    pass\n\n\
+             def test_code():\n    def nested():\n        pass\n\n\
+             class TestUtil:\n    def helper(self):\n        pass\n\n\
+             class UtilCase(unittest.TestCase):\n    def test_it(self):\n        pass\n\n\
+             def testing():\n    pass\n",
+        );
+        assert_eq!(
+            names,
+            [
+                "test_code",
+                "nested",
+                "TestUtil",
+                "helper",
+                "UtilCase",
+                "test_it"
+            ]
+        );
+    }
+
+    #[test]
+    fn javascript_tests() {
+        let source = "function code() {}\n\
+             describe('code', () => {\n\
+                 beforeEach(function setup() {});\n\
+                 it.each([1, 2])('works', (n) => {});\n\
+             });\n\
+             run(() => {});\n";
+        assert_eq!(
+            test_spaces::<JavascriptParser>("app/view.js", source).len(),
+            3
+        );
+        assert_eq!(
+            test_spaces::<TypescriptParser>("app/view.ts", source).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn java_tests() {
+        let names = test_spaces::<JavaParser>(
+            "src/main/java/app/Shapes.java",
+            "class Shapes {\n\
+                 void code() {}\n\
+                 @Test void unit() {}\n\
+                 @org.junit.jupiter.params.ParameterizedTest\n\
+                 @ValueSource(ints = {1})\n\
+                 void parameterized(int n) {}\n\
+                 @Override public String toString() { return \"\"; }\n\
+             }\n\
+             class ShapesTest {\n\
+                 void helper() {}\n\
+             }\n",
+        );
+        assert_eq!(names, ["unit", "parameterized", "ShapesTest", "helper"]);
+    }
+
+    #[test]
+    fn test_code_rollup() {
+        let path = PathBuf::from("src/lib.rs");
+        let source = "fn code(x: bool) -> u8 {\n    if x { 1 } else { 0 }\n}\n\n\
+             #[cfg(test)]\n\
+             mod tests {\n\
+                 #[test]\n\
+                 fn unit() {\n\
+                     assert_eq!(super::code(true), 1);\n\
+                 }\n\
+             }\n";
+        let parser = RustParser::new(source.as_bytes().to_vec(), &path, None);
+        let space = metrics(&parser, &path).unwrap();
+
+        insta::assert_snapshot!(space.metrics.test_code.to_string(), @"all: [sloc: 11, ploc: 10, lloc: 2, functions: 2, cyclomatic: 4, cognitive: 2], code: [sloc: 4, ploc: 3, lloc: 1, functions: 1, cyclomatic: 3, cognitive: 2], tests: [sloc: 7, ploc: 7, lloc: 1, functions: 1, cyclomatic: 1, cognitive: 0], sloc_ratio: 1.75, lloc_ratio: 1, functions_ratio: 1");
+        let parser = RustParser::new(b"fn code() {}\n".to_vec(), &path, None);
+        assert!(
+            metrics(&parser, &path)
+                .unwrap()
+                .metrics
+                .test_code
+                .is_disabled()
+        );
+    }
+}
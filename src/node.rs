@@ -1,21 +1,209 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
 use tree_sitter::Node as OtherNode;
 use tree_sitter::Tree as OtherTree;
-use tree_sitter::{Parser, TreeCursor};
+use tree_sitter::{ParseOptions, ParseState, Parser, TreeCursor};
 
 use crate::checker::Checker;
 use crate::traits::{LanguageInfo, Search};
 
+/// Limits applied to the parsers created while running
+/// [`with_parse_limits`].
+///
+/// [`with_parse_limits`]: fn.with_parse_limits.html
+#[derive(Clone, Debug, Default)]
+pub struct ParseLimits {
+    /// The time budget granted to the whole closure
+    ///
+    /// If `None`, parsing is never interrupted because of time
+    pub timeout: Option<Duration>,
+    /// A flag which interrupts parsing as soon as it is set
+    pub cancellation_flag: Option<Arc<AtomicBool>>,
+}
+
+/// The reason why a parsing has been interrupted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseCancelled {
+    /// The time budget is exhausted
+    Timeout(Duration),
+    /// The cancellation flag has been set
+    Cancelled,
+}
+
+impl fmt::Display for ParseCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Timeout(timeout) => {
+                write!(f, "the analysis exceeded the time budget of {timeout:?}")
+            }
+            Self::Cancelled => write!(f, "the analysis has been cancelled"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ActiveLimits {
+    timeout: Duration,
+    deadline: Option<Instant>,
+    cancellation_flag: Option<Arc<AtomicBool>>,
+    // Set once the limits have been exceeded
+    cancelled: Option<ParseCancelled>,
+}
+
+impl ActiveLimits {
+    fn check(&self) -> Result<(), ParseCancelled> {
+        if let Some(cancelled) = self.cancelled {
+            Err(cancelled)
+        } else if self
+            .cancellation_flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            Err(ParseCancelled::Cancelled)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Err(ParseCancelled::Timeout(self.timeout))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+thread_local! {
+    static PARSE_LIMITS: RefCell<Option<ActiveLimits>> = const { RefCell::new(None) };
+}
+
+// Restores the limits which were active before entering `with_parse_limits`,
+// even when the closure panics.
+struct LimitsGuard(Option<ActiveLimits>);
+
+impl Drop for LimitsGuard {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        PARSE_LIMITS.with(|limits| *limits.borrow_mut() = previous);
+    }
+}
+
+/// Runs a closure interrupting every parsing it performs once
+/// the given limits are exceeded.
+///
+/// Returns a [`ParseCancelled`] error when the limits have been exceeded,
+/// during a parsing or before the end of the closure.
+/// An interrupted parsing produces an empty tree, so that the closure
+/// ends quickly, and its result is discarded.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use std::time::Duration;
+///
+/// use rust_code_analysis::{get_function_spaces, with_parse_limits, ParseLimits, LANG};
+///
+/// let source_code = "fn main() {}";
+/// let path = PathBuf::from("foo.rs");
+///
+/// let limits = ParseLimits {
+///     timeout: Some(Duration::from_secs(10)),
+///     cancellation_flag: None,
+/// };
+///
+/// let space = with_parse_limits(limits, || {
+///     get_function_spaces(&LANG::Rust, source_code.as_bytes().to_vec(), &path, None)
+/// });
+/// assert!(space.unwrap().is_some());
+/// ```
+///
+/// [`ParseCancelled`]: enum.ParseCancelled.html
+pub fn with_parse_limits<R, F: FnOnce() -> R>(
+    limits: ParseLimits,
+    f: F,
+) -> Result<R, ParseCancelled> {
+    let active = ActiveLimits {
+        timeout: limits.timeout.unwrap_or_default(),
+        deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+        cancellation_flag: limits.cancellation_flag,
+        cancelled: None,
+    };
+    let previous = PARSE_LIMITS.with(|limits| limits.borrow_mut().replace(active));
+    let _guard = LimitsGuard(previous);
+
+    let res = f();
+    check_parse_limits().map(|()| res)
+}
+
+/// Checks the limits of the enclosing [`with_parse_limits`], if any,
+/// so that a long analysis can be stopped between two of its phases.
+///
+/// Once the limits have been exceeded, every parsing is interrupted
+/// and `with_parse_limits` returns the same [`ParseCancelled`] error.
+///
+/// [`with_parse_limits`]: fn.with_parse_limits.html
+/// [`ParseCancelled`]: enum.ParseCancelled.html
+pub fn check_parse_limits() -> Result<(), ParseCancelled> {
+    PARSE_LIMITS.with(|limits| {
+        let mut limits = limits.borrow_mut();
+        let Some(limits) = limits.as_mut() else {
+            return Ok(());
+        };
+        limits
+            .check()
+            .inspect_err(|&cancelled| limits.cancelled = Some(cancelled))
+    })
+}
+
+fn ts_parser<T: LanguageInfo>() -> Parser {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&T::get_lang().get_ts_language())
+        .unwrap();
+    parser
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct Tree(OtherTree);
 
 impl Tree {
-    pub(crate) fn new<T: LanguageInfo>(code: &[u8]) -> Self {
-        let mut parser = Parser::new();
-        parser
-            .set_language(&T::get_lang().get_ts_language())
-            .unwrap();
+    /// Parses a code, unless the limits of the enclosing
+    /// [`with_parse_limits`] are exceeded.
+    ///
+    /// [`with_parse_limits`]: fn.with_parse_limits.html
+    pub(crate) fn new<T: LanguageInfo>(code: &[u8]) -> Result<Self, ParseCancelled> {
+        let mut parser = ts_parser::<T>();
+        if PARSE_LIMITS.with(|limits| limits.borrow().is_none()) {
+            return Ok(Self(parser.parse(code, None).unwrap()));
+        }
+
+        // The progress callback is not invoked on small codes
+        check_parse_limits()?;
+        let mut progress = |_: &ParseState| match check_parse_limits() {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        };
+        let tree = parser.parse_with_options(
+            &mut |i, _| if i < code.len() { &code[i..] } else { &[] },
+            None,
+            Some(ParseOptions::new().progress_callback(&mut progress)),
+        );
+
+        match tree {
+            Some(tree) => Ok(Self(tree)),
+            // Parsing is only interrupted by the progress callback
+            None => Err(check_parse_limits().expect_err("tree-sitter failed to parse the code")),
+        }
+    }
 
-        Self(parser.parse(code, None).unwrap())
+    /// Returns the tree of an empty code, which stands for
+    /// a code whose parsing has been interrupted.
+    pub(crate) fn empty<T: LanguageInfo>() -> Self {
+        Self(ts_parser::<T>().parse(b"", None).unwrap())
     }
 
     pub(crate) fn get_root(&self) -> Node<'_> {
@@ -133,6 +321,42 @@ impl<'a> Node<'a> {
         Cursor(self.0.walk())
     }
 
+    /// Visits the nodes of a subtree in pre-order.
+    ///
+    /// The `action` returns the state passed to the children of a node,
+    /// or `None` to skip them.
+    pub(crate) fn traverse<S: Copy>(
+        &self,
+        state: S,
+        mut action: impl FnMut(&Node<'a>, S) -> Option<S>,
+    ) {
+        let mut cursor = self.cursor();
+        let mut stack = vec![(*self, state)];
+
+        while let Some((node, state)) = stack.pop() {
+            let Some(state) = action(&node, state) else {
+                continue;
+            };
+            cursor.reset(&node);
+            if cursor.goto_first_child() {
+                let start = stack.len();
+                loop {
+                    stack.push((cursor.node(), state));
+                    if !cursor.goto_next_sibling() {
+                        break;
+                    }
+                }
+                stack[start..].reverse();
+            }
+        }
+    }
+
+    /// Visits the nodes of a subtree in pre-order, skipping
+    /// the children of a node when `action` returns `false`.
+    pub(crate) fn visit(&self, mut action: impl FnMut(&Node<'a>) -> bool) {
+        self.traverse((), |node, ()| action(node).then_some(()));
+    }
+
     #[allow(dead_code)]
     pub(crate) fn get_parent(&self, level: usize) -> Option<Node<'a>> {
         let mut level = level;
@@ -237,27 +461,10 @@ impl<'a> Search<'a> for Node<'a> {
     }
 
     fn act_on_node(&self, action: &mut dyn FnMut(&Node<'a>)) {
-        let mut cursor = self.cursor();
-        let mut stack = Vec::new();
-        let mut children = Vec::new();
-
-        stack.push(*self);
-
-        while let Some(node) = stack.pop() {
-            action(&node);
-            cursor.reset(&node);
-            if cursor.goto_first_child() {
-                loop {
-                    children.push(cursor.node());
-                    if !cursor.goto_next_sibling() {
-                        break;
-                    }
-                }
-                for child in children.drain(..).rev() {
-                    stack.push(child);
-                }
-            }
-        }
+        self.visit(|node| {
+            action(node);
+            true
+        });
     }
 
     fn first_child(&self, pred: fn(u16) -> bool) -> Option<Node<'a>> {
@@ -270,3 +477,61 @@ impl<'a> Search<'a> for Node<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{LANG, get_function_spaces};
+
+    use super::*;
+
+    fn spaces(limits: ParseLimits) -> Result<bool, ParseCancelled> {
+        with_parse_limits(limits, || {
+            get_function_spaces(
+                &LANG::Rust,
+                b"fn main() {}".to_vec(),
+                Path::new("foo.rs"),
+                None,
+            )
+            .is_some()
+        })
+    }
+
+    #[test]
+    fn parse_within_limits() {
+        let limits = ParseLimits {
+            timeout: Some(Duration::from_secs(60)),
+            cancellation_flag: Some(Arc::new(AtomicBool::new(false))),
+        };
+        assert_eq!(spaces(limits), Ok(true));
+    }
+
+    #[test]
+    fn parse_cancelled() {
+        let limits = ParseLimits {
+            timeout: None,
+            cancellation_flag: Some(Arc::new(AtomicBool::new(true))),
+        };
+        assert_eq!(spaces(limits), Err(ParseCancelled::Cancelled));
+
+        // Limits do not outlive `with_parse_limits`
+        assert_eq!(spaces(ParseLimits::default()), Ok(true));
+    }
+
+    #[test]
+    fn analysis_exceeding_limits() {
+        let timeout = Duration::from_millis(1);
+        let limits = ParseLimits {
+            timeout: Some(timeout),
+            cancellation_flag: None,
+        };
+        let res = with_parse_limits(limits, || {
+            std::thread::sleep(timeout * 2);
+            // The limits are checked between two phases of an analysis
+            check_parse_limits().is_err()
+        });
+        assert_eq!(res, Err(ParseCancelled::Timeout(timeout)));
+        assert_eq!(check_parse_limits(), Ok(()));
+    }
+}
@@ -3,15 +3,20 @@ use termcolor::{Color, ColorChoice, StandardStream, StandardStreamLock};
 
 use crate::abc;
 use crate::cognitive;
+use crate::coverage;
 use crate::cyclomatic;
+use crate::docs;
 use crate::exit;
 use crate::halstead;
+use crate::ifdef;
 use crate::loc;
+use crate::markers;
 use crate::mi;
 use crate::nargs;
 use crate::nom;
 use crate::npa;
 use crate::npm;
+use crate::test_code;
 use crate::wmc;
 
 use crate::spaces::{CodeMetrics, FuncSpace};
@@ -73,7 +78,12 @@ fn dump_space(
     write!(stdout, "{}", space.name.as_ref().map_or("", |name| name))?;
 
     intense_color(stdout, Color::Red)?;
-    writeln!(stdout, " (@{})", space.start_line)?;
+    write!(stdout, " (@{})", space.start_line)?;
+    if space.is_test {
+        intense_color(stdout, Color::Magenta)?;
+        write!(stdout, " [test]")?;
+    }
+    writeln!(stdout)?;
 
     let prefix = format!("{prefix}{pref_child}");
     dump_metrics(&space.metrics, &prefix, space.spaces.is_empty(), stdout)?;
@@ -112,6 +122,11 @@ fn dump_metrics(
     dump_nom(&metrics.nom, &prefix, false, stdout)?;
     dump_mi(&metrics.mi, &prefix, false, stdout)?;
     dump_abc(&metrics.abc, &prefix, false, stdout)?;
+    dump_docs(&metrics.docs, &prefix, false, stdout)?;
+    dump_ifdef(&metrics.ifdef, &prefix, false, stdout)?;
+    dump_coverage(&metrics.coverage, &prefix, false, stdout)?;
+    dump_test_code(&metrics.test_code, &prefix, false, stdout)?;
+    dump_markers(&metrics.markers, &prefix, false, stdout)?;
     dump_wmc(&metrics.wmc, &prefix, false, stdout)?;
     dump_npm(&metrics.npm, &prefix, false, stdout)?;
     dump_npa(&metrics.npa, &prefix, true, stdout)
@@ -215,6 +230,9 @@ fn dump_loc(
     dump_value("ploc", stats.ploc(), &prefix, false, stdout)?;
     dump_value("lloc", stats.lloc(), &prefix, false, stdout)?;
     dump_value("cloc", stats.cloc(), &prefix, false, stdout)?;
+    if stats.has_cloc_code() {
+        dump_value("cloc_code", stats.cloc_code(), &prefix, false, stdout)?;
+    }
     dump_value("blank", stats.blank(), &prefix, true, stdout)
 }
 
@@ -331,6 +349,159 @@ fn dump_abc(
     dump_value("magnitude", stats.magnitude_sum(), &prefix, true, stdout)
 }
 
+fn dump_docs(
+    stats: &docs::Stats,
+    prefix: &str,
+    last: bool,
+    stdout: &mut StandardStreamLock,
+) -> std::io::Result<()> {
+    if stats.is_disabled() {
+        return Ok(());
+    }
+
+    let (pref_child, pref) = if last { ("   ", "`- ") } else { ("|  ", "|- ") };
+
+    color(stdout, Color::Blue)?;
+    write!(stdout, "{prefix}{pref}")?;
+
+    intense_color(stdout, Color::Green)?;
+    writeln!(stdout, "docs")?;
+
+    let prefix = format!("{prefix}{pref_child}");
+    dump_value("public", stats.public_sum(), &prefix, false, stdout)?;
+    dump_value("documented", stats.documented_sum(), &prefix, false, stdout)?;
+    dump_value("coverage", stats.coverage(), &prefix, true, stdout)
+}
+
+fn dump_ifdef(
+    stats: &ifdef::Stats,
+    prefix: &str,
+    last: bool,
+    stdout: &mut StandardStreamLock,
+) -> std::io::Result<()> {
+    if stats.is_disabled() {
+        return Ok(());
+    }
+
+    let (pref_child, pref) = if last { ("   ", "`- ") } else { ("|  ", "|- ") };
+
+    color(stdout, Color::Blue)?;
+    write!(stdout, "{prefix}{pref}")?;
+
+    intense_color(stdout, Color::Green)?;
+    writeln!(stdout, "ifdef")?;
+
+    let prefix = format!("{prefix}{pref_child}");
+    dump_value("blocks", stats.blocks_sum(), &prefix, false, stdout)?;
+    dump_value("macros", stats.macros_sum(), &prefix, true, stdout)
+}
+
+fn dump_coverage(
+    stats: &coverage::Stats,
+    prefix: &str,
+    last: bool,
+    stdout: &mut StandardStreamLock,
+) -> std::io::Result<()> {
+    if stats.is_disabled() {
+        return Ok(());
+    }
+
+    let (pref_child, pref) = if last { ("   ", "`- ") } else { ("|  ", "|- ") };
+
+    color(stdout, Color::Blue)?;
+    write!(stdout, "{prefix}{pref}")?;
+
+    intense_color(stdout, Color::Green)?;
+    writeln!(stdout, "coverage")?;
+
+    let prefix = format!("{prefix}{pref_child}");
+    dump_value("lines", stats.lines(), &prefix, false, stdout)?;
+    dump_value("covered", stats.covered(), &prefix, false, stdout)?;
+    dump_value("coverage", stats.coverage(), &prefix, false, stdout)?;
+    dump_value("crap", stats.crap(), &prefix, false, stdout)?;
+    dump_value("crap_sum", stats.crap_sum(), &prefix, false, stdout)?;
+    dump_value("crap_max", stats.crap_max(), &prefix, true, stdout)
+}
+
+fn dump_test_code(
+    stats: &test_code::Stats,
+    prefix: &str,
+    last: bool,
+    stdout: &mut StandardStreamLock,
+) -> std::io::Result<()> {
+    if stats.is_disabled() {
+        return Ok(());
+    }
+
+    let (pref_child, pref) = if last { ("   ", "`- ") } else { ("|  ", "|- ") };
+
+    color(stdout, Color::Blue)?;
+    write!(stdout, "{prefix}{pref}")?;
+
+    intense_color(stdout, Color::Green)?;
+    writeln!(stdout, "test_code")?;
+
+    let prefix = format!("{prefix}{pref_child}");
+    dump_size("all", &stats.all(), &prefix, stdout)?;
+    dump_size("code", &stats.code(), &prefix, stdout)?;
+    dump_size("tests", &stats.tests(), &prefix, stdout)?;
+    dump_value("sloc_ratio", stats.sloc_ratio(), &prefix, false, stdout)?;
+    dump_value("lloc_ratio", stats.lloc_ratio(), &prefix, false, stdout)?;
+    dump_value(
+        "functions_ratio",
+        stats.functions_ratio(),
+        &prefix,
+        true,
+        stdout,
+    )
+}
+
+fn dump_size(
+    name: &str,
+    size: &test_code::Size,
+    prefix: &str,
+    stdout: &mut StandardStreamLock,
+) -> std::io::Result<()> {
+    color(stdout, Color::Blue)?;
+    write!(stdout, "{prefix}|- ")?;
+
+    intense_color(stdout, Color::Green)?;
+    writeln!(stdout, "{name}")?;
+
+    let prefix = format!("{prefix}|  ");
+    dump_value("sloc", size.sloc, &prefix, false, stdout)?;
+    dump_value("ploc", size.ploc, &prefix, false, stdout)?;
+    dump_value("lloc", size.lloc, &prefix, false, stdout)?;
+    dump_value("functions", size.functions, &prefix, false, stdout)?;
+    dump_value("cyclomatic", size.cyclomatic, &prefix, false, stdout)?;
+    dump_value("cognitive", size.cognitive, &prefix, true, stdout)
+}
+
+fn dump_markers(
+    stats: &markers::Stats,
+    prefix: &str,
+    last: bool,
+    stdout: &mut StandardStreamLock,
+) -> std::io::Result<()> {
+    if stats.is_empty() {
+        return Ok(());
+    }
+
+    let (pref_child, pref) = if last { ("   ", "`- ") } else { ("|  ", "|- ") };
+
+    color(stdout, Color::Blue)?;
+    write!(stdout, "{prefix}{pref}")?;
+
+    intense_color(stdout, Color::Green)?;
+    writeln!(stdout, "markers")?;
+
+    let prefix = format!("{prefix}{pref_child}");
+    for (marker, count) in stats.markers() {
+        dump_value(marker, count, &prefix, false, stdout)?;
+    }
+    dump_value("total", stats.total(), &prefix, true, stdout)
+}
+
 fn dump_wmc(
     stats: &wmc::Stats,
     prefix: &str,
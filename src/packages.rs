@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use petgraph::visit::EdgeRef;
+use serde::Serialize;
+
+use crate::Rust;
+use crate::checker::Checker;
+use crate::getter::Getter;
+use crate::imports::ModuleGraph;
+use crate::langs::LANG;
+use crate::node::Node;
+use crate::spaces::SpaceKind;
+use crate::traits::*;
+
+/// The types declared in a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FileTypes {
+    /// The number of classes, structs, enums, interfaces and traits
+    pub types: usize,
+    /// The number of interfaces, traits and abstract classes
+    pub abstract_types: usize,
+}
+
+// Returns `true` if the declaration of a class has the `abstract` modifier.
+fn is_abstract_class(node: &Node, code: &[u8]) -> bool {
+    let Some(text) = node.utf8_text(code) else {
+        return false;
+    };
+    let declaration = text.split('{').next().unwrap_or_default();
+    declaration
+        .split_whitespace()
+        .take_while(|word| *word != "class")
+        .any(|word| word == "abstract")
+}
+
+/// Counts the types declared in a code, and the abstract ones among them.
+pub fn file_types<T: ParserTrait>(parser: &T) -> FileTypes {
+    let code = parser.get_code();
+    let mut types = FileTypes::default();
+
+    let is_rust = parser.get_language() == LANG::Rust;
+
+    parser.get_root().act_on_node(&mut |node| {
+        // The `Rust` structs and enums are not spaces
+        if is_rust
+            && matches!(
+                node.kind_id().into(),
+                Rust::StructItem | Rust::EnumItem | Rust::UnionItem
+            )
+        {
+            types.types += 1;
+            return;
+        }
+        if !T::Checker::is_func_space(node) {
+            return;
+        }
+        match T::Getter::get_space_kind(node) {
+            SpaceKind::Interface | SpaceKind::Trait => {
+                types.types += 1;
+                types.abstract_types += 1;
+            }
+            SpaceKind::Class if is_abstract_class(node, code) => {
+                types.types += 1;
+                types.abstract_types += 1;
+            }
+            SpaceKind::Class | SpaceKind::Struct => types.types += 1,
+            _ => {}
+        }
+    });
+
+    types
+}
+
+/// The metrics of a package, as defined by Robert C. Martin.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PackageMetrics {
+    /// The directory of the package
+    pub package: PathBuf,
+    /// The number of files of the package
+    pub files: usize,
+    /// The number of types declared in the package
+    pub types: usize,
+    /// The number of abstract types declared in the package
+    pub abstract_types: usize,
+    /// The afferent coupling, that is the number of other packages
+    /// importing the package
+    pub ca: usize,
+    /// The efferent coupling, that is the number of other packages
+    /// imported by the package
+    pub ce: usize,
+    /// The instability, that is `Ce / (Ca + Ce)`
+    pub instability: f64,
+    /// The abstractness, that is the ratio of abstract types
+    pub abstractness: f64,
+    /// The distance from the main sequence, that is `|A + I - 1|`
+    pub distance: f64,
+}
+
+/// The metrics of the packages of a series of files.
+///
+/// A package is a directory, containing the analyzed files
+/// which are directly in it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Packages {
+    /// The metrics of each package, sorted by directory
+    pub packages: Vec<PackageMetrics>,
+}
+
+impl Packages {
+    /// Computes the metrics of the packages of the files of a module graph.
+    pub fn new(graph: &ModuleGraph) -> Self {
+        let package = |path: &Path| path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut packages: BTreeMap<PathBuf, PackageMetrics> = BTreeMap::new();
+        let mut afferent: BTreeMap<PathBuf, BTreeSet<PathBuf>> = BTreeMap::new();
+        let mut efferent: BTreeMap<PathBuf, BTreeSet<PathBuf>> = BTreeMap::new();
+
+        for index in graph.graph.node_indices() {
+            let path = package(&graph.graph[index]);
+            let types = graph.types.get(index.index()).copied().unwrap_or_default();
+            let metrics = packages.entry(path.clone()).or_default();
+            metrics.package = path;
+            metrics.files += 1;
+            metrics.types += types.types;
+            metrics.abstract_types += types.abstract_types;
+        }
+
+        for edge in graph.graph.edge_references() {
+            let source = package(&graph.graph[edge.source()]);
+            let target = package(&graph.graph[edge.target()]);
+            if source != target {
+                afferent
+                    .entry(target.clone())
+                    .or_default()
+                    .insert(source.clone());
+                efferent.entry(source).or_default().insert(target);
+            }
+        }
+
+        for (path, metrics) in packages.iter_mut() {
+            metrics.ca = afferent.get(path).map_or(0, BTreeSet::len);
+            metrics.ce = efferent.get(path).map_or(0, BTreeSet::len);
+            let coupling = metrics.ca + metrics.ce;
+            metrics.instability = if coupling == 0 {
+                0.
+            } else {
+                metrics.ce as f64 / coupling as f64
+            };
+            metrics.abstractness = if metrics.types == 0 {
+                0.
+            } else {
+                metrics.abstract_types as f64 / metrics.types as f64
+            };
+            metrics.distance = (metrics.abstractness + metrics.instability - 1.).abs();
+        }
+
+        Self {
+            packages: packages.into_values().collect(),
+        }
+    }
+}
+
+impl fmt::Display for Packages {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for package in &self.packages {
+            writeln!(
+                f,
+                "{}: files: {}, types: {}, abstract types: {}, ca: {}, ce: {}, instability: {:.2}, abstractness: {:.2}, distance: {:.2}",
+                package.package.display(),
+                package.files,
+                package.types,
+                package.abstract_types,
+                package.ca,
+                package.ce,
+                package.instability,
+                package.abstractness,
+                package.distance
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ImportsCfg, ImportsCollect, JavaParser, ModuleGraphBuilder, RustParser, TypescriptParser,
+    };
+
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn types<T: ParserTrait>(source: &str) -> FileTypes {
+        let parser = T::new(source.as_bytes().to_vec(), Path::new("foo"), None);
+        file_types(&parser)
+    }
+
+    #[test]
+    fn abstract_types() {
+        assert_eq!(
+            types::<JavaParser>(
+                "public abstract class Shape {}\n\
+                 interface Drawable {}\n\
+                 final class Circle extends Shape implements Drawable {}\n"
+            ),
+            FileTypes {
+                types: 3,
+                abstract_types: 2
+            }
+        );
+        assert_eq!(
+            types::<RustParser>("trait Draw {}\nstruct Circle;\nimpl Draw for Circle {}\n"),
+            FileTypes {
+                types: 2,
+                abstract_types: 1
+            }
+        );
+        assert_eq!(
+            types::<TypescriptParser>("interface A {}\nclass B implements A {}\n"),
+            FileTypes {
+                types: 2,
+                abstract_types: 1
+            }
+        );
+    }
+
+    #[test]
+    fn package_metrics() {
+        let builder = Arc::new(Mutex::new(ModuleGraphBuilder::default()));
+        for (path, source) in [
+            (
+                "app/ui/View.java",
+                "package app.ui;\nimport app.core.Shape;\nimport app.db.Store;\nclass View {}\n",
+            ),
+            (
+                "app/core/Shape.java",
+                "package app.core;\npublic interface Shape {}\n",
+            ),
+            (
+                "app/core/Circle.java",
+                "package app.core;\nclass Circle implements Shape {}\n",
+            ),
+            (
+                "app/db/Store.java",
+                "package app.db;\nimport app.core.Shape;\nclass Store {}\n",
+            ),
+        ] {
+            let parser = JavaParser::new(source.as_bytes().to_vec(), Path::new(path), None);
+            let cfg = ImportsCfg {
+                path: PathBuf::from(path),
+                builder: builder.clone(),
+                types: true,
+            };
+            ImportsCollect::call(cfg, &parser).unwrap();
+        }
+        let builder = Arc::try_unwrap(builder).unwrap().into_inner().unwrap();
+        let packages = Packages::new(&builder.build());
+
+        insta::assert_snapshot!(packages.to_string(), @r#"
+            app/core: files: 2, types: 2, abstract types: 1, ca: 2, ce: 0, instability: 0.00, abstractness: 0.50, distance: 0.50
+            app/db: files: 1, types: 1, abstract types: 0, ca: 1, ce: 1, instability: 0.50, abstractness: 0.00, distance: 0.50
+            app/ui: files: 1, types: 1, abstract types: 0, ca: 0, ce: 2, instability: 1.00, abstractness: 0.00, distance: 0.00
+            "#);
+    }
+}
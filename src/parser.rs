@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::path::Path;
 use std::sync::Arc;
@@ -5,9 +6,12 @@ use std::sync::Arc;
 use crate::abc::Abc;
 use crate::checker::Checker;
 use crate::cognitive::Cognitive;
+use crate::comments::commented_code;
 use crate::cyclomatic::Cyclomatic;
+use crate::docs::Docs;
 use crate::exit::Exit;
 use crate::halstead::Halstead;
+use crate::ifdef::Ifdef;
 use crate::loc::Loc;
 use crate::mi::Mi;
 use crate::nargs::NArgs;
@@ -19,10 +23,10 @@ use crate::wmc::Wmc;
 use crate::alterator::Alterator;
 use crate::getter::Getter;
 
-use crate::c_macro;
+use crate::c_macro::{self, SourceMap};
 use crate::langs::*;
 use crate::node::{Node, Tree};
-use crate::preproc::{PreprocResults, get_macros};
+use crate::preproc::{PreprocResults, get_definitions, get_macros};
 use crate::traits::*;
 
 #[derive(Debug)]
@@ -34,8 +38,10 @@ pub struct Parser<
         + Abc
         + Cognitive
         + Cyclomatic
+        + Docs
         + Exit
         + Halstead
+        + Ifdef
         + Loc
         + Mi
         + NArgs
@@ -46,6 +52,7 @@ pub struct Parser<
 > {
     code: Vec<u8>,
     tree: Tree,
+    source_map: Option<SourceMap>,
     phantom: PhantomData<T>,
 }
 
@@ -80,12 +87,18 @@ fn get_fake_code<T: LanguageInfo>(
     code: &[u8],
     path: &Path,
     pr: Option<Arc<PreprocResults>>,
-) -> Option<Vec<u8>> {
+) -> Option<(Vec<u8>, Option<SourceMap>)> {
     if let Some(pr) = pr {
         match T::get_lang() {
             LANG::Cpp => {
                 let macros = get_macros(path, &pr.files);
-                c_macro::replace(code, &macros)
+                if let Some(cfg) = &pr.expansion {
+                    let definitions = get_definitions(code, path, &pr.files, cfg);
+                    let (code, source_map) = c_macro::expand(code, &definitions, &macros);
+                    Some((code, Some(source_map)))
+                } else {
+                    c_macro::replace(code, &macros).map(|code| (code, None))
+                }
             }
             _ => None,
         }
@@ -103,8 +116,10 @@ impl<
         + Abc
         + Cognitive
         + Cyclomatic
+        + Docs
         + Exit
         + Halstead
+        + Ifdef
         + Loc
         + Mi
         + NArgs
@@ -128,20 +143,24 @@ impl<
     type Abc = T;
     type Npm = T;
     type Npa = T;
+    type Docs = T;
+    type Ifdef = T;
 
     fn new(code: Vec<u8>, path: &Path, pr: Option<Arc<PreprocResults>>) -> Self {
         let fake_code = get_fake_code::<T>(&code, path, pr);
-        let code = if let Some(fake) = fake_code {
-            fake
+        let (code, source_map) = if let Some((fake, source_map)) = fake_code {
+            (fake, source_map)
         } else {
-            code
+            (code, None)
         };
 
-        let tree = Tree::new::<T>(&code);
+        // The analysis of an interrupted parsing is discarded by `with_parse_limits`
+        let tree = Tree::new::<T>(&code).unwrap_or_else(|_| Tree::empty::<T>());
 
         Self {
             code,
             tree,
+            source_map,
             phantom: PhantomData,
         }
     }
@@ -161,6 +180,11 @@ impl<
         &self.code
     }
 
+    #[inline(always)]
+    fn get_source_map(&self) -> Option<&SourceMap> {
+        self.source_map.as_ref()
+    }
+
     fn get_filters(&self, filters: &[String]) -> Filter {
         let mut res: Vec<Box<FilterFn>> = Vec::new();
         for f in filters.iter() {
@@ -169,6 +193,12 @@ impl<
                 "all" => res.push(Box::new(|_: &Node| -> bool { true })),
                 "call" => res.push(Box::new(T::is_call)),
                 "comment" => res.push(Box::new(T::is_comment)),
+                "commented_code" => {
+                    let ids: HashSet<usize> = commented_code(self).iter().map(|n| n.id()).collect();
+                    res.push(Box::new(move |node: &Node| -> bool {
+                        ids.contains(&node.id())
+                    }))
+                }
                 "error" => res.push(Box::new(T::is_error)),
                 "string" => res.push(Box::new(T::is_string)),
                 "function" => res.push(Box::new(T::is_func)),
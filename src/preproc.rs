@@ -1,28 +1,79 @@
-use std::collections::{HashMap, HashSet, hash_map};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use petgraph::{
-    Direction, algo::kosaraju_scc, graph::NodeIndex, stable_graph::StableGraph, visit::Dfs,
-};
+use petgraph::visit::Dfs;
 use serde::{Deserialize, Serialize};
 
 use crate::c_langs_macros::is_specials;
+use crate::include_graph::IncludeGraph;
+use crate::include_paths::{IncludePaths, IncludeResolver};
 
 use crate::langs::*;
 use crate::languages::language_preproc::*;
-use crate::tools::*;
+use crate::node::Node;
 use crate::traits::*;
 
 /// Preprocessor data of a `C/C++` file.
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct PreprocFile {
     /// The set of include directives explicitly written in a file
+    /// with double quotes
     pub direct_includes: HashSet<String>,
+    /// The set of include directives explicitly written in a file
+    /// with angle brackets
+    #[serde(default)]
+    pub angle_includes: HashSet<String>,
+    /// The set of include directives which do not refer to any
+    /// of the analyzed files, such as the system headers
+    #[serde(default)]
+    pub system_includes: HashSet<String>,
     /// The set of include directives implicitly imported in a file
     /// from other files
     pub indirect_includes: HashSet<String>,
     /// The set of macros of a file
     pub macros: HashSet<String>,
+    /// The macros defined in a file, in order of definition
+    #[serde(default)]
+    pub definitions: Vec<MacroDefinition>,
+    /// The branches of the conditional compilation blocks of a file,
+    /// ordered by their first line
+    #[serde(default)]
+    pub conditionals: Vec<ConditionalBranch>,
+}
+
+/// A macro defined in a `C/C++` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MacroDefinition {
+    /// The name of the macro
+    pub name: String,
+    /// The parameters of a function-like macro,
+    /// `None` for an object-like macro
+    pub parameters: Option<Vec<String>>,
+    /// The replacement list of the macro
+    pub body: String,
+    /// The line of the `#define` directive
+    pub line: usize,
+}
+
+/// A branch of a conditional compilation block of a `C/C++` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConditionalBranch {
+    /// The directive opening the branch, that is `if`, `ifdef`,
+    /// `ifndef`, `elif` or `else`
+    pub directive: String,
+    /// The condition of the directive, empty for an `else`
+    pub condition: String,
+    /// The sorted macros gating the branch, that is the macros referenced
+    /// by its condition, by the conditions of the previous branches of its
+    /// block and by the branches containing its block
+    pub macros: Vec<String>,
+    /// The line of the directive opening the branch
+    pub start_line: usize,
+    /// The last line of the branch, which is the line of the `#endif`
+    /// directive for the last branch of a block
+    pub end_line: usize,
+    /// The number of conditional blocks containing the block of the branch
+    pub depth: usize,
 }
 
 /// Preprocessor data of a series of `C/C++` files.
@@ -30,6 +81,76 @@ pub struct PreprocFile {
 pub struct PreprocResults {
     /// The preprocessor data of each `C/C++` file
     pub files: HashMap<PathBuf, PreprocFile>,
+    /// When set, the macros of the `C/C++` files are expanded with
+    /// their definitions instead of being neutralized
+    #[serde(skip)]
+    pub expansion: Option<MacroExpansionCfg>,
+}
+
+/// Configuration options for expanding the macros of `C/C++` files.
+#[derive(Clone, Debug, Default)]
+pub struct MacroExpansionCfg {
+    /// The macros defined by the user, which override
+    /// the ones defined in the files
+    pub defines: Vec<MacroDefinition>,
+    /// The macros undefined by the user, whose definitions
+    /// in the files are ignored
+    pub undefines: Vec<String>,
+}
+
+impl MacroExpansionCfg {
+    /// Adds a macro written as the `-D` option of a compiler,
+    /// that is `NAME`, `NAME=VALUE` or `NAME(PARAMETERS)=VALUE`.
+    ///
+    /// A macro without a value is defined as `1`.
+    pub fn define(&mut self, define: &str) {
+        let (name, body) = define.split_once('=').unwrap_or((define, "1"));
+        let (name, parameters) = match name.split_once('(') {
+            Some((name, parameters)) => {
+                let parameters = parameters
+                    .trim_end_matches(')')
+                    .split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                (name, Some(parameters))
+            }
+            None => (name, None),
+        };
+        self.defines.push(MacroDefinition {
+            name: name.trim().to_string(),
+            parameters,
+            body: body.to_string(),
+            line: 0,
+        });
+    }
+
+    /// Adds a macro written as the `-U` option of a compiler.
+    pub fn undefine(&mut self, name: &str) {
+        self.undefines.push(name.trim().to_string());
+    }
+
+    // Returns the value of a macro in the conditions of the directives,
+    // if it has been defined or undefined by the user.
+    fn value(&self, name: &str) -> Option<i64> {
+        if let Some(define) = self.defines.iter().rev().find(|d| d.name == name) {
+            return parse_integer(&define.body);
+        }
+        // An undefined macro is evaluated as `0`
+        self.undefines.iter().any(|u| u == name).then_some(0)
+    }
+
+    // Checks whether a macro is defined, if it has been
+    // defined or undefined by the user.
+    fn is_defined(&self, name: &str) -> Option<bool> {
+        if self.defines.iter().any(|d| d.name == name) {
+            Some(true)
+        } else if self.undefines.iter().any(|u| u == name) {
+            Some(false)
+        } else {
+            None
+        }
+    }
 }
 
 impl PreprocFile {
@@ -64,113 +185,310 @@ pub fn get_macros<S: ::std::hash::BuildHasher>(
     macros
 }
 
-/// Constructs a dependency graph of the include directives
-/// in a `C/C++` file.
-///
-/// The dependency graph is built using both preprocessor data and not
-/// extracted from the considered `C/C++` files.
-pub fn fix_includes<S: ::std::hash::BuildHasher>(
-    files: &mut HashMap<PathBuf, PreprocFile, S>,
-    all_files: &HashMap<String, Vec<PathBuf>, S>,
-) {
-    let mut nodes: HashMap<PathBuf, NodeIndex> = HashMap::new();
-    // Since we'll remove strong connected components we need to have a stable graph
-    // in order to use the nodes we've in the nodes HashMap.
-    let mut g = StableGraph::new();
-
-    // First we build a graph of include dependencies
-    for (file, pf) in files.iter() {
-        let node = match nodes.entry(file.clone()) {
-            hash_map::Entry::Occupied(l) => *l.get(),
-            hash_map::Entry::Vacant(p) => *p.insert(g.add_node(file.clone())),
+// Parses an integer literal of a condition, ignoring its suffixes.
+fn parse_integer(literal: &str) -> Option<i64> {
+    let literal = literal.trim().trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = literal
+        .strip_prefix("0x")
+        .or_else(|| literal.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        i64::from_str_radix(&literal[1..], 8).ok()
+    } else {
+        literal.parse().ok()
+    }
+}
+
+// Splits a condition into identifiers, integers and operators.
+fn condition_tokens(condition: &str) -> Option<Vec<&str>> {
+    const OPERATORS: &[&str] = &[
+        "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "(", ")", "!", "~", "<", ">", "+", "-",
+        "*", "/", "%", "&", "|", "^",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = condition.trim_start();
+    while !rest.is_empty() {
+        let end = if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len())
+        } else {
+            OPERATORS.iter().find(|op| rest.starts_with(**op))?.len()
         };
-        let direct_includes = &pf.direct_includes;
-        for i in direct_includes {
-            let possibilities = guess_file(file, i, all_files);
-            for i in possibilities {
-                if &i != file {
-                    let i = match nodes.entry(i.clone()) {
-                        hash_map::Entry::Occupied(l) => *l.get(),
-                        hash_map::Entry::Vacant(p) => *p.insert(g.add_node(i)),
-                    };
-                    g.add_edge(node, i, 0);
-                } else {
-                    // TODO: add an option to display warning
-                    eprintln!("Warning: possible self inclusion {file:?}");
-                }
-            }
+        tokens.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some(tokens)
+}
+
+// Evaluates the condition of an `#if` directive with the macros defined
+// and undefined by the user.
+//
+// The value of an expression is `None` when it depends on other macros,
+// while an expression which cannot be evaluated is an error.
+struct ConditionEvaluator<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    cfg: &'a MacroExpansionCfg,
+}
+
+// The binary operators, from the lowest to the highest precedence.
+const BINARY_OPERATORS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl ConditionEvaluator<'_> {
+    fn next_if(&mut self, token: &str) -> bool {
+        let found = self.tokens.get(self.pos) == Some(&token);
+        self.pos += found as usize;
+        found
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Option<i64>, ()> {
+        let Some(operators) = BINARY_OPERATORS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self
+            .tokens
+            .get(self.pos)
+            .copied()
+            .filter(|t| operators.contains(t))
+        {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = match (op, left, right) {
+                // A logical operator can be evaluated with a single known operand
+                ("||", Some(l), _) if l != 0 => Some(1),
+                ("||", _, Some(r)) if r != 0 => Some(1),
+                ("&&", Some(0), _) | ("&&", _, Some(0)) => Some(0),
+                (_, Some(l), Some(r)) => Some(match op {
+                    "||" => (l != 0 || r != 0) as i64,
+                    "&&" => (l != 0 && r != 0) as i64,
+                    "|" => l | r,
+                    "^" => l ^ r,
+                    "&" => l & r,
+                    "==" => (l == r) as i64,
+                    "!=" => (l != r) as i64,
+                    "<" => (l < r) as i64,
+                    ">" => (l > r) as i64,
+                    "<=" => (l <= r) as i64,
+                    ">=" => (l >= r) as i64,
+                    "<<" => l.checked_shl(r as u32).ok_or(())?,
+                    ">>" => l.checked_shr(r as u32).ok_or(())?,
+                    "+" => l.wrapping_add(r),
+                    "-" => l.wrapping_sub(r),
+                    "*" => l.wrapping_mul(r),
+                    "/" => l.checked_div(r).ok_or(())?,
+                    _ => l.checked_rem(r).ok_or(())?,
+                }),
+                _ => None,
+            };
         }
+        Ok(left)
     }
 
-    // In order to walk in the graph without issues due to cycles
-    // we replace strong connected components by a unique node
-    // All the paths in a scc finally represents a kind of unique file containing
-    // all the files in the scc.
-    let mut scc = kosaraju_scc(&g);
-    let mut scc_map: HashMap<NodeIndex, HashSet<String>> = HashMap::new();
-    for component in scc.iter_mut() {
-        if component.len() > 1 {
-            // For Firefox, there are only few scc and all of them are pretty small
-            // So no need to take a hammer here (for 'contains' stuff).
-            // TODO: in some case a hammer can be useful: check perf Vec vs HashSet
-            let mut incoming = Vec::new();
-            let mut outgoing = Vec::new();
-            let mut paths = HashSet::new();
-
-            for c in component.iter() {
-                for i in g.neighbors_directed(*c, Direction::Incoming) {
-                    if !component.contains(&i) && !incoming.contains(&i) {
-                        incoming.push(i);
-                    }
-                }
-                for o in g.neighbors_directed(*c, Direction::Outgoing) {
-                    if !component.contains(&o) && !outgoing.contains(&o) {
-                        outgoing.push(o);
-                    }
-                }
+    fn unary(&mut self) -> Result<Option<i64>, ()> {
+        let token = *self.tokens.get(self.pos).ok_or(())?;
+        self.pos += 1;
+        match token {
+            "!" => Ok(self.unary()?.map(|v| (v == 0) as i64)),
+            "~" => Ok(self.unary()?.map(|v| !v)),
+            "-" => Ok(self.unary()?.map(|v| v.wrapping_neg())),
+            "+" => self.unary(),
+            "(" => {
+                let value = self.binary(0)?;
+                self.next_if(")").then_some(value).ok_or(())
             }
-
-            let replacement = g.add_node(PathBuf::from(""));
-            for i in incoming.drain(..) {
-                g.add_edge(i, replacement, 0);
+            "defined" => {
+                let parenthesized = self.next_if("(");
+                let name = *self.tokens.get(self.pos).ok_or(())?;
+                self.pos += 1;
+                if parenthesized && !self.next_if(")") {
+                    return Err(());
+                }
+                Ok(self.cfg.is_defined(name).map(i64::from))
             }
-            for o in outgoing.drain(..) {
-                g.add_edge(replacement, o, 0);
+            _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                parse_integer(token).map(Some).ok_or(())
             }
-            for c in component.drain(..) {
-                let path = g.remove_node(c).unwrap();
-                paths.insert(path.to_str().unwrap().to_string());
-                *nodes.get_mut(&path).unwrap() = replacement;
+            // The invocations of function-like macros are not expanded
+            _ if self.tokens.get(self.pos) == Some(&"(") => Err(()),
+            _ => Ok(self.cfg.value(token)),
+        }
+    }
+}
+
+// Evaluates the condition of a branch of a conditional block, returning
+// `None` when it depends on macros not defined or undefined by the user.
+fn eval_branch(branch: &ConditionalBranch, cfg: &MacroExpansionCfg) -> Option<bool> {
+    match branch.directive.as_str() {
+        "else" => Some(true),
+        "ifdef" => cfg.is_defined(&branch.condition),
+        "ifndef" => cfg.is_defined(&branch.condition).map(|d| !d),
+        "if" | "elif" => {
+            let mut evaluator = ConditionEvaluator {
+                tokens: condition_tokens(&branch.condition)?,
+                pos: 0,
+                cfg,
+            };
+            let value = evaluator.binary(0).ok()?;
+            if evaluator.pos != evaluator.tokens.len() {
+                return None;
             }
+            value.map(|v| v != 0)
+        }
+        _ => None,
+    }
+}
+
+// Returns the line ranges of the branches which are not compiled
+// with the macros defined and undefined by the user.
+fn skipped_branches(
+    conditionals: &[ConditionalBranch],
+    cfg: &MacroExpansionCfg,
+) -> Vec<(usize, usize)> {
+    // Whether a previous branch of the open block at each depth is compiled
+    let mut previous_taken: Vec<Option<bool>> = Vec::new();
+    let mut skipped = Vec::new();
+    for branch in conditionals {
+        if matches!(branch.directive.as_str(), "if" | "ifdef" | "ifndef") {
+            previous_taken.truncate(branch.depth);
+            previous_taken.resize(branch.depth + 1, Some(false));
+        }
+        let Some(previous) = previous_taken.get_mut(branch.depth) else {
+            continue;
+        };
+        let taken = eval_branch(branch, cfg);
+        let skip = *previous == Some(true) || taken == Some(false);
+        *previous = match (*previous, taken) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        };
+        if skip {
+            skipped.push((branch.start_line, branch.end_line));
+        }
+    }
+    skipped
+}
 
-            eprintln!("Warning: possible include cycle:");
-            for p in paths.iter() {
-                eprintln!("  - {p:?}");
+/// Returns the definitions of the macros available in a `C/C++` file,
+/// that is the ones defined in the files it includes, in the file itself
+/// and by the user.
+///
+/// The definitions in the branches of the conditional blocks which are not
+/// compiled, according to the macros defined and undefined by the user,
+/// are ignored.
+pub(crate) fn get_definitions<S: ::std::hash::BuildHasher>(
+    code: &[u8],
+    file: &Path,
+    files: &HashMap<PathBuf, PreprocFile, S>,
+    cfg: &MacroExpansionCfg,
+) -> HashMap<String, MacroDefinition> {
+    let mut definitions = HashMap::new();
+    let mut add = |pf: &PreprocFile| {
+        // The definitions in the branches which are not compiled are ignored
+        let skipped = skipped_branches(&pf.conditionals, cfg);
+        for d in pf.definitions.iter() {
+            if !skipped
+                .iter()
+                .any(|(start, end)| *start < d.line && d.line <= *end)
+            {
+                definitions.insert(d.name.clone(), d.clone());
             }
-            eprintln!();
+        }
+    };
 
-            scc_map.insert(replacement, paths);
+    if let Some(pf) = files.get(file) {
+        // An included file is added before the files including it, which
+        // have more indirect includes since they contain all of its ones
+        let mut includes: Vec<_> = pf
+            .indirect_includes
+            .iter()
+            .filter(|f| Path::new(f) != file)
+            .filter_map(|f| {
+                files
+                    .get(Path::new(f))
+                    .map(|pf| (pf.indirect_includes.len(), f, pf))
+            })
+            .collect();
+        includes.sort_unstable_by(|(len1, f1, _), (len2, f2, _)| (len1, f1).cmp(&(len2, f2)));
+        for (_, _, pf) in includes {
+            add(pf);
+        }
+        add(pf);
+    } else {
+        // The file has not been preprocessed, so get its own definitions
+        let parser = PreprocParser::new(code.to_vec(), file, None);
+        let mut results = PreprocResults::default();
+        preprocess(&parser, file, &mut results);
+        if let Some(pf) = results.files.get(file) {
+            add(pf);
         }
     }
 
-    for (path, node) in nodes {
-        let mut dfs = Dfs::new(&g, node);
-        if let Some(pf) = files.get_mut(&path) {
-            let x_inc = &mut pf.indirect_includes;
-            while let Some(node) = dfs.next(&g) {
-                let w = g.node_weight(node).unwrap();
-                if w == &PathBuf::from("") {
-                    let paths = scc_map.get(&node);
-                    if let Some(paths) = paths {
-                        for p in paths {
-                            x_inc.insert(p.to_string());
-                        }
-                    } else {
-                        eprintln!("DEBUG: {path:?} {node:?}");
-                    }
-                } else {
-                    x_inc.insert(w.to_str().unwrap().to_string());
-                }
+    for u in cfg.undefines.iter() {
+        definitions.remove(u);
+    }
+    for d in cfg.defines.iter() {
+        definitions.insert(d.name.clone(), d.clone());
+    }
+    definitions
+}
+
+/// Constructs a dependency graph of the include directives
+/// in a `C/C++` file.
+///
+/// The dependency graph is built using both preprocessor data and not
+/// extracted from the considered `C/C++` files.
+///
+/// The include directives are searched in the directories of `include_paths`
+/// and, when there are none, they are guessed from the names of the files.
+/// The directives which do not refer to any analyzed file are recorded as
+/// system includes.
+///
+/// Returns the include graph of the files.
+pub fn fix_includes<S: ::std::hash::BuildHasher>(
+    files: &mut HashMap<PathBuf, PreprocFile, S>,
+    all_files: &HashMap<String, Vec<PathBuf>, S>,
+    include_paths: &IncludePaths,
+) -> IncludeGraph {
+    let resolver = IncludeResolver::new(all_files, include_paths);
+    let (include_graph, system_includes) = IncludeGraph::new(files, &resolver);
+
+    for (file, include) in system_includes {
+        if let Some(pf) = files.get_mut(&file) {
+            pf.system_includes.insert(include);
+        }
+    }
+
+    for cycle in include_graph.cycles.iter() {
+        eprintln!("Warning: possible include cycle:");
+        for path in cycle {
+            eprintln!("  - {path:?}");
+        }
+        eprintln!();
+    }
+
+    // A file is part of its own indirect includes
+    let g = &include_graph.graph;
+    for node in g.node_indices() {
+        let path = &g[node];
+        if let Some(pf) = files.get_mut(path) {
+            let mut dfs = Dfs::new(g, node);
+            while let Some(node) = dfs.next(g) {
+                pf.indirect_includes
+                    .insert(g[node].to_str().unwrap().to_string());
             }
         } else {
             eprintln!(
@@ -179,6 +497,168 @@ pub fn fix_includes<S: ::std::hash::BuildHasher>(
             );
         }
     }
+
+    include_graph
+}
+
+// Builds the definition of a macro from a `#define` directive.
+fn macro_definition(node: &Node, identifier: &Node, code: &[u8]) -> MacroDefinition {
+    // Join the continuation lines and the last line of the directive
+    let text = String::from_utf8_lossy(&code[identifier.end_byte()..node.end_byte()]);
+    let text = text.replace("\\\r\n", "\\\n");
+    let text = text
+        .split("\\\n")
+        .enumerate()
+        .map(|(i, line)| if i == 0 { line.trim_end() } else { line.trim() })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // A function-like macro has its parameters right after its name
+    let (parameters, body) = match text.strip_prefix('(').and_then(|t| t.split_once(')')) {
+        Some((parameters, body)) => {
+            let parameters = parameters
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect();
+            (Some(parameters), body)
+        }
+        None => (None, text.as_str()),
+    };
+
+    MacroDefinition {
+        name: identifier.utf8_text(code).unwrap().to_string(),
+        parameters,
+        body: body.trim().to_string(),
+        line: node.start_row() + 1,
+    }
+}
+
+// Splits the first line of a conditional directive into
+// its name and its condition, without comments.
+fn split_directive(node: &Node, code: &[u8]) -> (String, String) {
+    let line = &code[node.start_byte()..node.end_byte()];
+    let line = line.split(|&c| c == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_start().trim_start_matches('#').trim_start();
+    let end = line
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(line.len());
+    let (directive, condition) = line.split_at(end);
+    let condition = ["//", "/*"]
+        .iter()
+        .filter_map(|comment| condition.find(comment))
+        .min()
+        .map_or(condition, |end| &condition[..end]);
+    (directive.to_string(), condition.trim().to_string())
+}
+
+// Returns the macros referenced by the condition of a directive.
+fn condition_macros(condition: &str) -> impl Iterator<Item = &str> {
+    condition
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| {
+            word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && *word != "defined"
+                && !word.starts_with("__has_")
+        })
+}
+
+// Returns the branches of a conditional block, that is
+// the `#if` node itself and its `#elif` and `#else` children.
+fn block_branches<'a>(block: &Node<'a>) -> Vec<Node<'a>> {
+    let mut branches = vec![*block];
+    branches.extend(block.children().filter(|c| {
+        matches!(
+            c.kind_id().into(),
+            Preproc::PreprocElif | Preproc::PreprocElse
+        )
+    }));
+    branches
+}
+
+// Returns the conditional block of a branch.
+fn branch_block<'a>(branch: &Node<'a>) -> Node<'a> {
+    if branch.kind_id() == Preproc::PreprocIf {
+        *branch
+    } else {
+        branch.parent().unwrap()
+    }
+}
+
+// Returns the branch containing a conditional block, if any.
+fn enclosing_branch<'a>(block: &Node<'a>) -> Option<Node<'a>> {
+    let mut parent = block.parent();
+    while let Some(node) = parent {
+        if matches!(
+            node.kind_id().into(),
+            Preproc::PreprocIf | Preproc::PreprocElif | Preproc::PreprocElse
+        ) {
+            return Some(node);
+        }
+        parent = node.parent();
+    }
+    None
+}
+
+// Returns the macros gating a branch of a conditional block.
+fn gating_macros(branch: &Node, code: &[u8]) -> BTreeSet<String> {
+    let block = branch_block(branch);
+    let mut macros = BTreeSet::new();
+    for b in block_branches(&block) {
+        let (_, condition) = split_directive(&b, code);
+        macros.extend(condition_macros(&condition).map(|m| m.to_string()));
+        if b.id() == branch.id() {
+            break;
+        }
+    }
+    if let Some(outer) = enclosing_branch(&block) {
+        macros.extend(gating_macros(&outer, code));
+    }
+    macros
+}
+
+// Returns the branches of a conditional block with their line ranges.
+fn conditional_branches(block: &Node, code: &[u8]) -> Vec<ConditionalBranch> {
+    // The block ends with the new line of its `#endif`
+    let (end_row, end_column) = block.end_position();
+    let endif_line = if end_column == 0 {
+        end_row
+    } else {
+        end_row + 1
+    };
+
+    let mut depth = 0;
+    let mut outer = enclosing_branch(block);
+    while let Some(branch) = outer {
+        depth += 1;
+        outer = enclosing_branch(&branch_block(&branch));
+    }
+
+    let branches = block_branches(block);
+    let mut result = Vec::with_capacity(branches.len());
+    for (i, branch) in branches.iter().enumerate() {
+        let (directive, condition) = split_directive(branch, code);
+        let end_line = branches
+            .get(i + 1)
+            .map_or(endif_line, |next| next.start_row());
+        result.push(ConditionalBranch {
+            directive,
+            condition,
+            macros: gating_macros(branch, code).into_iter().collect(),
+            start_line: branch.start_row() + 1,
+            end_line,
+            depth,
+        });
+    }
+    result
+}
+
+// Removes the blanks around the path of an include directive.
+fn trim_include(file: &[u8]) -> Option<String> {
+    let start = file.iter().position(|&c| c != b' ' && c != b'\t')?;
+    let end = file.iter().rposition(|&c| c != b' ' && c != b'\t')?;
+    String::from_utf8(file[start..=end].to_vec()).ok()
 }
 
 /// Extracts preprocessor data from a `C/C++` file
@@ -217,27 +697,279 @@ pub fn preprocess(parser: &PreprocParser, path: &Path, results: &mut PreprocResu
                     let r#macro = identifier.utf8_text(code).unwrap();
                     if !is_specials(r#macro) {
                         file_result.macros.insert(r#macro.to_string());
+                        if id == Preproc::Define {
+                            file_result.definitions.push(macro_definition(
+                                &node,
+                                &identifier,
+                                code,
+                            ));
+                        }
                     }
                 }
             }
+            Preproc::PreprocIf => {
+                file_result
+                    .conditionals
+                    .extend(conditional_branches(&node, code));
+            }
             Preproc::PreprocInclude => {
                 cursor.reset(&node);
                 cursor.goto_first_child();
                 let file = cursor.node();
 
-                if file.kind_id() == Preproc::StringLiteral {
+                let (file, angle) = match Preproc::from(file.kind_id()) {
                     // remove the starting/ending double quote
-                    let file = &code[file.start_byte() + 1..file.end_byte() - 1];
-                    let start = file.iter().position(|&c| c != b' ' && c != b'\t').unwrap();
-                    let end = file.iter().rposition(|&c| c != b' ' && c != b'\t').unwrap();
-                    let file = &file[start..=end];
-                    let file = String::from_utf8(file.to_vec()).unwrap();
-                    file_result.direct_includes.insert(file);
+                    Preproc::StringLiteral => {
+                        (&code[file.start_byte() + 1..file.end_byte() - 1], false)
+                    }
+                    Preproc::LT if cursor.goto_next_sibling() => {
+                        let path = cursor.node();
+                        (&code[path.start_byte()..path.end_byte()], true)
+                    }
+                    _ => continue,
+                };
+                if let Some(file) = trim_include(file) {
+                    if angle {
+                        file_result.angle_includes.insert(file);
+                    } else {
+                        file_result.direct_includes.insert(file);
+                    }
                 }
             }
             _ => {}
         }
     }
 
+    file_result.definitions.sort_by_key(|d| d.line);
+    file_result.conditionals.sort_by_key(|c| c.start_line);
+
     results.files.insert(path.to_path_buf(), file_result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocess_code(code: &str) -> PreprocFile {
+        let path = PathBuf::from("foo.c");
+        let parser = PreprocParser::new(code.as_bytes().to_vec(), &path, None);
+        let mut results = PreprocResults::default();
+        preprocess(&parser, &path, &mut results);
+        results.files.remove(&path).unwrap()
+    }
+
+    #[test]
+    fn preproc_definitions() {
+        let file = preprocess_code(
+            "#define A 1\n\
+             #define MAX(a, b) ((a) > (b) ? (a) : (b))\n\
+             #define EMPTY()\n\
+             #define LONG(x) \\\n  do { x; } \\\n  while (0)\n\
+             #undef A\n",
+        );
+
+        insta::assert_json_snapshot!(file.definitions, @r#"
+            [
+              {
+                "name": "A",
+                "parameters": null,
+                "body": "1",
+                "line": 1
+              },
+              {
+                "name": "MAX",
+                "parameters": [
+                  "a",
+                  "b"
+                ],
+                "body": "((a) > (b) ? (a) : (b))",
+                "line": 2
+              },
+              {
+                "name": "EMPTY",
+                "parameters": [],
+                "body": "",
+                "line": 3
+              },
+              {
+                "name": "LONG",
+                "parameters": [
+                  "x"
+                ],
+                "body": "do { x; } while (0)",
+                "line": 4
+              }
+            ]
+            "#);
+    }
+
+    #[test]
+    fn preproc_conditionals() {
+        let file = preprocess_code(
+            "#ifdef DEBUG // debug builds\n\
+             int a;\n\
+             #if defined(LINUX) && VERSION > 2\n\
+             int b;\n\
+             #endif\n\
+             #elif RELEASE\n\
+             int c;\n\
+             #else\n\
+             int d;\n\
+             #endif\n",
+        );
+
+        insta::assert_json_snapshot!(file.conditionals, @r#"
+            [
+              {
+                "directive": "ifdef",
+                "condition": "DEBUG",
+                "macros": [
+                  "DEBUG"
+                ],
+                "start_line": 1,
+                "end_line": 5,
+                "depth": 0
+              },
+              {
+                "directive": "if",
+                "condition": "defined(LINUX) && VERSION > 2",
+                "macros": [
+                  "DEBUG",
+                  "LINUX",
+                  "VERSION"
+                ],
+                "start_line": 3,
+                "end_line": 5,
+                "depth": 1
+              },
+              {
+                "directive": "elif",
+                "condition": "RELEASE",
+                "macros": [
+                  "DEBUG",
+                  "RELEASE"
+                ],
+                "start_line": 6,
+                "end_line": 7,
+                "depth": 0
+              },
+              {
+                "directive": "else",
+                "condition": "",
+                "macros": [
+                  "DEBUG",
+                  "RELEASE"
+                ],
+                "start_line": 8,
+                "end_line": 10,
+                "depth": 0
+              }
+            ]
+            "#);
+    }
+
+    #[test]
+    fn preproc_expansion_definitions() {
+        let mut cfg = MacroExpansionCfg::default();
+        cfg.define("A=2");
+        cfg.define("C");
+        cfg.define("D(x, y)=x + y");
+
+        let code = b"#define A 1\n#define B(x) x\n";
+        let definitions = get_definitions(code, Path::new("foo.c"), &HashMap::new(), &cfg);
+        let mut definitions: Vec<_> = definitions
+            .values()
+            .map(|d| (d.name.as_str(), d.parameters.clone(), d.body.as_str()))
+            .collect();
+        definitions.sort();
+
+        assert_eq!(
+            definitions,
+            [
+                ("A", None, "2"),
+                ("B", Some(vec!["x".to_string()]), "x"),
+                ("C", None, "1"),
+                ("D", Some(vec!["x".to_string(), "y".to_string()]), "x + y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn preproc_conditional_definitions() {
+        let code = b"#ifdef WIDE\n\
+                     #define CHAR wchar_t\n\
+                     #else\n\
+                     #define CHAR char\n\
+                     #endif\n\
+                     #if defined(DEBUG) && LEVEL > 1\n\
+                     #define LOG(x) log(x)\n\
+                     #elif LEVEL == 0 || defined(QUIET)\n\
+                     #define LOG(x)\n\
+                     #else\n\
+                     #define LOG(x) print(x)\n\
+                     #endif\n";
+        let bodies = |cfg: &MacroExpansionCfg| {
+            let definitions = get_definitions(code, Path::new("foo.c"), &HashMap::new(), cfg);
+            (
+                definitions["CHAR"].body.clone(),
+                definitions["LOG"].body.clone(),
+            )
+        };
+
+        // The last definitions win when the conditions cannot be evaluated
+        let mut cfg = MacroExpansionCfg::default();
+        assert_eq!(bodies(&cfg), ("char".to_owned(), "print(x)".to_owned()));
+
+        cfg.define("WIDE");
+        cfg.define("DEBUG");
+        cfg.define("LEVEL=2");
+        assert_eq!(bodies(&cfg), ("wchar_t".to_owned(), "log(x)".to_owned()));
+
+        let mut cfg = MacroExpansionCfg::default();
+        cfg.undefine("WIDE");
+        cfg.define("LEVEL=0");
+        assert_eq!(bodies(&cfg), ("char".to_owned(), "".to_owned()));
+
+        let mut cfg = MacroExpansionCfg::default();
+        cfg.define("WIDE");
+        cfg.undefine("DEBUG");
+        cfg.define("LEVEL=3");
+        cfg.undefine("QUIET");
+        assert_eq!(bodies(&cfg), ("wchar_t".to_owned(), "print(x)".to_owned()));
+
+        // An undefined macro has no definition
+        cfg.undefine("CHAR");
+        let definitions = get_definitions(code, Path::new("foo.c"), &HashMap::new(), &cfg);
+        assert!(!definitions.contains_key("CHAR"));
+    }
+    #[test]
+    fn preproc_included_definitions() {
+        let file = |code: &str, includes: &[&str]| {
+            let mut pf = preprocess_code(code);
+            pf.indirect_includes = includes.iter().map(|f| f.to_string()).collect();
+            pf
+        };
+
+        // `config.h` includes `base.h` and redefines its macro,
+        // whatever the order of the indirect includes
+        for _ in 0..10 {
+            let files = HashMap::from([
+                (
+                    PathBuf::from("base.h"),
+                    file("#define SIZE 1\n", &["base.h"]),
+                ),
+                (
+                    PathBuf::from("config.h"),
+                    file("#undef SIZE\n#define SIZE 2\n", &["config.h", "base.h"]),
+                ),
+                (
+                    PathBuf::from("foo.c"),
+                    file("int a[SIZE];\n", &["foo.c", "config.h", "base.h"]),
+                ),
+            ]);
+            let cfg = MacroExpansionCfg::default();
+            let definitions = get_definitions(b"", Path::new("foo.c"), &files, &cfg);
+            assert_eq!(definitions["SIZE"].body, "2");
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::Serialize;
+use termcolor::{Color, ColorChoice, StandardStream};
+use tree_sitter::{Query, QueryCursor, QueryErrorKind, StreamingIterator};
+
+use crate::find::FoundNode;
+use crate::langs::LANG;
+use crate::node::Node;
+use crate::tools::{color, intense_color};
+use crate::traits::*;
+
+/// An error found in the source of a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryError {
+    /// The line of the query containing the error
+    pub line: usize,
+    /// The column of the query containing the error
+    pub column: usize,
+    /// The description of the error
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid query at ({}, {}): {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// A node captured by a query.
+#[derive(Clone, Debug, Serialize)]
+pub struct QueryCapture {
+    /// The name of the capture, without the leading `@`
+    pub name: String,
+    /// The captured node
+    #[serde(flatten)]
+    pub node: FoundNode,
+}
+
+/// A match of a query.
+#[derive(Clone, Debug, Serialize)]
+pub struct QueryMatch {
+    /// The index of the pattern of the query which matched
+    pub pattern: usize,
+    /// The nodes captured by the pattern
+    pub captures: Vec<QueryCapture>,
+}
+
+// A query compiled for a language.
+#[derive(Clone)]
+enum Compiled {
+    Query(Arc<Query>),
+    // The query refers to node types or fields of another language,
+    // or the grammar of the language is not supported by the query engine
+    Incompatible(QueryError),
+    Invalid(QueryError),
+}
+
+// The queries compiled for each language, so that a query is compiled
+// only once when it is run over many files.
+type QueryCache = HashMap<(&'static str, String), Compiled>;
+
+static QUERIES: OnceLock<Mutex<QueryCache>> = OnceLock::new();
+
+fn query_error_kind(kind: &QueryErrorKind) -> &'static str {
+    match kind {
+        QueryErrorKind::Syntax => "syntax error at",
+        QueryErrorKind::NodeType => "unknown node type",
+        QueryErrorKind::Field => "unknown field",
+        QueryErrorKind::Capture => "unknown capture",
+        QueryErrorKind::Predicate => "invalid predicate",
+        QueryErrorKind::Structure => "impossible pattern",
+        QueryErrorKind::Language => "incompatible language",
+    }
+}
+
+/// Checks whether a filter is a tree-sitter query rather than a node type.
+///
+/// Queries are S-expressions, so they start with a parenthesis
+/// or with a bracket.
+pub fn is_query(filter: &str) -> bool {
+    filter.trim_start().starts_with(['(', '['])
+}
+
+// Compiles a query for a language, or returns the cached one.
+fn compile(lang: LANG, source: &str) -> Compiled {
+    let cache = QUERIES.get_or_init(Mutex::default);
+    let key = (lang.get_name(), source.to_owned());
+    if let Some(compiled) = cache.lock().unwrap().get(&key) {
+        return compiled.clone();
+    }
+
+    let compiled = match Query::new(&lang.get_ts_language(), source) {
+        Ok(query) => Compiled::Query(Arc::new(query)),
+        Err(e) => {
+            let error = QueryError {
+                line: e.row + 1,
+                column: e.column + 1,
+                message: format!("{} {}", query_error_kind(&e.kind), e.message),
+            };
+            // The unknown node types and fields may be the ones of another language,
+            // whereas an impossible pattern is a mistake in the query
+            match e.kind {
+                QueryErrorKind::NodeType | QueryErrorKind::Field | QueryErrorKind::Language => {
+                    Compiled::Incompatible(error)
+                }
+                _ => Compiled::Invalid(error),
+            }
+        }
+    };
+    cache.lock().unwrap().insert(key, compiled.clone());
+    compiled
+}
+
+/// Checks that a tree-sitter query is valid for at least one language.
+///
+/// Returns the [`QueryError`] found for the first language otherwise.
+///
+/// [`QueryError`]: struct.QueryError.html
+pub fn check_query(source: &str) -> Result<(), QueryError> {
+    let mut first_error = None;
+    for lang in LANG::into_enum_iter() {
+        match compile(lang, source) {
+            Compiled::Query(_) => return Ok(()),
+            Compiled::Incompatible(e) | Compiled::Invalid(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+// Runs a compiled query on a code.
+fn run_query<T: ParserTrait>(parser: &T, query: &Query) -> Vec<QueryMatch> {
+    let names = query.capture_names();
+    let code = parser.get_code();
+    let root = parser.get_root();
+
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, root.0, code);
+    let mut res = Vec::new();
+    while let Some(m) = matches.next() {
+        res.push(QueryMatch {
+            pattern: m.pattern_index,
+            captures: m
+                .captures
+                .iter()
+                .map(|c| QueryCapture {
+                    name: names[c.index as usize].to_owned(),
+                    node: FoundNode::new(&Node(c.node), parser),
+                })
+                .collect(),
+        });
+    }
+    res
+}
+
+/// Runs a tree-sitter query on a code.
+///
+/// Predicates such as `#eq?` and `#match?` are evaluated,
+/// so only the matches satisfying them are returned.
+///
+/// Returns a [`QueryError`] when the query is not valid
+/// for the language of the code.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+///
+/// use rust_code_analysis::{query, ParserTrait, RustParser};
+///
+/// let source_code = "fn main() { foo(); bar(); }";
+/// let parser = RustParser::new(source_code.as_bytes().to_vec(), Path::new("foo.rs"), None);
+///
+/// let matches = query(
+///     &parser,
+///     r#"(call_expression function: (identifier) @f (#eq? @f "bar"))"#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].captures[0].name, "f");
+/// assert_eq!(matches[0].captures[0].node.text, "bar");
+/// ```
+///
+/// [`QueryError`]: struct.QueryError.html
+pub fn query<T: ParserTrait>(parser: &T, source: &str) -> Result<Vec<QueryMatch>, QueryError> {
+    match compile(parser.get_language(), source) {
+        Compiled::Query(query) => Ok(run_query(parser, &query)),
+        Compiled::Incompatible(e) | Compiled::Invalid(e) => Err(e),
+    }
+}
+
+/// Runs a tree-sitter query on a code, if the query is written
+/// for the language of the code.
+///
+/// Returns no match when the query refers to node types or fields
+/// which do not exist in the language of the code, and a [`QueryError`]
+/// when the query is not valid at all.
+///
+/// [`QueryError`]: struct.QueryError.html
+pub fn query_if_compatible<T: ParserTrait>(
+    parser: &T,
+    source: &str,
+) -> Result<Vec<QueryMatch>, QueryError> {
+    match compile(parser.get_language(), source) {
+        Compiled::Query(query) => Ok(run_query(parser, &query)),
+        Compiled::Incompatible(_) => Ok(Vec::new()),
+        Compiled::Invalid(e) => Err(e),
+    }
+}
+
+/// Dumps the matches of a query.
+///
+/// Only the matches whose first capture starts between
+/// `line_start` and `line_end` are dumped.
+pub fn dump_query_matches(
+    matches: &[QueryMatch],
+    line_start: Option<usize>,
+    line_end: Option<usize>,
+) -> std::io::Result<()> {
+    let stdout = StandardStream::stdout(ColorChoice::Always);
+    let mut stdout = stdout.lock();
+
+    for m in matches {
+        let Some(line) = m.captures.first().map(|c| c.node.start_line) else {
+            continue;
+        };
+        if line_start.is_some_and(|start| line < start) || line_end.is_some_and(|end| line > end) {
+            continue;
+        }
+
+        color(&mut stdout, Color::White)?;
+        writeln!(stdout, "match of pattern {}", m.pattern)?;
+
+        for (i, capture) in m.captures.iter().enumerate() {
+            let pref = if i + 1 == m.captures.len() {
+                "   `- "
+            } else {
+                "   |- "
+            };
+            color(&mut stdout, Color::Blue)?;
+            write!(stdout, "{pref}")?;
+
+            intense_color(&mut stdout, Color::Magenta)?;
+            write!(stdout, "@{} ", capture.name)?;
+
+            let node = &capture.node;
+            intense_color(&mut stdout, Color::Yellow)?;
+            write!(stdout, "{{{}}} ", node.kind)?;
+
+            color(&mut stdout, Color::Green)?;
+            write!(
+                stdout,
+                "({}, {}) to ({}, {})",
+                node.start_line, node.start_column, node.end_line, node.end_column
+            )?;
+
+            if node.start_line == node.end_line {
+                color(&mut stdout, Color::White)?;
+                write!(stdout, ": ")?;
+
+                intense_color(&mut stdout, Color::Red)?;
+                write!(stdout, "{}", node.text)?;
+            }
+            writeln!(stdout)?;
+        }
+    }
+
+    color(&mut stdout, Color::White)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{PythonParser, RustParser};
+
+    use super::*;
+
+    #[test]
+    fn query_with_predicate() {
+        let source = "fn main() { let a = malloc(4); free(a); let b = malloc(2); }";
+        let parser = RustParser::new(source.as_bytes().to_vec(), Path::new("foo.rs"), None);
+
+        let matches = query(
+            &parser,
+            r#"(call_expression function: (identifier) @f (#eq? @f "malloc")) @call"#,
+        )
+        .unwrap();
+
+        assert_eq!(matches.len(), 2);
+        for m in &matches {
+            let names: Vec<_> = m.captures.iter().map(|c| c.name.as_str()).collect();
+            assert_eq!(names, ["call", "f"]);
+            assert_eq!(m.captures[1].node.text, "malloc");
+            assert_eq!(m.captures[1].node.kind, "identifier");
+        }
+        assert_eq!(matches[0].captures[0].node.text, "malloc(4)");
+        assert_eq!(matches[0].captures[0].node.start_line, 1);
+        assert_eq!(matches[0].captures[0].node.start_column, 21);
+        assert_eq!(matches[0].captures[0].node.start_byte, 20);
+        assert_eq!(matches[0].captures[0].node.end_byte, 29);
+    }
+
+    #[test]
+    fn query_invalid() {
+        let parser = PythonParser::new(b"a = 1".to_vec(), Path::new("foo.py"), None);
+
+        let err = query(&parser, "(not_a_python_node) @a").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 2));
+        assert_eq!(err.message, r#"unknown node type "not_a_python_node""#);
+    }
+
+    #[test]
+    fn query_other_language() {
+        let source = "(function_definition name: (identifier) @name)";
+        let python = PythonParser::new(b"def f(): pass".to_vec(), Path::new("foo.py"), None);
+        let rust = RustParser::new(b"fn f() {}".to_vec(), Path::new("foo.rs"), None);
+
+        assert_eq!(query_if_compatible(&python, source).unwrap().len(), 1);
+        assert!(query(&rust, source).is_err());
+        assert!(query_if_compatible(&rust, source).unwrap().is_empty());
+        assert!(check_query(source).is_ok());
+
+        assert!(query_if_compatible(&rust, "(function_item").is_err());
+        assert!(query_if_compatible(&rust, "(identifier (identifier))").is_err());
+        assert!(check_query("(function_item").is_err());
+        assert!(check_query("(not_a_node_of_any_language) @a").is_err());
+    }
+
+    #[test]
+    fn query_filter() {
+        assert!(is_query("(identifier) @id"));
+        assert!(is_query(" [(string) (integer)] @lit"));
+        assert!(!is_query("call"));
+        assert!(!is_query("identifier"));
+    }
+}
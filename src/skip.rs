@@ -0,0 +1,155 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Number of bytes read from the beginning of a file to detect
+// whether it has been generated or minified.
+const SAMPLE_SIZE: u64 = 64 * 1024;
+
+// Number of lines at the beginning of a file searched for
+// generated markers.
+const HEADER_LINES: usize = 16;
+
+/// Criteria used to skip generated, minified and oversized files.
+#[derive(Clone, Debug)]
+pub struct SkipCfg {
+    /// The maximum size of a file in bytes
+    ///
+    /// If `None`, files are never skipped because of their size
+    pub max_size: Option<u64>,
+    /// The maximum average length of the lines of a file
+    ///
+    /// If `None`, files are never considered as minified
+    pub max_avg_line_length: Option<usize>,
+    /// Markers which identify a generated file when found
+    /// in its first lines
+    pub generated_markers: Vec<String>,
+}
+
+impl Default for SkipCfg {
+    fn default() -> Self {
+        Self {
+            max_size: Some(1024 * 1024),
+            max_avg_line_length: Some(250),
+            generated_markers: vec!["@generated".to_owned(), "DO NOT EDIT".to_owned()],
+        }
+    }
+}
+
+/// The reason why a file has been skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file is larger than the maximum size, in bytes
+    TooLarge(u64),
+    /// The average length of the lines of the file
+    /// is larger than the maximum one
+    Minified(usize),
+    /// The file contains the generated marker
+    Generated(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooLarge(size) => write!(f, "too large ({size} bytes)"),
+            Self::Minified(len) => write!(f, "minified (average line length {len})"),
+            Self::Generated(marker) => write!(f, "generated (contains {marker:?})"),
+        }
+    }
+}
+
+/// Checks whether a file must be skipped.
+///
+/// Only the beginning of the file is read, so oversized files
+/// are detected without loading them.
+///
+/// Returns the reason why a file must be skipped, or `None`
+/// if the file must be analyzed.
+pub fn skip_reason(path: &Path, cfg: &SkipCfg) -> std::io::Result<Option<SkipReason>> {
+    let size = path.metadata()?.len();
+    if let Some(max_size) = cfg.max_size
+        && size > max_size
+    {
+        return Ok(Some(SkipReason::TooLarge(size)));
+    }
+
+    let mut sample = Vec::new();
+    File::open(path)?
+        .take(SAMPLE_SIZE)
+        .read_to_end(&mut sample)?;
+
+    Ok(skip_code_reason(&sample, cfg))
+}
+
+/// Checks whether a code must be skipped because it has been
+/// generated or minified.
+///
+/// Returns the reason why a code must be skipped, or `None`
+/// if the code must be analyzed.
+pub fn skip_code_reason(code: &[u8], cfg: &SkipCfg) -> Option<SkipReason> {
+    let header = code
+        .split(|c| *c == b'\n')
+        .take(HEADER_LINES)
+        .map(String::from_utf8_lossy);
+    for line in header {
+        if let Some(marker) = cfg
+            .generated_markers
+            .iter()
+            .find(|marker| line.contains(marker.as_str()))
+        {
+            return Some(SkipReason::Generated(marker.clone()));
+        }
+    }
+
+    let max_avg_line_length = cfg.max_avg_line_length?;
+    let (count, length) = code
+        .split(|c| *c == b'\n')
+        .map(|line| line.trim_ascii())
+        .filter(|line| !line.is_empty())
+        .fold((0, 0), |(count, length), line| {
+            (count + 1, length + line.len())
+        });
+    if count == 0 {
+        return None;
+    }
+
+    let avg_line_length = length / count;
+    (avg_line_length > max_avg_line_length).then_some(SkipReason::Minified(avg_line_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_generated() {
+        let code = b"// Generated by the protocol buffer compiler.  DO NOT EDIT!\nint a;\n";
+        assert_eq!(
+            skip_code_reason(code, &SkipCfg::default()),
+            Some(SkipReason::Generated("DO NOT EDIT".to_owned()))
+        );
+
+        let code = b"/**\n * @generated\n */\nconst a = 1;\n";
+        assert_eq!(
+            skip_code_reason(code, &SkipCfg::default()),
+            Some(SkipReason::Generated("@generated".to_owned()))
+        );
+    }
+
+    #[test]
+    fn skip_minified() {
+        let code = format!("var a={};\n", "b+".repeat(300)).into_bytes();
+        assert!(matches!(
+            skip_code_reason(&code, &SkipCfg::default()),
+            Some(SkipReason::Minified(_))
+        ));
+    }
+
+    #[test]
+    fn keep_regular_code() {
+        let code = b"fn main() {\n    println!(\"Hello\");\n}\n\n\n";
+        assert_eq!(skip_code_reason(code, &SkipCfg::default()), None);
+        assert_eq!(skip_code_reason(b"", &SkipCfg::default()), None);
+    }
+}
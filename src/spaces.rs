@@ -1,5 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use regex::bytes::Regex;
 use serde::Serialize;
 use std::fmt;
 use std::path::{Path, PathBuf};
@@ -9,16 +10,22 @@ use crate::node::Node;
 
 use crate::abc::{self, Abc};
 use crate::cognitive::{self, Cognitive};
+use crate::comments::commented_code;
+use crate::coverage;
 use crate::cyclomatic::{self, Cyclomatic};
+use crate::docs::{self, Docs};
 use crate::exit::{self, Exit};
 use crate::getter::Getter;
 use crate::halstead::{self, Halstead, HalsteadMaps};
+use crate::ifdef::{self, Ifdef};
 use crate::loc::{self, Loc};
+use crate::markers;
 use crate::mi::{self, Mi};
 use crate::nargs::{self, NArgs};
 use crate::nom::{self, Nom};
 use crate::npa::{self, Npa};
 use crate::npm::{self, Npm};
+use crate::test_code::{self, is_test_path, is_test_space, test_item_lines};
 use crate::wmc::{self, Wmc};
 
 use crate::dump_metrics::*;
@@ -95,6 +102,21 @@ pub struct CodeMetrics {
     /// `Npa` data
     #[serde(skip_serializing_if = "npa::Stats::is_disabled")]
     pub npa: npa::Stats,
+    /// `Docs` data
+    #[serde(skip_serializing_if = "docs::Stats::is_disabled")]
+    pub docs: docs::Stats,
+    /// `Ifdef` data
+    #[serde(skip_serializing_if = "ifdef::Stats::is_disabled")]
+    pub ifdef: ifdef::Stats,
+    /// `Coverage` data
+    #[serde(skip_serializing_if = "coverage::Stats::is_disabled")]
+    pub coverage: coverage::Stats,
+    /// `TestCode` data
+    #[serde(skip_serializing_if = "test_code::Stats::is_disabled")]
+    pub test_code: test_code::Stats,
+    /// `Markers` data
+    #[serde(skip_serializing_if = "markers::Stats::is_empty")]
+    pub markers: markers::Stats,
 }
 
 impl fmt::Display for CodeMetrics {
@@ -124,6 +146,9 @@ impl CodeMetrics {
         self.wmc.merge(&other.wmc);
         self.npm.merge(&other.npm);
         self.npa.merge(&other.npa);
+        self.docs.merge(&other.docs);
+        self.ifdef.merge(&other.ifdef);
+        self.markers.merge(&other.markers);
     }
 }
 
@@ -141,6 +166,9 @@ pub struct FuncSpace {
     pub end_line: usize,
     /// The space kind
     pub kind: SpaceKind,
+    /// If `true`, the space is test code
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_test: bool,
     /// All subspaces contained in a function space
     pub spaces: Vec<FuncSpace>,
     /// All metrics of a function space
@@ -166,6 +194,7 @@ impl FuncSpace {
             spaces: Vec::new(),
             metrics: CodeMetrics::default(),
             kind,
+            is_test: false,
             start_line: start_position,
             end_line: end_position,
         }
@@ -223,6 +252,8 @@ fn compute_sum(state: &mut State) {
     state.space.metrics.wmc.compute_sum();
     state.space.metrics.npm.compute_sum();
     state.space.metrics.npa.compute_sum();
+    state.space.metrics.docs.compute_sum();
+    state.space.metrics.ifdef.compute_sum();
 }
 
 fn finalize<T: ParserTrait>(state_stack: &mut Vec<State>, diff_level: usize) {
@@ -284,6 +315,50 @@ struct State<'a> {
 /// metrics(&parser, &path).unwrap();
 /// ```
 pub fn metrics<'a, T: ParserTrait>(parser: &'a T, path: &'a Path) -> Option<FuncSpace> {
+    metrics_with_opts(parser, path, &MetricsOpts::default())
+}
+
+/// Options changing how the metrics of a code are computed.
+///
+/// The default options compute the metrics as [`metrics`] does.
+///
+/// [`metrics`]: fn.metrics.html
+#[derive(Clone, Debug, Default)]
+pub struct MetricsOpts {
+    markers: Option<Regex>,
+    commented_code: bool,
+    root: Option<PathBuf>,
+}
+
+impl MetricsOpts {
+    /// Counts the given markers in place of the default ones.
+    pub fn markers(mut self, markers: &[String]) -> Self {
+        self.markers = (!markers.is_empty()).then(|| markers::marker_regex(markers));
+        self
+    }
+
+    /// Counts the comment lines containing commented-out code,
+    /// which requires parsing the text of the comments.
+    pub fn commented_code(mut self, commented_code: bool) -> Self {
+        self.commented_code = commented_code;
+        self
+    }
+
+    /// Looks for the test directories only below a root directory,
+    /// as the analyzed one, when checking whether a file is a test file.
+    pub fn root(mut self, root: &Path) -> Self {
+        self.root = Some(root.to_path_buf());
+        self
+    }
+}
+
+/// Returns all function spaces data of a code, computed
+/// with the given options.
+pub fn metrics_with_opts<'a, T: ParserTrait>(
+    parser: &'a T,
+    path: &'a Path,
+    opts: &MetricsOpts,
+) -> Option<FuncSpace> {
     let code = parser.get_code();
     let node = parser.get_root();
     let mut cursor = node.cursor();
@@ -295,6 +370,32 @@ pub fn metrics<'a, T: ParserTrait>(parser: &'a T, path: &'a Path) -> Option<Func
     // Three type of nesting info: conditionals, functions and lambdas
     let mut nesting_map = HashMap::<usize, (usize, usize, usize)>::default();
     nesting_map.insert(node.id(), (0, 0, 0));
+    let commented_code: HashSet<usize> = if opts.commented_code {
+        commented_code(parser).iter().map(|n| n.id()).collect()
+    } else {
+        HashSet::new()
+    };
+    let language = parser.get_language();
+    let test_path = is_test_path(
+        match opts
+            .root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+        {
+            // A file analyzed alone is its own root
+            Some(relative) if relative.as_os_str().is_empty() => {
+                path.file_name().map_or(path, Path::new)
+            }
+            Some(relative) => relative,
+            None => path,
+        },
+    );
+    // The lines of the test items which are not spaces
+    let mut test_items: Vec<(usize, usize)> = Vec::new();
+    let marker_regex = opts
+        .markers
+        .as_ref()
+        .unwrap_or_else(|| markers::default_marker_regex());
     stack.push((node, 0));
 
     while let Some((node, level)) = stack.pop() {
@@ -309,14 +410,31 @@ pub fn metrics<'a, T: ParserTrait>(parser: &'a T, path: &'a Path) -> Option<Func
         let unit = kind == SpaceKind::Unit;
 
         let new_level = if func_space {
+            let mut space = FuncSpace::new::<T::Getter>(&node, code, kind);
+            // The subspaces of a test space are test code too
+            space.is_test = test_path
+                || state_stack.last().is_some_and(|state| state.space.is_test)
+                || is_test_space(language, &node, code);
+            if opts.commented_code {
+                space.metrics.loc.enable_cloc_code();
+            }
             let state = State {
-                space: FuncSpace::new::<T::Getter>(&node, code, kind),
+                space,
                 halstead_maps: HalsteadMaps::new(),
             };
             state_stack.push(state);
             last_level = level + 1;
             last_level
         } else {
+            // The items nested in a test item are already part of it
+            if !test_path
+                && let Some((start, end)) = test_item_lines(language, &node, code)
+                && !test_items
+                    .last()
+                    .is_some_and(|&(first, last)| first <= start && end <= last)
+            {
+                test_items.push((start, end));
+            }
             level
         };
 
@@ -326,12 +444,18 @@ pub fn metrics<'a, T: ParserTrait>(parser: &'a T, path: &'a Path) -> Option<Func
             T::Cyclomatic::compute(&node, &mut last.metrics.cyclomatic);
             T::Halstead::compute(&node, code, &mut state.halstead_maps);
             T::Loc::compute(&node, &mut last.metrics.loc, func_space, unit);
+            if commented_code.contains(&node.id()) {
+                last.metrics.loc.add_commented_code(&node);
+            }
             T::Nom::compute(&node, &mut last.metrics.nom);
             T::NArgs::compute(&node, &mut last.metrics.nargs);
             T::Exit::compute(&node, &mut last.metrics.nexits);
             T::Abc::compute(&node, &mut last.metrics.abc);
             T::Npm::compute(&node, &mut last.metrics.npm);
             T::Npa::compute(&node, &mut last.metrics.npa);
+            T::Docs::compute(&node, code, &mut last.metrics.docs);
+            T::Ifdef::compute(&node, code, &mut last.metrics.ifdef);
+            markers::compute::<T::Checker>(&node, code, marker_regex, &mut last.metrics.markers);
         }
 
         cursor.reset(&node);
@@ -352,6 +476,9 @@ pub fn metrics<'a, T: ParserTrait>(parser: &'a T, path: &'a Path) -> Option<Func
 
     state_stack.pop().map(|mut state| {
         state.space.name = path.to_str().map(|name| name.to_string());
+        let mut test_code = test_code::Stats::default();
+        test_code.compute(&state.space, &test_items);
+        state.space.metrics.test_code = test_code;
         state.space
     })
 }
@@ -0,0 +1,223 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use termcolor::{Color, ColorChoice, StandardStream};
+
+use crate::checker::Checker;
+use crate::getter::Getter;
+use crate::markers::{default_marker_regex, find_markers, marker_regex};
+use crate::node::Node;
+use crate::spaces::SpaceKind;
+use crate::tools::{color, intense_color};
+use crate::traits::*;
+
+/// A tech-debt marker, such as `TODO` or `FIXME`, found in a comment.
+#[derive(Clone, Debug, Serialize)]
+pub struct Marker {
+    /// The marker
+    pub marker: String,
+    /// The author of the marker, as `bob` in `TODO(bob): fix me`
+    pub author: Option<String>,
+    /// The issue referenced by the marker, as `#123` in `TODO: #123`
+    pub issue: Option<String>,
+    /// The text following the marker
+    pub text: String,
+    /// The line of the marker
+    pub line: usize,
+    /// The column of the marker
+    pub column: usize,
+    /// The name of the function space containing the marker
+    ///
+    /// If `None`, the marker is contained in the unit space of the file
+    /// or an error is occurred in parsing the name of the space
+    pub space: Option<String>,
+    /// The kind of the function space containing the marker
+    pub space_kind: SpaceKind,
+}
+
+/// The markers found in a file.
+#[derive(Debug, Serialize)]
+pub struct Markers {
+    /// The path of the file
+    pub file: PathBuf,
+    /// The markers in the file
+    pub markers: Vec<Marker>,
+}
+
+// Finds the innermost function space containing a node,
+// but the unit space of the file.
+fn enclosing_space<'a, T: ParserTrait>(node: &Node<'a>) -> Option<Node<'a>> {
+    let mut node = node.parent();
+    while let Some(n) = node {
+        if T::Checker::is_func(&n) || T::Checker::is_func_space(&n) {
+            return (T::Getter::get_space_kind(&n) != SpaceKind::Unit).then_some(n);
+        }
+        node = n.parent();
+    }
+    None
+}
+
+/// Finds the tech-debt markers contained in the comments of a code.
+///
+/// When `markers` is empty, the `TODO`, `FIXME`, `HACK` and `XXX`
+/// markers are searched.
+pub fn markers<T: ParserTrait>(parser: &T, markers: &[String]) -> Vec<Marker> {
+    let custom_regex = (!markers.is_empty()).then(|| marker_regex(markers));
+    let regex = custom_regex
+        .as_ref()
+        .unwrap_or_else(|| default_marker_regex());
+    let code = parser.get_code();
+    let root = parser.get_root();
+
+    let mut res = Vec::new();
+    root.visit(|node| {
+        if T::Checker::is_comment(node) {
+            let text = &code[node.start_byte()..node.end_byte()];
+            let space = enclosing_space::<T>(node);
+            let (row, column) = node.start_position();
+
+            for m in find_markers(text, regex) {
+                let before = &text[..m.offset];
+                let (line, column) = match before.iter().rposition(|&c| c == b'\n') {
+                    Some(pos) => (
+                        row + before.iter().filter(|&&c| c == b'\n').count(),
+                        m.offset - pos - 1,
+                    ),
+                    None => (row, column + m.offset),
+                };
+                res.push(Marker {
+                    marker: m.marker,
+                    author: m.author,
+                    issue: m.issue,
+                    text: m.text,
+                    line: line + 1,
+                    column: column + 1,
+                    space: space
+                        .and_then(|s| T::Getter::get_func_space_name(&s, code))
+                        .map(|name| name.to_owned()),
+                    space_kind: space.map_or(SpaceKind::Unit, |s| T::Getter::get_space_kind(&s)),
+                });
+            }
+            return false;
+        }
+        true
+    });
+
+    res
+}
+
+fn dump_markers(markers: &Markers) -> std::io::Result<()> {
+    if markers.markers.is_empty() {
+        return Ok(());
+    }
+
+    let stdout = StandardStream::stdout(ColorChoice::Always);
+    let mut stdout = stdout.lock();
+
+    intense_color(&mut stdout, Color::Yellow)?;
+    writeln!(stdout, "In file {}", markers.file.display())?;
+
+    for marker in &markers.markers {
+        color(&mut stdout, Color::Green)?;
+        write!(stdout, "   ({}, {}) ", marker.line, marker.column)?;
+
+        intense_color(&mut stdout, Color::Magenta)?;
+        write!(stdout, "{}", marker.marker)?;
+        if let Some(author) = &marker.author {
+            write!(stdout, "({author})")?;
+        }
+
+        color(&mut stdout, Color::White)?;
+        write!(stdout, ": {}", marker.text)?;
+
+        if let Some(space) = &marker.space {
+            intense_color(&mut stdout, Color::Cyan)?;
+            write!(stdout, " in {} {space}", marker.space_kind)?;
+        }
+        writeln!(stdout)?;
+    }
+
+    color(&mut stdout, Color::White)
+}
+
+/// Configuration options for finding the tech-debt markers of a code.
+#[derive(Debug)]
+pub struct MarkersCfg {
+    /// Path to the file containing the code
+    pub path: PathBuf,
+    /// The markers to find
+    ///
+    /// If empty, the `TODO`, `FIXME`, `HACK` and `XXX` markers are found
+    pub markers: Vec<String>,
+}
+
+/// Finds the tech-debt markers of a code, returning them as data.
+pub struct MarkersData {
+    _guard: (),
+}
+
+impl Callback for MarkersData {
+    type Res = Markers;
+    type Cfg = MarkersCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        Markers {
+            markers: markers(parser, &cfg.markers),
+            file: cfg.path,
+        }
+    }
+}
+
+/// Finds and prints the tech-debt markers of a code.
+pub struct MarkersDump {
+    _guard: (),
+}
+
+impl Callback for MarkersDump {
+    type Res = std::io::Result<()>;
+    type Cfg = MarkersCfg;
+
+    fn call<T: ParserTrait>(cfg: Self::Cfg, parser: &T) -> Self::Res {
+        dump_markers(&MarkersData::call(cfg, parser))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::RustParser;
+
+    use super::*;
+
+    #[test]
+    fn markers_in_spaces() {
+        let source = "// TODO(alice): split this file\n\
+                      fn foo() {\n    \
+                          /* The first line\n     \
+                             FIXME: #12 handle errors */\n    \
+                          let x = 1; // NOTE check x\n\
+                      }\n";
+        let parser = RustParser::new(source.as_bytes().to_vec(), Path::new("foo.rs"), None);
+
+        let found = markers(&parser, &[]);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].marker, "TODO");
+        assert_eq!(found[0].author.as_deref(), Some("alice"));
+        assert_eq!((found[0].line, found[0].column), (1, 4));
+        assert_eq!(found[0].space, None);
+        assert_eq!(found[0].space_kind, SpaceKind::Unit);
+        assert_eq!(found[1].marker, "FIXME");
+        assert_eq!(found[1].issue.as_deref(), Some("#12"));
+        assert_eq!(found[1].text, "#12 handle errors");
+        assert_eq!((found[1].line, found[1].column), (4, 6));
+        assert_eq!(found[1].space.as_deref(), Some("foo"));
+        assert_eq!(found[1].space_kind, SpaceKind::Function);
+
+        let found = markers(&parser, &["NOTE".to_owned()]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].text, "check x");
+        assert_eq!((found[0].line, found[0].column), (5, 19));
+    }
+}
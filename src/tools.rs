@@ -383,12 +383,22 @@ pub(crate) fn check_func_space<T: crate::ParserTrait, F: Fn(crate::FuncSpace)>(
     source: &str,
     filename: &str,
     check: F,
+) {
+    check_func_space_with_opts::<T, _>(source, filename, &crate::MetricsOpts::default(), check)
+}
+
+#[cfg(test)]
+fn check_func_space_with_opts<T: crate::ParserTrait, F: Fn(crate::FuncSpace)>(
+    source: &str,
+    filename: &str,
+    opts: &crate::MetricsOpts,
+    check: F,
 ) {
     let path = std::path::PathBuf::from(filename);
     let mut trimmed_bytes = source.trim_end().trim_matches('\n').as_bytes().to_vec();
     trimmed_bytes.push(b'\n');
     let parser = T::new(trimmed_bytes, &path, None);
-    let func_space = crate::metrics(&parser, &path).unwrap();
+    let func_space = crate::metrics_with_opts(&parser, &path, opts).unwrap();
 
     check(func_space)
 }
@@ -402,6 +412,18 @@ pub(crate) fn check_metrics<T: crate::ParserTrait>(
     check_func_space::<T, _>(source, filename, |func_space| check(func_space.metrics))
 }
 
+#[cfg(test)]
+pub(crate) fn check_metrics_with_opts<T: crate::ParserTrait>(
+    source: &str,
+    filename: &str,
+    opts: &crate::MetricsOpts,
+    check: fn(crate::CodeMetrics) -> (),
+) {
+    check_func_space_with_opts::<T, _>(source, filename, opts, |func_space| {
+        check(func_space.metrics)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
@@ -3,12 +3,15 @@ use std::sync::Arc;
 
 use crate::abc::Abc;
 use crate::alterator::Alterator;
+use crate::c_macro::SourceMap;
 use crate::checker::Checker;
 use crate::cognitive::Cognitive;
 use crate::cyclomatic::Cyclomatic;
+use crate::docs::Docs;
 use crate::exit::Exit;
 use crate::getter::Getter;
 use crate::halstead::Halstead;
+use crate::ifdef::Ifdef;
 use crate::langs::*;
 use crate::loc::Loc;
 use crate::mi::Mi;
@@ -58,11 +61,14 @@ pub trait ParserTrait {
     type Abc: Abc;
     type Npm: Npm;
     type Npa: Npa;
+    type Docs: Docs;
+    type Ifdef: Ifdef;
 
     fn new(code: Vec<u8>, path: &Path, pr: Option<Arc<PreprocResults>>) -> Self;
     fn get_language(&self) -> LANG;
     fn get_root(&self) -> Node<'_>;
     fn get_code(&self) -> &[u8];
+    fn get_source_map(&self) -> Option<&SourceMap>;
     fn get_filters(&self, filters: &[String]) -> Filter;
 }
 
@@ -19,12 +19,12 @@ struct Config {
     language: Option<LANG>,
 }
 
-fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<()> {
+fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<Option<FuncSpace>> {
     // Open file
     let source = if let Some(source) = read_file_with_eol(&path)? {
         source
     } else {
-        return Ok(());
+        return Ok(None);
     };
 
     // Guess programming language
@@ -33,12 +33,16 @@ fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<()> {
     } else if let Some(language) = guess_language(&source, &path).0 {
         language
     } else {
-        return Ok(());
+        return Ok(None);
     };
 
     // Get FuncSpace struct
     let funcspace_struct = get_function_spaces(&language, source, &path, None).unwrap();
 
+    Ok(Some(funcspace_struct))
+}
+
+fn check_func_space(path: &Path, funcspace_struct: FuncSpace) {
     insta::with_settings!({snapshot_path => Path::new(SNAPSHOT_PATH)
                 .join(path.strip_prefix(Path::new(REPO)).unwrap())
                 .parent()
@@ -60,8 +64,6 @@ fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<()> {
         );
 
     });
-
-    Ok(())
 }
 
 /// Produces metrics runtime and compares them with previously generated json files
@@ -86,8 +88,25 @@ pub fn compare_rca_output_with_files(repo_name: &str, include: &[&str], exclude:
         paths: vec![Path::new(REPO).join(repo_name)],
     };
 
-    if let Err(e) = ConcurrentRunner::new(num_jobs, act_on_file).run(cfg, files_data) {
-        eprintln!("{e:?}");
-        process::exit(1);
+    let results = match ConcurrentRunner::new(num_jobs, act_on_file)
+        .set_sorted(true)
+        .collect(cfg, files_data)
+    {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("{e:?}");
+            process::exit(1);
+        }
+    };
+
+    for (path, err) in &results.errors {
+        eprintln!("Error: {err} for file {path:?}");
+    }
+    assert!(results.errors.is_empty());
+
+    for (path, funcspace_struct) in results.values {
+        if let Some(funcspace_struct) = funcspace_struct {
+            check_func_space(&path, funcspace_struct);
+        }
     }
 }